@@ -55,62 +55,6 @@ fn sanity_check() {
     println!("Sanity check: Total records in encrypted_records: {}", count);
 }
 
-fn bench_db_encrypt(c: &mut Criterion) {
-    let conn = DB_CONN.lock().unwrap();
-    let key = [0u8; 32];
-
-    c.bench_function("db_encrypt", |b| {
-        b.iter(|| {
-            let mut stmt = conn.prepare("SELECT data FROM plain_records ORDER BY RANDOM() LIMIT 1").unwrap();
-            let record: String = stmt.query_row([], |row| row.get(0)).unwrap();
-            let record: serde_json::Value = serde_json::from_str(&record).unwrap();
-            let _encrypted = encryption::encrypt_fields(black_box(&record), black_box(&FIELDS_TO_ENCRYPT.iter().map(|&s| s.to_string()).collect::<Vec<String>>()), black_box(&key)).unwrap();
-        })
-    });
-}
-
-fn bench_db_decrypt(c: &mut Criterion) {
-    let conn = DB_CONN.lock().unwrap();
-    let key = [0u8; 32];
-
-    c.bench_function("db_decrypt", |b| {
-        b.iter(|| {
-            let mut stmt = conn.prepare("SELECT data FROM encrypted_records ORDER BY RANDOM() LIMIT 1").unwrap();
-            let encrypted_record: String = stmt.query_row([], |row| row.get(0)).unwrap();
-            let encrypted_record: serde_json::Value = serde_json::from_str(&encrypted_record).unwrap();
-            let _decrypted = encryption::decrypt_fields(black_box(&encrypted_record), black_box(&FIELDS_TO_ENCRYPT.iter().map(|&s| s.to_string()).collect::<Vec<String>>()), black_box(&key));
-        })
-    });
-}
-
-fn bench_db_encrypt_fields(c: &mut Criterion) {
-    let conn = DB_CONN.lock().unwrap();
-    let key = [0u8; 32];
-
-    c.bench_function("db_encrypt_fields", |b| {
-        b.iter(|| {
-            let mut stmt = conn.prepare("SELECT data FROM plain_records ORDER BY RANDOM() LIMIT 1").unwrap();
-            let record: String = stmt.query_row([], |row| row.get(0)).unwrap();
-            let record: serde_json::Value = serde_json::from_str(&record).unwrap();
-            let _encrypted = encryption::encrypt_fields(black_box(&record), black_box(&FIELDS_TO_ENCRYPT.iter().map(|&s| s.to_string()).collect::<Vec<String>>()), black_box(&key)).unwrap();
-        })
-    });
-}
-
-fn bench_db_decrypt_fields(c: &mut Criterion) {
-    let conn = DB_CONN.lock().unwrap();
-    let key = [0u8; 32];
-
-    c.bench_function("db_decrypt_fields", |b| {
-        b.iter(|| {
-            let mut stmt = conn.prepare("SELECT data FROM encrypted_records ORDER BY RANDOM() LIMIT 1").unwrap();
-            let encrypted_record: String = stmt.query_row([], |row| row.get(0)).unwrap();
-            let encrypted_record: serde_json::Value = serde_json::from_str(&encrypted_record).unwrap();
-            let _decrypted = encryption::decrypt_fields(black_box(&encrypted_record), black_box(&FIELDS_TO_ENCRYPT.iter().map(|&s| s.to_string()).collect::<Vec<String>>()), black_box(&key));
-        })
-    });
-}
-
 fn bench_db_encrypt_fields_in_batch(c: &mut Criterion) {
     let conn = DB_CONN.lock().unwrap();
     let key = [0u8; 32];
@@ -221,6 +165,7 @@ fn bench_db_ffi_encrypt_fields_in_batch(c: &mut Criterion) {
                 black_box(records_cstring.as_ptr()),
                 black_box(fields_cstring.as_ptr()),
                 black_box(key.as_ptr()),
+                black_box(key.len()),
             );
             ffi::free_ffi_result(result);
         })
@@ -249,6 +194,7 @@ fn bench_db_ffi_decrypt_fields_in_batch(c: &mut Criterion) {
         records_cstring.as_ptr(),
         fields_cstring.as_ptr(),
         key.as_ptr(),
+        key.len(),
     );
 
     c.bench_function(&format!("db_ffi_decrypt_fields_in_batch ({})", BATCH_SIZE), |b| {
@@ -258,6 +204,7 @@ fn bench_db_ffi_decrypt_fields_in_batch(c: &mut Criterion) {
                 black_box(encrypted.data.len),
                 black_box(fields_cstring.as_ptr()),
                 black_box(key.as_ptr()),
+                black_box(key.len()),
             );
             ffi::free_ffi_result(result);
         })
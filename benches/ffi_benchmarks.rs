@@ -11,6 +11,7 @@ fn bench_ffi_encrypt(c: &mut Criterion) {
                 black_box(plaintext.as_ptr()),
                 black_box(plaintext.len()),
                 black_box(key.as_ptr()),
+                black_box(key.len()),
             );
             ffi::free_ffi_result(result);
         })
@@ -20,13 +21,14 @@ fn bench_ffi_encrypt(c: &mut Criterion) {
 fn bench_ffi_decrypt(c: &mut Criterion) {
     let plaintext = b"Hello, world! This is a test message for benchmarking.";
     let key = [0u8; 32];
-    let encrypted = ffi::encrypt(plaintext.as_ptr(), plaintext.len(), key.as_ptr());
+    let encrypted = ffi::encrypt(plaintext.as_ptr(), plaintext.len(), key.as_ptr(), key.len());
     c.bench_function("ffi_decrypt", |b| {
         b.iter(|| {
             let result = ffi::decrypt(
                 black_box(encrypted.data.data),
                 black_box(encrypted.data.len),
                 black_box(key.as_ptr()),
+                black_box(key.len()),
             );
             ffi::free_ffi_result(result);
         })
@@ -47,6 +49,7 @@ fn bench_ffi_encrypt_fields(c: &mut Criterion) {
                 black_box(record_cstring.as_ptr()),
                 black_box(fields_cstring.as_ptr()),
                 black_box(key.as_ptr()),
+                black_box(key.len()),
             );
             ffi::free_ffi_result(result);
         })
@@ -64,6 +67,7 @@ fn bench_ffi_decrypt_fields(c: &mut Criterion) {
         record_cstring.as_ptr(),
         fields_cstring.as_ptr(),
         key.as_ptr(),
+        key.len(),
     );
 
     c.bench_function("ffi_decrypt_fields", |b| {
@@ -73,6 +77,7 @@ fn bench_ffi_decrypt_fields(c: &mut Criterion) {
                 black_box(encrypted.data.len),
                 black_box(fields_cstring.as_ptr()),
                 black_box(key.as_ptr()),
+                black_box(key.len()),
             );
             ffi::free_ffi_result(result);
         })
@@ -97,6 +102,7 @@ fn bench_ffi_encrypt_fields_in_batch(c: &mut Criterion) {
                 black_box(records_cstring.as_ptr()),
                 black_box(fields_cstring.as_ptr()),
                 black_box(key.as_ptr()),
+                black_box(key.len()),
             );
             ffi::free_ffi_result(result);
         })
@@ -117,6 +123,7 @@ fn bench_ffi_decrypt_fields_in_batch(c: &mut Criterion) {
         records_cstring.as_ptr(),
         fields_cstring.as_ptr(),
         key.as_ptr(),
+        key.len(),
     );
 
     c.bench_function("ffi_decrypt_fields_in_batch", |b| {
@@ -126,6 +133,7 @@ fn bench_ffi_decrypt_fields_in_batch(c: &mut Criterion) {
                 black_box(encrypted.data.len),
                 black_box(fields_cstring.as_ptr()),
                 black_box(key.as_ptr()),
+                black_box(key.len()),
             );
             ffi::free_ffi_result(result);
         })
@@ -12,7 +12,7 @@ func main() {
 	os.Setenv("RUST_LOG", "info")
 
 	// Initialize the logger
-	polycrypt.InitLogger()
+	polycrypt.InitLogger(nil)
 
 	plaintext := []byte("Hello, world!")
 	key := make([]byte, 32)
@@ -11,7 +11,7 @@ import (
 func TestEncryptDecrypt(t *testing.T) {
 	plaintext := []byte("Hello, world!")
 	key := make([]byte, 32)
-	// polycrypt.InitLogger()
+	// polycrypt.InitLogger(nil)
 	pc := polycrypt.NewPolyCrypt(key)
 
 	encrypted, err := pc.Encrypt(plaintext)
@@ -32,6 +32,54 @@ func TestEncryptDecrypt(t *testing.T) {
 	}
 }
 
+func TestEncryptDecryptEmpty(t *testing.T) {
+	pc := polycrypt.NewPolyCrypt(make([]byte, 32))
+
+	encrypted, err := pc.Encrypt(nil)
+	if err != nil {
+		t.Fatalf("Encryption of empty plaintext failed: %v", err)
+	}
+
+	decrypted, err := pc.Decrypt(encrypted)
+	if err != nil {
+		t.Fatalf("Decryption failed: %v", err)
+	}
+	if len(decrypted) != 0 {
+		t.Errorf("Expected empty plaintext, got %d bytes", len(decrypted))
+	}
+
+	if _, err := polycrypt.NewPolyCrypt(make([]byte, 16)).Encrypt([]byte("Hello")); err == nil {
+		t.Error("Encryption should fail with a 16-byte key")
+	}
+}
+
+func TestEncryptDecryptInto(t *testing.T) {
+	plaintext := []byte("Hello, world!")
+	pc := polycrypt.NewPolyCrypt(make([]byte, 32))
+
+	ciphertext := make([]byte, polycrypt.CiphertextLen(len(plaintext)))
+	n, err := pc.EncryptInto(plaintext, ciphertext)
+	if err != nil {
+		t.Fatalf("EncryptInto failed: %v", err)
+	}
+	if n != len(ciphertext) {
+		t.Fatalf("EncryptInto wrote %d bytes, expected %d", n, len(ciphertext))
+	}
+
+	decrypted := make([]byte, len(ciphertext))
+	n, err = pc.DecryptInto(ciphertext, decrypted)
+	if err != nil {
+		t.Fatalf("DecryptInto failed: %v", err)
+	}
+	if !reflect.DeepEqual(plaintext, decrypted[:n]) {
+		t.Errorf("Decrypted text does not match original plaintext")
+	}
+
+	if _, err := pc.EncryptInto(plaintext, make([]byte, 16)); err == nil {
+		t.Error("EncryptInto should fail with a short buffer")
+	}
+}
+
 func TestFieldEncryptionDecryption(t *testing.T) {
 	record := map[string]interface{}{
 		"id":             "1234",
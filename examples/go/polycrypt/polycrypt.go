@@ -17,22 +17,74 @@ typedef struct {
     int32_t error_code;
 } FFIResult;
 
-FFIResult encrypt(const uint8_t* plaintext, uintptr_t plaintext_len, const uint8_t* key);
-FFIResult decrypt(const uint8_t* ciphertext, uintptr_t ciphertext_len, const uint8_t* key);
-FFIResult encrypt_fields(const char* record, const char* fields_to_encrypt, const uint8_t* key);
-FFIResult decrypt_fields(const uint8_t* encrypted, uintptr_t encrypted_len, const char* fields_to_decrypt, const uint8_t* key);
-FFIResult encrypt_fields_in_batch(const char* records, const char* fields_to_encrypt, const uint8_t* key);
-FFIResult decrypt_fields_in_batch(const uint8_t* encrypted, uintptr_t encrypted_len, const char* fields_to_decrypt, const uint8_t* key);
+FFIResult encrypt(const uint8_t* plaintext, uintptr_t plaintext_len, const uint8_t* key, uintptr_t key_len);
+FFIResult decrypt(const uint8_t* ciphertext, uintptr_t ciphertext_len, const uint8_t* key, uintptr_t key_len);
+uintptr_t ciphertext_len(uintptr_t plaintext_len);
+int32_t encrypt_into(const uint8_t* plaintext, uintptr_t plaintext_len, const uint8_t* key, uintptr_t key_len, uint8_t* out, uintptr_t out_len, uintptr_t* written);
+int32_t decrypt_into(const uint8_t* ciphertext, uintptr_t ciphertext_len, const uint8_t* key, uintptr_t key_len, uint8_t* out, uintptr_t out_len, uintptr_t* written);
+FFIResult encrypt_fields(const char* record, const char* fields_to_encrypt, const uint8_t* key, uintptr_t key_len);
+FFIResult decrypt_fields(const uint8_t* encrypted, uintptr_t encrypted_len, const char* fields_to_decrypt, const uint8_t* key, uintptr_t key_len);
+FFIResult encrypt_fields_in_batch(const char* records, const char* fields_to_encrypt, const uint8_t* key, uintptr_t key_len);
+FFIResult decrypt_fields_in_batch(const uint8_t* encrypted, uintptr_t encrypted_len, const char* fields_to_decrypt, const uint8_t* key, uintptr_t key_len);
+FFIResult encrypt_fields_bytes(const uint8_t* record, uintptr_t record_len, const uint8_t* fields_to_encrypt, uintptr_t fields_len, const uint8_t* key, uintptr_t key_len);
+FFIResult decrypt_fields_bytes(const uint8_t* encrypted, uintptr_t encrypted_len, const uint8_t* fields_to_decrypt, uintptr_t fields_len, const uint8_t* key, uintptr_t key_len);
+FFIResult encrypt_fields_in_batch_bytes(const uint8_t* records, uintptr_t records_len, const uint8_t* fields_to_encrypt, uintptr_t fields_len, const uint8_t* key, uintptr_t key_len);
+FFIResult decrypt_fields_in_batch_bytes(const uint8_t* encrypted, uintptr_t encrypted_len, const uint8_t* fields_to_decrypt, uintptr_t fields_len, const uint8_t* key, uintptr_t key_len);
+FFIResult mask_fields(const char* record, const char* masks);
+FFIResult decrypt_and_mask_fields(const uint8_t* encrypted, uintptr_t encrypted_len, const char* masks, const uint8_t* key, uintptr_t key_len);
+FFIResult pseudonymize_fields(const char* record, const char* fields_to_pseudonymize, const char* domain, const uint8_t* key, uintptr_t key_len);
+FFIResult inspect_ciphertext(const uint8_t* ciphertext, uintptr_t ciphertext_len);
+FFIResult phi_fields_from_schema(const char* schema);
+FFIResult metrics_snapshot();
 void free_ffi_result(FFIResult result);
-void init_logger();
+FFIResult init_logger(const char* config);
+typedef void (*LogCallback)(const char* level, const char* message, const char* context, void* user_data);
+void set_log_callback(LogCallback callback, void* user_data);
+extern void goLogCallback(char* level, char* message, char* context, void* user_data);
 */
 import "C"
 import (
 	"encoding/json"
 	"errors"
+	"fmt"
+	"sync"
 	"unsafe"
 )
 
+// Error codes reported by the library in FFIResult.error_code.
+const (
+	ErrCodeFailed      = -1
+	ErrCodeInvalidUTF8 = -2
+	ErrCodeInvalidJSON = -3
+	ErrCodeNullPointer = -4
+	ErrCodeInvalidKey  = -5
+)
+
+// bytesPtr returns a pointer to the first byte of b, or nil when b is empty, which the
+// library accepts for zero-length input.
+func bytesPtr(b []byte) *C.uint8_t {
+	if len(b) == 0 {
+		return nil
+	}
+	return (*C.uint8_t)(unsafe.Pointer(&b[0]))
+}
+
+// ffiError describes a failed call of operation by its error code.
+func ffiError(operation string, code C.int32_t) error {
+	switch code {
+	case ErrCodeInvalidUTF8:
+		return fmt.Errorf("%s failed: invalid UTF-8", operation)
+	case ErrCodeInvalidJSON:
+		return fmt.Errorf("%s failed: invalid JSON", operation)
+	case ErrCodeNullPointer:
+		return fmt.Errorf("%s failed: null pointer", operation)
+	case ErrCodeInvalidKey:
+		return fmt.Errorf("%s failed: invalid key length", operation)
+	default:
+		return errors.New(operation + " failed")
+	}
+}
+
 type PolyCrypt struct {
 	key []byte
 }
@@ -41,12 +93,84 @@ func NewPolyCrypt(key []byte) *PolyCrypt {
 	return &PolyCrypt{key: key}
 }
 
-func InitLogger() {
-	C.init_logger()
+// InitLogger configures library logging from a config such as
+// {"level": "debug", "format": "text", "destination": {"file": "polycrypt.log"}, "context": {...}}.
+// A nil config selects info-level JSON lines on stderr. It may be called again to reconfigure.
+func InitLogger(config map[string]interface{}) error {
+	var cConfig *C.char
+	if config != nil {
+		configJSON, err := json.Marshal(config)
+		if err != nil {
+			return err
+		}
+		cConfig = C.CString(string(configJSON))
+		defer C.free(unsafe.Pointer(cConfig))
+	}
+
+	result := C.init_logger(cConfig)
+	defer C.free_ffi_result(result)
+
+	if result.error_code != 0 {
+		return errors.New("logger configuration failed")
+	}
+	return nil
+}
+
+// MetricsSnapshot returns call counts, error counts and latency histograms per operation.
+func MetricsSnapshot() ([]map[string]interface{}, error) {
+	result := C.metrics_snapshot()
+	defer C.free_ffi_result(result)
+
+	if result.error_code != 0 {
+		return nil, errors.New("metrics snapshot failed")
+	}
+
+	snapshotJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
+	var snapshot []map[string]interface{}
+	if err := json.Unmarshal(snapshotJSON, &snapshot); err != nil {
+		return nil, err
+	}
+	return snapshot, nil
+}
+
+// LogHandler receives library log entries, e.g. to forward them to zap.
+type LogHandler func(level, message string, context map[string]interface{})
+
+var (
+	logHandlerMu sync.RWMutex
+	logHandler   LogHandler
+)
+
+//export goLogCallback
+func goLogCallback(level, message, context *C.char, _ unsafe.Pointer) {
+	logHandlerMu.RLock()
+	handler := logHandler
+	logHandlerMu.RUnlock()
+	if handler == nil {
+		return
+	}
+
+	var fields map[string]interface{}
+	_ = json.Unmarshal([]byte(C.GoString(context)), &fields)
+	handler(C.GoString(level), C.GoString(message), fields)
+}
+
+// SetLogHandler routes library log entries to handler, or stops routing them when handler
+// is nil. The handler may be called concurrently from library worker threads.
+func SetLogHandler(handler LogHandler) {
+	logHandlerMu.Lock()
+	logHandler = handler
+	logHandlerMu.Unlock()
+
+	if handler == nil {
+		C.set_log_callback(nil, nil)
+	} else {
+		C.set_log_callback(C.LogCallback(C.goLogCallback), nil)
+	}
 }
 
 func (pc *PolyCrypt) Encrypt(plaintext []byte) ([]byte, error) {
-	result := C.encrypt((*C.uint8_t)(&plaintext[0]), C.uintptr_t(len(plaintext)), (*C.uint8_t)(&pc.key[0]))
+	result := C.encrypt(bytesPtr(plaintext), C.uintptr_t(len(plaintext)), bytesPtr(pc.key), C.uintptr_t(len(pc.key)))
 	defer C.free_ffi_result(result)
 
 	if result.error_code != 0 {
@@ -57,7 +181,7 @@ func (pc *PolyCrypt) Encrypt(plaintext []byte) ([]byte, error) {
 }
 
 func (pc *PolyCrypt) Decrypt(ciphertext []byte) ([]byte, error) {
-	result := C.decrypt((*C.uint8_t)(&ciphertext[0]), C.uintptr_t(len(ciphertext)), (*C.uint8_t)(&pc.key[0]))
+	result := C.decrypt(bytesPtr(ciphertext), C.uintptr_t(len(ciphertext)), bytesPtr(pc.key), C.uintptr_t(len(pc.key)))
 	defer C.free_ffi_result(result)
 
 	if result.error_code != 0 {
@@ -67,6 +191,37 @@ func (pc *PolyCrypt) Decrypt(ciphertext []byte) ([]byte, error) {
 	return C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len)), nil
 }
 
+// CiphertextLen returns the number of bytes Encrypt and EncryptInto produce for
+// plaintextLen bytes of plaintext.
+func CiphertextLen(plaintextLen int) int {
+	return int(C.ciphertext_len(C.uintptr_t(plaintextLen)))
+}
+
+// EncryptInto encrypts plaintext into out, which must hold at least
+// CiphertextLen(len(plaintext)) bytes, and returns the number of bytes written.
+// Unlike Encrypt it neither allocates nor copies, so out can be reused across calls.
+func (pc *PolyCrypt) EncryptInto(plaintext, out []byte) (int, error) {
+	var written C.uintptr_t
+	code := C.encrypt_into(bytesPtr(plaintext), C.uintptr_t(len(plaintext)), bytesPtr(pc.key), C.uintptr_t(len(pc.key)),
+		bytesPtr(out), C.uintptr_t(len(out)), &written)
+	if code != 0 {
+		return 0, errors.New("encryption failed")
+	}
+	return int(written), nil
+}
+
+// DecryptInto decrypts ciphertext into out, which must hold at least len(ciphertext)-16
+// bytes, and returns the number of plaintext bytes written.
+func (pc *PolyCrypt) DecryptInto(ciphertext, out []byte) (int, error) {
+	var written C.uintptr_t
+	code := C.decrypt_into(bytesPtr(ciphertext), C.uintptr_t(len(ciphertext)), bytesPtr(pc.key), C.uintptr_t(len(pc.key)),
+		bytesPtr(out), C.uintptr_t(len(out)), &written)
+	if code != 0 {
+		return 0, errors.New("decryption failed")
+	}
+	return int(written), nil
+}
+
 func (pc *PolyCrypt) EncryptFields(record map[string]interface{}, fieldsToEncrypt []string) (map[string]interface{}, error) {
 	recordJSON, err := json.Marshal(record)
 	if err != nil {
@@ -78,17 +233,12 @@ func (pc *PolyCrypt) EncryptFields(record map[string]interface{}, fieldsToEncryp
 		return nil, err
 	}
 
-	cRecord := C.CString(string(recordJSON))
-	defer C.free(unsafe.Pointer(cRecord))
-
-	cFields := C.CString(string(fieldsJSON))
-	defer C.free(unsafe.Pointer(cFields))
-
-	result := C.encrypt_fields(cRecord, cFields, (*C.uint8_t)(&pc.key[0]))
+	result := C.encrypt_fields_bytes(bytesPtr(recordJSON), C.uintptr_t(len(recordJSON)),
+		bytesPtr(fieldsJSON), C.uintptr_t(len(fieldsJSON)), bytesPtr(pc.key), C.uintptr_t(len(pc.key)))
 	defer C.free_ffi_result(result)
 
 	if result.error_code != 0 {
-		return nil, errors.New("field encryption failed")
+		return nil, ffiError("field encryption", result.error_code)
 	}
 
 	encryptedJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
@@ -112,14 +262,12 @@ func (pc *PolyCrypt) DecryptFields(encryptedRecord map[string]interface{}, field
 		return nil, err
 	}
 
-	cFields := C.CString(string(fieldsJSON))
-	defer C.free(unsafe.Pointer(cFields))
-
-	result := C.decrypt_fields((*C.uint8_t)(&encryptedJSON[0]), C.uintptr_t(len(encryptedJSON)), cFields, (*C.uint8_t)(&pc.key[0]))
+	result := C.decrypt_fields_bytes(bytesPtr(encryptedJSON), C.uintptr_t(len(encryptedJSON)),
+		bytesPtr(fieldsJSON), C.uintptr_t(len(fieldsJSON)), bytesPtr(pc.key), C.uintptr_t(len(pc.key)))
 	defer C.free_ffi_result(result)
 
 	if result.error_code != 0 {
-		return nil, errors.New("field decryption failed")
+		return nil, ffiError("field decryption", result.error_code)
 	}
 
 	decryptedJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
@@ -154,17 +302,12 @@ func (pc *PolyCrypt) EncryptFieldsInBatch(records []map[string]interface{}, fiel
 		return nil, err
 	}
 
-	cRecords := C.CString(string(recordsJSON))
-	defer C.free(unsafe.Pointer(cRecords))
-
-	cFields := C.CString(string(fieldsJSON))
-	defer C.free(unsafe.Pointer(cFields))
-
-	result := C.encrypt_fields_in_batch(cRecords, cFields, (*C.uint8_t)(&pc.key[0]))
+	result := C.encrypt_fields_in_batch_bytes(bytesPtr(recordsJSON), C.uintptr_t(len(recordsJSON)),
+		bytesPtr(fieldsJSON), C.uintptr_t(len(fieldsJSON)), bytesPtr(pc.key), C.uintptr_t(len(pc.key)))
 	defer C.free_ffi_result(result)
 
 	if result.error_code != 0 {
-		return nil, errors.New("batch field encryption failed")
+		return nil, ffiError("batch field encryption", result.error_code)
 	}
 
 	encryptedJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
@@ -188,14 +331,12 @@ func (pc *PolyCrypt) DecryptFieldsInBatch(encryptedRecords []map[string]interfac
 		return nil, err
 	}
 
-	cFields := C.CString(string(fieldsJSON))
-	defer C.free(unsafe.Pointer(cFields))
-
-	result := C.decrypt_fields_in_batch((*C.uint8_t)(&encryptedJSON[0]), C.uintptr_t(len(encryptedJSON)), cFields, (*C.uint8_t)(&pc.key[0]))
+	result := C.decrypt_fields_in_batch_bytes(bytesPtr(encryptedJSON), C.uintptr_t(len(encryptedJSON)),
+		bytesPtr(fieldsJSON), C.uintptr_t(len(fieldsJSON)), bytesPtr(pc.key), C.uintptr_t(len(pc.key)))
 	defer C.free_ffi_result(result)
 
 	if result.error_code != 0 {
-		return nil, errors.New("batch field decryption failed")
+		return nil, ffiError("batch field decryption", result.error_code)
 	}
 
 	decryptedJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
@@ -220,3 +361,175 @@ func (pc *PolyCrypt) DecryptFieldsInBatch(encryptedRecords []map[string]interfac
 
 	return decryptedRecords, nil
 }
+
+// DecryptAndMaskFields decrypts the fields named in masks and masks them, so callers never
+// see the full plaintext. Masks map field paths to modes such as "redact", "email", "year"
+// or {"keep_last": 4}.
+func (pc *PolyCrypt) DecryptAndMaskFields(encryptedRecord map[string]interface{}, masks map[string]interface{}) (map[string]interface{}, error) {
+	encryptedJSON, err := json.Marshal(encryptedRecord)
+	if err != nil {
+		return nil, err
+	}
+
+	masksJSON, err := json.Marshal(masks)
+	if err != nil {
+		return nil, err
+	}
+
+	cMasks := C.CString(string(masksJSON))
+	defer C.free(unsafe.Pointer(cMasks))
+
+	result := C.decrypt_and_mask_fields(bytesPtr(encryptedJSON), C.uintptr_t(len(encryptedJSON)), cMasks, bytesPtr(pc.key), C.uintptr_t(len(pc.key)))
+	defer C.free_ffi_result(result)
+
+	if result.error_code != 0 {
+		return nil, errors.New("field decryption and masking failed")
+	}
+
+	maskedJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
+	var maskedRecord map[string]interface{}
+	err = json.Unmarshal(maskedJSON, &maskedRecord)
+	if err != nil {
+		return nil, err
+	}
+
+	return maskedRecord, nil
+}
+
+// MaskFields masks the fields of a plaintext record.
+func MaskFields(record map[string]interface{}, masks map[string]interface{}) (map[string]interface{}, error) {
+	recordJSON, err := json.Marshal(record)
+	if err != nil {
+		return nil, err
+	}
+
+	masksJSON, err := json.Marshal(masks)
+	if err != nil {
+		return nil, err
+	}
+
+	cRecord := C.CString(string(recordJSON))
+	defer C.free(unsafe.Pointer(cRecord))
+
+	cMasks := C.CString(string(masksJSON))
+	defer C.free(unsafe.Pointer(cMasks))
+
+	result := C.mask_fields(cRecord, cMasks)
+	defer C.free_ffi_result(result)
+
+	if result.error_code != 0 {
+		return nil, errors.New("field masking failed")
+	}
+
+	maskedJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
+	var maskedRecord map[string]interface{}
+	err = json.Unmarshal(maskedJSON, &maskedRecord)
+	if err != nil {
+		return nil, err
+	}
+
+	return maskedRecord, nil
+}
+
+// PseudonymizeFields replaces the given fields with keyed pseudonyms. The same value, domain
+// and key always yield the same pseudonym; different domains yield unrelated ones.
+func (pc *PolyCrypt) PseudonymizeFields(record map[string]interface{}, fieldsToPseudonymize []string, domain string) (map[string]interface{}, error) {
+	recordJSON, err := json.Marshal(record)
+	if err != nil {
+		return nil, err
+	}
+
+	fieldsJSON, err := json.Marshal(fieldsToPseudonymize)
+	if err != nil {
+		return nil, err
+	}
+
+	cRecord := C.CString(string(recordJSON))
+	defer C.free(unsafe.Pointer(cRecord))
+
+	cFields := C.CString(string(fieldsJSON))
+	defer C.free(unsafe.Pointer(cFields))
+
+	cDomain := C.CString(domain)
+	defer C.free(unsafe.Pointer(cDomain))
+
+	result := C.pseudonymize_fields(cRecord, cFields, cDomain, bytesPtr(pc.key), C.uintptr_t(len(pc.key)))
+	defer C.free_ffi_result(result)
+
+	if result.error_code != 0 {
+		return nil, errors.New("field pseudonymization failed")
+	}
+
+	pseudonymizedJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
+	var pseudonymizedRecord map[string]interface{}
+	err = json.Unmarshal(pseudonymizedJSON, &pseudonymizedRecord)
+	if err != nil {
+		return nil, err
+	}
+
+	return pseudonymizedRecord, nil
+}
+
+// CiphertextInfo describes a ciphertext without decrypting it.
+type CiphertextInfo struct {
+	Format        string  `json:"format"`
+	Version       int     `json:"version"`
+	Algorithm     string  `json:"algorithm"`
+	KeyID         *string `json:"key_id"`
+	Deterministic bool    `json:"deterministic"`
+	NonceLen      int     `json:"nonce_len"`
+	PayloadLen    int     `json:"payload_len"`
+	Base64        bool    `json:"base64"`
+}
+
+// InspectCiphertext reports the format version, algorithm and key id of a binary ciphertext
+// or base64 field value. Legacy IV-prefixed ciphertexts are reported with format "legacy".
+func InspectCiphertext(ciphertext []byte) (*CiphertextInfo, error) {
+	if len(ciphertext) == 0 {
+		return nil, errors.New("empty ciphertext")
+	}
+
+	result := C.inspect_ciphertext(bytesPtr(ciphertext), C.uintptr_t(len(ciphertext)))
+	defer C.free_ffi_result(result)
+
+	if result.error_code != 0 {
+		return nil, errors.New("ciphertext inspection failed")
+	}
+
+	infoJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
+	var info CiphertextInfo
+	err := json.Unmarshal(infoJSON, &info)
+	if err != nil {
+		return nil, err
+	}
+
+	return &info, nil
+}
+
+// PhiFieldsFromSchema returns the field paths annotated with "x-phi": true in a JSON Schema,
+// ready to pass to EncryptFields and the batch functions.
+func PhiFieldsFromSchema(schema map[string]interface{}) ([]string, error) {
+	schemaJSON, err := json.Marshal(schema)
+	if err != nil {
+		return nil, err
+	}
+
+	cSchema := C.CString(string(schemaJSON))
+	defer C.free(unsafe.Pointer(cSchema))
+
+	result := C.phi_fields_from_schema(cSchema)
+	defer C.free_ffi_result(result)
+
+	if result.error_code != 0 {
+		return nil, errors.New("schema field discovery failed")
+	}
+
+	fieldsJSON := C.GoBytes(unsafe.Pointer(result.data.data), C.int(result.data.len))
+	var fields []string
+	err = json.Unmarshal(fieldsJSON, &fields)
+	if err != nil {
+		return nil, err
+	}
+
+	return fields, nil
+}
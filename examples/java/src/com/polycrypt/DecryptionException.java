@@ -0,0 +1,8 @@
+package com.polycrypt;
+
+/** A value could not be decrypted, e.g. because it is corrupt or the key is wrong. */
+public class DecryptionException extends PolyCryptException {
+    public DecryptionException(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,8 @@
+package com.polycrypt;
+
+/** A value could not be encrypted. */
+public class EncryptionException extends PolyCryptException {
+    public EncryptionException(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,8 @@
+package com.polycrypt;
+
+/** A key is not 32 bytes long. */
+public class InvalidKeyException extends PolyCryptException {
+    public InvalidKeyException(String message) {
+        super(message);
+    }
+}
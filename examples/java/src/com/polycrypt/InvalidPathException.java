@@ -0,0 +1,8 @@
+package com.polycrypt;
+
+/** A field path could not be parsed. */
+public class InvalidPathException extends PolyCryptException {
+    public InvalidPathException(String message) {
+        super(message);
+    }
+}
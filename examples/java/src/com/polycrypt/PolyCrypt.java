@@ -0,0 +1,32 @@
+package com.polycrypt;
+
+/**
+ * Java bindings for polycrypt-rs.
+ *
+ * <p>Data is passed as {@code byte[]} and records as JSON strings, so the ciphertext format is
+ * the same as the Go and Python wrappers produce. Keys are 32 bytes. Failures are thrown as
+ * subclasses of {@link PolyCryptException}.
+ */
+public final class PolyCrypt {
+    static {
+        System.loadLibrary("polycrypt_rs");
+    }
+
+    private PolyCrypt() {}
+
+    public static native byte[] encrypt(byte[] plaintext, byte[] key);
+
+    public static native byte[] decrypt(byte[] ciphertext, byte[] key);
+
+    /** Encrypts the named fields of a JSON record and returns the record as JSON. */
+    public static native String encryptFields(String recordJson, String[] fields, byte[] key);
+
+    /** Decrypts the named fields of a JSON record and returns the record as JSON. */
+    public static native String decryptFields(String recordJson, String[] fields, byte[] key);
+
+    /** Encrypts the named fields of every record in a JSON array. */
+    public static native String encryptFieldsInBatch(String recordsJson, String[] fields, byte[] key);
+
+    /** Decrypts the named fields of every record in a JSON array. */
+    public static native String decryptFieldsInBatch(String recordsJson, String[] fields, byte[] key);
+}
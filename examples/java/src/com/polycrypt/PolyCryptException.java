@@ -0,0 +1,8 @@
+package com.polycrypt;
+
+/** Base class of every error thrown by polycrypt-rs. */
+public class PolyCryptException extends RuntimeException {
+    public PolyCryptException(String message) {
+        super(message);
+    }
+}
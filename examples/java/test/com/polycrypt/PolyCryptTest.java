@@ -0,0 +1,85 @@
+package com.polycrypt;
+
+import java.nio.charset.StandardCharsets;
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.nio.file.Paths;
+import java.util.Arrays;
+
+/**
+ * Tests for the Java bindings.
+ *
+ * <p>Takes the directory holding {@code record.json} and {@code record.encrypted.json}, the
+ * latter written by the Rust {@code encrypt_fields} through the {@code polycrypt} CLI, so it
+ * also proves Java reads the ciphertext the other languages produce. Run with
+ * {@code make java-test}.
+ */
+public final class PolyCryptTest {
+    private static final byte[] KEY = "00000000000000000000000000000000".getBytes(StandardCharsets.US_ASCII);
+    private static final String[] FIELDS = {"name", "ssn", "address.zip"};
+
+    public static void main(String[] args) throws Exception {
+        Path testdata = Paths.get(args[0]);
+        String record = read(testdata.resolve("record.json"));
+
+        testEncryptDecrypt();
+        testEncryptDecryptFields(record);
+        testBatch(record);
+        testDecryptRustCiphertext(record, read(testdata.resolve("record.encrypted.json")));
+        testExceptions(record);
+        System.out.println("All Java tests passed.");
+    }
+
+    private static void testEncryptDecrypt() {
+        byte[] plaintext = "Hello, world!".getBytes(StandardCharsets.UTF_8);
+        byte[] encrypted = PolyCrypt.encrypt(plaintext, KEY);
+        check(!Arrays.equals(encrypted, plaintext), "ciphertext differs from plaintext");
+        check(Arrays.equals(PolyCrypt.decrypt(encrypted, KEY), plaintext), "round trip");
+    }
+
+    private static void testEncryptDecryptFields(String record) {
+        String encrypted = PolyCrypt.encryptFields(record, FIELDS, KEY);
+        check(!encrypted.contains("John Doe"), "name is encrypted");
+        check(encrypted.contains("\"id\":\"1234\""), "id is left alone");
+        check(PolyCrypt.decryptFields(encrypted, FIELDS, KEY).equals(record), "fields round trip");
+    }
+
+    private static void testBatch(String record) {
+        String records = "[" + record + "," + record + "]";
+        String encrypted = PolyCrypt.encryptFieldsInBatch(records, FIELDS, KEY);
+        check(PolyCrypt.decryptFieldsInBatch(encrypted, FIELDS, KEY).equals(records), "batch round trip");
+    }
+
+    private static void testDecryptRustCiphertext(String record, String encrypted) {
+        check(!encrypted.contains("John Doe"), "Rust output is encrypted");
+        check(PolyCrypt.decryptFields(encrypted, FIELDS, KEY).equals(record), "decrypts Rust output");
+    }
+
+    private static void testExceptions(String record) {
+        expect(InvalidKeyException.class, () -> PolyCrypt.encrypt(new byte[] {1}, new byte[16]));
+        expect(DecryptionException.class, () -> PolyCrypt.decrypt("not ciphertext".getBytes(StandardCharsets.UTF_8), KEY));
+        expect(InvalidPathException.class, () -> PolyCrypt.encryptFields(record, new String[] {"a..b"}, KEY));
+        expect(IllegalArgumentException.class, () -> PolyCrypt.encryptFields("{", FIELDS, KEY));
+        expect(NullPointerException.class, () -> PolyCrypt.encrypt(null, KEY));
+    }
+
+    private static String read(Path path) throws Exception {
+        return new String(Files.readAllBytes(path), StandardCharsets.UTF_8).trim();
+    }
+
+    private static void check(boolean condition, String description) {
+        if (!condition) {
+            throw new AssertionError("Failed: " + description);
+        }
+    }
+
+    private static void expect(Class<? extends RuntimeException> expected, Runnable call) {
+        try {
+            call.run();
+        } catch (RuntimeException e) {
+            check(expected.isInstance(e), "expected " + expected.getSimpleName() + ", got " + e);
+            return;
+        }
+        throw new AssertionError("Failed: expected " + expected.getSimpleName());
+    }
+}
@@ -0,0 +1,32 @@
+/// <reference types="node" />
+
+/** A JSON record; field paths such as `address.zip` or `phones[*]` select values inside it. */
+export type JsonRecord = { [key: string]: unknown };
+
+/** Encrypts `plaintext` with a 32-byte AES-256 key. */
+export function encrypt(plaintext: Buffer, key: Buffer): Buffer;
+/** Decrypts a buffer produced by `encrypt`. */
+export function decrypt(ciphertext: Buffer, key: Buffer): Buffer;
+
+/** Encrypts the named fields of a record, leaving already encrypted values unchanged. */
+export function encryptFields(record: JsonRecord, fields: string[], key: Buffer): JsonRecord;
+/** Decrypts the named fields of a record, leaving values that are not encrypted unchanged. */
+export function decryptFields(record: JsonRecord, fields: string[], key: Buffer): JsonRecord;
+
+/** Encrypts the named fields of every record. */
+export function encryptFieldsInBatch(records: JsonRecord[], fields: string[], key: Buffer): JsonRecord[];
+/** Decrypts the named fields of every record. */
+export function decryptFieldsInBatch(records: JsonRecord[], fields: string[], key: Buffer): JsonRecord[];
+
+/** Like `encrypt`, but runs on the libuv worker pool. */
+export function encryptAsync(plaintext: Buffer, key: Buffer): Promise<Buffer>;
+/** Like `decrypt`, but runs on the libuv worker pool. */
+export function decryptAsync(ciphertext: Buffer, key: Buffer): Promise<Buffer>;
+/** Like `encryptFields`, but runs on the libuv worker pool. */
+export function encryptFieldsAsync(record: JsonRecord, fields: string[], key: Buffer): Promise<JsonRecord>;
+/** Like `decryptFields`, but runs on the libuv worker pool. */
+export function decryptFieldsAsync(record: JsonRecord, fields: string[], key: Buffer): Promise<JsonRecord>;
+/** Like `encryptFieldsInBatch`, but runs on the libuv worker pool. */
+export function encryptFieldsInBatchAsync(records: JsonRecord[], fields: string[], key: Buffer): Promise<JsonRecord[]>;
+/** Like `decryptFieldsInBatch`, but runs on the libuv worker pool. */
+export function decryptFieldsInBatchAsync(records: JsonRecord[], fields: string[], key: Buffer): Promise<JsonRecord[]>;
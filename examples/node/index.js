@@ -0,0 +1,4 @@
+'use strict';
+
+// Loads the N-API addon built by `make node-build`.
+module.exports = require('./polycrypt_rs.node');
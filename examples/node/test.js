@@ -0,0 +1,58 @@
+'use strict';
+
+const assert = require('node:assert/strict');
+const test = require('node:test');
+
+const polycrypt = require('./index');
+
+const key = Buffer.alloc(32, '0');
+
+test('encrypt and decrypt', () => {
+  const encrypted = polycrypt.encrypt(Buffer.from('Hello, world!'), key);
+  assert.ok(Buffer.isBuffer(encrypted));
+  assert.equal(polycrypt.decrypt(encrypted, key).toString(), 'Hello, world!');
+});
+
+test('encrypt and decrypt fields', () => {
+  const record = {
+    id: 1234,
+    name: 'John Doe',
+    active: true,
+    score: 9.5,
+    notes: null,
+    address: { zip: '12345' },
+    phones: ['555-0100', '555-0101'],
+  };
+  const fields = ['name', 'address.zip', 'phones'];
+
+  const encrypted = polycrypt.encryptFields(record, fields, key);
+  assert.notEqual(encrypted.name, 'John Doe');
+  assert.equal(encrypted.id, 1234);
+  assert.deepEqual(polycrypt.decryptFields(encrypted, fields, key), record);
+});
+
+test('batch operations', () => {
+  const records = Array.from({ length: 100 }, (_, i) => ({ id: String(i), name: `Patient ${i}` }));
+  const encrypted = polycrypt.encryptFieldsInBatch(records, ['name'], key);
+  assert.equal(encrypted.length, 100);
+  assert.deepEqual(polycrypt.decryptFieldsInBatch(encrypted, ['name'], key), records);
+});
+
+test('async variants', async () => {
+  const encrypted = await polycrypt.encryptAsync(Buffer.from('Hello, world!'), key);
+  assert.equal((await polycrypt.decryptAsync(encrypted, key)).toString(), 'Hello, world!');
+
+  const record = { id: '1', ssn: '123-45-6789' };
+  const encryptedRecord = await polycrypt.encryptFieldsAsync(record, ['ssn'], key);
+  assert.deepEqual(await polycrypt.decryptFieldsAsync(encryptedRecord, ['ssn'], key), record);
+
+  const records = Array.from({ length: 1000 }, (_, i) => ({ id: String(i), name: `Patient ${i}` }));
+  const batch = await polycrypt.encryptFieldsInBatchAsync(records, ['name'], key);
+  assert.deepEqual(await polycrypt.decryptFieldsInBatchAsync(batch, ['name'], key), records);
+});
+
+test('errors', async () => {
+  assert.throws(() => polycrypt.encrypt(Buffer.from('data'), Buffer.alloc(16)), /expected 32 bytes/);
+  assert.throws(() => polycrypt.decrypt(Buffer.from('not ciphertext'), key), /Decryption error/);
+  await assert.rejects(polycrypt.decryptAsync(Buffer.from('not ciphertext'), key), /Decryption error/);
+});
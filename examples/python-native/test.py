@@ -0,0 +1,79 @@
+import json
+import threading
+import unittest
+
+import polycrypt_rs
+
+
+class TestPolyCryptNative(unittest.TestCase):
+    def setUp(self):
+        self.key = b'0' * 32
+
+    def test_encrypt_decrypt(self):
+        encrypted = polycrypt_rs.encrypt(b"Hello, world!", self.key)
+        self.assertIsInstance(encrypted, bytes)
+        self.assertEqual(polycrypt_rs.decrypt(encrypted, self.key), b"Hello, world!")
+
+    def test_encrypt_decrypt_fields(self):
+        record = {
+            "id": 1234,
+            "name": "John Doe",
+            "active": True,
+            "score": 9.5,
+            "notes": None,
+            "address": {"zip": "12345"},
+            "phones": ["555-0100", "555-0101"],
+        }
+        fields = ["name", "address.zip", "phones"]
+
+        encrypted = polycrypt_rs.encrypt_fields(record, fields, self.key)
+        self.assertNotEqual(encrypted["name"], "John Doe")
+        self.assertEqual(encrypted["id"], 1234)
+        self.assertEqual(polycrypt_rs.decrypt_fields(encrypted, fields, self.key), record)
+
+    def test_batch_releases_gil(self):
+        records = [{"id": str(i), "name": f"Patient {i}"} for i in range(2000)]
+        results = []
+
+        def worker():
+            encrypted = polycrypt_rs.encrypt_fields_in_batch(records, ["name"], self.key)
+            results.append(polycrypt_rs.decrypt_fields_in_batch(encrypted, ["name"], self.key))
+
+        threads = [threading.Thread(target=worker) for _ in range(4)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        self.assertEqual(results, [records] * 4)
+
+    def test_exceptions(self):
+        with self.assertRaises(polycrypt_rs.InvalidKeyError):
+            polycrypt_rs.encrypt(b"data", b"short")
+        with self.assertRaises(polycrypt_rs.DecryptionError):
+            polycrypt_rs.decrypt(polycrypt_rs.encrypt(b"data", self.key), b'1' * 32)
+        with self.assertRaises(polycrypt_rs.PolyCryptError):
+            polycrypt_rs.encrypt_fields({"age": 42}, ["age"], self.key)
+        with self.assertRaises(TypeError):
+            polycrypt_rs.encrypt_fields({"name": object()}, ["name"], self.key)
+
+    def test_policy_records(self):
+        keyring = polycrypt_rs.Keyring()
+        keyring.insert("phi-2024", self.key)
+        keyring.set_primary("phi-2024")
+        self.assertEqual(keyring.primary_key_id, "phi-2024")
+        self.assertIn("phi-2024", keyring)
+
+        pc = polycrypt_rs.PolyCrypt({"service": "billing"})
+        with self.assertRaises(polycrypt_rs.PolicyError):
+            pc.encrypt_record("patient", {"ssn": "123-45-6789"})
+
+        pc.load_policy({"record_types": {"patient": {"fields": [{"path": "ssn"}]}}}, keyring)
+        records = [{"id": "1", "ssn": "123-45-6789"}, {"id": "2", "ssn": "987-65-4321"}]
+        encrypted = pc.encrypt_records("patient", records)
+        self.assertNotEqual(encrypted[0]["ssn"], "123-45-6789")
+        self.assertEqual(pc.decrypt_record("patient", encrypted[0]), records[0])
+        self.assertEqual(pc.decrypt_records("patient", encrypted), records)
+
+
+if __name__ == '__main__':
+    unittest.main()
@@ -37,22 +37,70 @@ class FFIResult(ctypes.Structure):
                 ("error_code", ctypes.c_int32)]
 
 # Define function signatures
-lib.encrypt.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8)]
+lib.encrypt.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
 lib.encrypt.restype = FFIResult
-lib.decrypt.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8)]
+lib.decrypt.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
 lib.decrypt.restype = FFIResult
-lib.encrypt_fields.argtypes = [ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8)]
+lib.ciphertext_len.argtypes = [ctypes.c_size_t]
+lib.ciphertext_len.restype = ctypes.c_size_t
+lib.encrypt_into.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_size_t)]
+lib.encrypt_into.restype = ctypes.c_int32
+lib.decrypt_into.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_size_t)]
+lib.decrypt_into.restype = ctypes.c_int32
+lib.encrypt_fields.argtypes = [ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
 lib.encrypt_fields.restype = FFIResult
-lib.decrypt_fields.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8)]
+lib.decrypt_fields.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
 lib.decrypt_fields.restype = FFIResult
-lib.encrypt_fields_in_batch.argtypes = [ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8)]
+lib.encrypt_fields_in_batch.argtypes = [ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
 lib.encrypt_fields_in_batch.restype = FFIResult
-lib.decrypt_fields_in_batch.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8)]
+lib.decrypt_fields_in_batch.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
 lib.decrypt_fields_in_batch.restype = FFIResult
+lib.encrypt_fields_bytes.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+lib.encrypt_fields_bytes.restype = FFIResult
+lib.decrypt_fields_bytes.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+lib.decrypt_fields_bytes.restype = FFIResult
+lib.encrypt_fields_in_batch_bytes.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+lib.encrypt_fields_in_batch_bytes.restype = FFIResult
+lib.decrypt_fields_in_batch_bytes.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+lib.decrypt_fields_in_batch_bytes.restype = FFIResult
+lib.mask_fields.argtypes = [ctypes.c_char_p, ctypes.c_char_p]
+lib.mask_fields.restype = FFIResult
+lib.decrypt_and_mask_fields.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+lib.decrypt_and_mask_fields.restype = FFIResult
+lib.pseudonymize_fields.argtypes = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+lib.pseudonymize_fields.restype = FFIResult
+lib.inspect_ciphertext.argtypes = [ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+lib.inspect_ciphertext.restype = FFIResult
+lib.phi_fields_from_schema.argtypes = [ctypes.c_char_p]
+lib.phi_fields_from_schema.restype = FFIResult
+lib.metrics_snapshot.argtypes = []
+lib.metrics_snapshot.restype = FFIResult
 lib.free_ffi_result.argtypes = [FFIResult]
 lib.free_ffi_result.restype = None
-lib.init_logger.argtypes = []
-lib.init_logger.restype = None
+lib.init_logger.argtypes = [ctypes.c_char_p]
+lib.init_logger.restype = FFIResult
+LOG_CALLBACK = ctypes.CFUNCTYPE(None, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_void_p)
+lib.set_log_callback.argtypes = [LOG_CALLBACK, ctypes.c_void_p]
+lib.set_log_callback.restype = None
+
+ERROR_INVALID_UTF8 = -2
+ERROR_INVALID_JSON = -3
+ERROR_NULL_POINTER = -4
+ERROR_INVALID_KEY = -5
+
+def _ffi_error(operation, code):
+    if code == ERROR_INVALID_UTF8:
+        return ValueError(f"{operation} failed: invalid UTF-8")
+    if code == ERROR_INVALID_JSON:
+        return ValueError(f"{operation} failed: invalid JSON")
+    if code == ERROR_NULL_POINTER:
+        return ValueError(f"{operation} failed: null pointer")
+    if code == ERROR_INVALID_KEY:
+        return ValueError(f"{operation} failed: invalid key length")
+    return ValueError(f"{operation} failed")
+
+def _buffer(data):
+    return (ctypes.c_uint8 * len(data)).from_buffer_copy(data)
 
 class PolyCrypt:
     def __init__(self, key):
@@ -63,7 +111,7 @@ class PolyCrypt:
     def encrypt(self, plaintext):
         plaintext_ptr = (ctypes.c_uint8 * len(plaintext)).from_buffer_copy(plaintext)
         key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
-        result = lib.encrypt(plaintext_ptr, len(plaintext), key_ptr)
+        result = lib.encrypt(plaintext_ptr, len(plaintext), key_ptr, len(self.key))
         if result.error_code != 0:
             lib.free_ffi_result(result)
             raise ValueError("Encryption failed")
@@ -74,7 +122,7 @@ class PolyCrypt:
     def decrypt(self, ciphertext):
         ciphertext_ptr = (ctypes.c_uint8 * len(ciphertext)).from_buffer_copy(ciphertext)
         key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
-        result = lib.decrypt(ciphertext_ptr, len(ciphertext), key_ptr)
+        result = lib.decrypt(ciphertext_ptr, len(ciphertext), key_ptr, len(self.key))
         if result.error_code != 0:
             lib.free_ffi_result(result)
             raise ValueError("Decryption failed")
@@ -82,14 +130,37 @@ class PolyCrypt:
         lib.free_ffi_result(result)
         return decrypted
 
+    def encrypt_into(self, plaintext, out):
+        """Encrypts into the bytearray `out`, which must hold at least
+        ciphertext_len(len(plaintext)) bytes, and returns the number of bytes written."""
+        plaintext_ptr = (ctypes.c_uint8 * len(plaintext)).from_buffer_copy(plaintext)
+        key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
+        out_ptr = (ctypes.c_uint8 * len(out)).from_buffer(out)
+        written = ctypes.c_size_t()
+        if lib.encrypt_into(plaintext_ptr, len(plaintext), key_ptr, len(self.key), out_ptr, len(out), ctypes.byref(written)) != 0:
+            raise ValueError("Encryption failed")
+        return written.value
+
+    def decrypt_into(self, ciphertext, out):
+        """Decrypts into the bytearray `out`, which must hold at least len(ciphertext) - 16
+        bytes, and returns the number of plaintext bytes written."""
+        ciphertext_ptr = (ctypes.c_uint8 * len(ciphertext)).from_buffer_copy(ciphertext)
+        key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
+        out_ptr = (ctypes.c_uint8 * len(out)).from_buffer(out)
+        written = ctypes.c_size_t()
+        if lib.decrypt_into(ciphertext_ptr, len(ciphertext), key_ptr, len(self.key), out_ptr, len(out), ctypes.byref(written)) != 0:
+            raise ValueError("Decryption failed")
+        return written.value
+
     def encrypt_fields(self, record, fields_to_encrypt):
         record_json = json.dumps(record).encode('utf-8')
         fields_json = json.dumps(fields_to_encrypt).encode('utf-8')
         key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
-        result = lib.encrypt_fields(record_json, fields_json, key_ptr)
+        result = lib.encrypt_fields_bytes(_buffer(record_json), len(record_json), _buffer(fields_json), len(fields_json), key_ptr, len(self.key))
         if result.error_code != 0:
+            code = result.error_code
             lib.free_ffi_result(result)
-            raise ValueError("Field encryption failed")
+            raise _ffi_error("Field encryption", code)
         encrypted_json = bytes(result.data.data[:result.data.len])
         lib.free_ffi_result(result)
         return json.loads(encrypted_json)
@@ -98,11 +169,11 @@ class PolyCrypt:
         encrypted_json = json.dumps(encrypted_record).encode('utf-8')
         fields_json = json.dumps(fields_to_decrypt).encode('utf-8')
         key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
-        encrypted_ptr = (ctypes.c_uint8 * len(encrypted_json)).from_buffer_copy(encrypted_json)
-        result = lib.decrypt_fields(encrypted_ptr, len(encrypted_json), fields_json, key_ptr)
+        result = lib.decrypt_fields_bytes(_buffer(encrypted_json), len(encrypted_json), _buffer(fields_json), len(fields_json), key_ptr, len(self.key))
         if result.error_code != 0:
+            code = result.error_code
             lib.free_ffi_result(result)
-            raise ValueError("Field decryption failed")
+            raise _ffi_error("Field decryption", code)
         decrypted_json = bytes(result.data.data[:result.data.len])
         lib.free_ffi_result(result)
         return json.loads(decrypted_json)
@@ -111,10 +182,11 @@ class PolyCrypt:
         records_json = json.dumps(records).encode('utf-8')
         fields_json = json.dumps(fields_to_encrypt).encode('utf-8')
         key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
-        result = lib.encrypt_fields_in_batch(records_json, fields_json, key_ptr)
+        result = lib.encrypt_fields_in_batch_bytes(_buffer(records_json), len(records_json), _buffer(fields_json), len(fields_json), key_ptr, len(self.key))
         if result.error_code != 0:
+            code = result.error_code
             lib.free_ffi_result(result)
-            raise ValueError("Batch field encryption failed")
+            raise _ffi_error("Batch field encryption", code)
         encrypted_json = bytes(result.data.data[:result.data.len])
         lib.free_ffi_result(result)
         return json.loads(encrypted_json)
@@ -123,14 +195,112 @@ class PolyCrypt:
         encrypted_json = json.dumps(encrypted_records).encode('utf-8')
         fields_json = json.dumps(fields_to_decrypt).encode('utf-8')
         key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
-        encrypted_ptr = (ctypes.c_uint8 * len(encrypted_json)).from_buffer_copy(encrypted_json)
-        result = lib.decrypt_fields_in_batch(encrypted_ptr, len(encrypted_json), fields_json, key_ptr)
+        result = lib.decrypt_fields_in_batch_bytes(_buffer(encrypted_json), len(encrypted_json), _buffer(fields_json), len(fields_json), key_ptr, len(self.key))
         if result.error_code != 0:
+            code = result.error_code
             lib.free_ffi_result(result)
-            raise ValueError("Batch field decryption failed")
+            raise _ffi_error("Batch field decryption", code)
         decrypted_json = bytes(result.data.data[:result.data.len])
         lib.free_ffi_result(result)
         return json.loads(decrypted_json)
 
-def init_logger():
-    lib.init_logger()
+    def decrypt_and_mask_fields(self, encrypted_record, masks):
+        encrypted_json = json.dumps(encrypted_record).encode('utf-8')
+        masks_json = json.dumps(masks).encode('utf-8')
+        key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
+        encrypted_ptr = (ctypes.c_uint8 * len(encrypted_json)).from_buffer_copy(encrypted_json)
+        result = lib.decrypt_and_mask_fields(encrypted_ptr, len(encrypted_json), masks_json, key_ptr, len(self.key))
+        if result.error_code != 0:
+            lib.free_ffi_result(result)
+            raise ValueError("Field decryption and masking failed")
+        masked_json = bytes(result.data.data[:result.data.len])
+        lib.free_ffi_result(result)
+        return json.loads(masked_json)
+
+    def pseudonymize_fields(self, record, fields_to_pseudonymize, domain):
+        record_json = json.dumps(record).encode('utf-8')
+        fields_json = json.dumps(fields_to_pseudonymize).encode('utf-8')
+        key_ptr = (ctypes.c_uint8 * len(self.key)).from_buffer_copy(self.key)
+        result = lib.pseudonymize_fields(record_json, fields_json, domain.encode('utf-8'), key_ptr, len(self.key))
+        if result.error_code != 0:
+            lib.free_ffi_result(result)
+            raise ValueError("Field pseudonymization failed")
+        pseudonymized_json = bytes(result.data.data[:result.data.len])
+        lib.free_ffi_result(result)
+        return json.loads(pseudonymized_json)
+
+def ciphertext_len(plaintext_len):
+    return lib.ciphertext_len(plaintext_len)
+
+def mask_fields(record, masks):
+    record_json = json.dumps(record).encode('utf-8')
+    masks_json = json.dumps(masks).encode('utf-8')
+    result = lib.mask_fields(record_json, masks_json)
+    if result.error_code != 0:
+        lib.free_ffi_result(result)
+        raise ValueError("Field masking failed")
+    masked_json = bytes(result.data.data[:result.data.len])
+    lib.free_ffi_result(result)
+    return json.loads(masked_json)
+
+def inspect_ciphertext(ciphertext):
+    if isinstance(ciphertext, str):
+        ciphertext = ciphertext.encode('utf-8')
+    ciphertext_ptr = (ctypes.c_uint8 * len(ciphertext)).from_buffer_copy(ciphertext)
+    result = lib.inspect_ciphertext(ciphertext_ptr, len(ciphertext))
+    if result.error_code != 0:
+        lib.free_ffi_result(result)
+        raise ValueError("Ciphertext inspection failed")
+    info_json = bytes(result.data.data[:result.data.len])
+    lib.free_ffi_result(result)
+    return json.loads(info_json)
+
+def phi_fields_from_schema(schema):
+    schema_json = json.dumps(schema).encode('utf-8')
+    result = lib.phi_fields_from_schema(schema_json)
+    if result.error_code != 0:
+        lib.free_ffi_result(result)
+        raise ValueError("Schema field discovery failed")
+    fields_json = bytes(result.data.data[:result.data.len])
+    lib.free_ffi_result(result)
+    return json.loads(fields_json)
+
+def metrics_snapshot():
+    """Returns call counts, error counts and latency histograms per operation."""
+    result = lib.metrics_snapshot()
+    if result.error_code != 0:
+        lib.free_ffi_result(result)
+        raise ValueError("Metrics snapshot failed")
+    snapshot_json = bytes(result.data.data[:result.data.len])
+    lib.free_ffi_result(result)
+    return json.loads(snapshot_json)
+
+def init_logger(config=None):
+    """Configures library logging, e.g. {"level": "debug", "format": "text",
+    "destination": {"file": "polycrypt.log"}, "context": {"service": "billing"}}.
+    Uses info-level JSON lines on stderr when config is None. May be called again."""
+    config_json = json.dumps(config).encode('utf-8') if config is not None else None
+    result = lib.init_logger(config_json)
+    lib.free_ffi_result(result)
+    if result.error_code != 0:
+        raise ValueError("Logger configuration failed")
+
+# Keeps the registered ctypes callback alive while the library may call it
+_log_callback = None
+
+def set_log_callback(callback):
+    """Routes library log entries to callback(level, message, context), where context is a
+    dict, e.g. to forward them to structlog. Pass None to stop. The callback may be invoked
+    from library worker threads."""
+    global _log_callback
+    if callback is None:
+        lib.set_log_callback(ctypes.cast(None, LOG_CALLBACK), None)
+        _log_callback = None
+        return
+
+    def trampoline(level, message, context, _user_data):
+        callback(level.decode('utf-8'), message.decode('utf-8'), json.loads(context))
+
+    new_callback = LOG_CALLBACK(trampoline)
+    lib.set_log_callback(new_callback, None)
+    _log_callback = new_callback
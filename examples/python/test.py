@@ -1,5 +1,5 @@
 import unittest
-from polycrypt.polycrypt import PolyCrypt, init_logger
+from polycrypt.polycrypt import PolyCrypt, ciphertext_len, init_logger
 
 class TestPolyCrypt(unittest.TestCase):
     def setUp(self):
@@ -13,6 +13,23 @@ class TestPolyCrypt(unittest.TestCase):
         decrypted = self.pc.decrypt(encrypted)
         self.assertEqual(plaintext, decrypted)
 
+    def test_encrypt_decrypt_empty(self):
+        encrypted = self.pc.encrypt(b"")
+        self.assertEqual(len(encrypted), ciphertext_len(0))
+        self.assertEqual(self.pc.decrypt(encrypted), b"")
+
+    def test_encrypt_decrypt_into(self):
+        plaintext = b"Hello, world!"
+        ciphertext = bytearray(ciphertext_len(len(plaintext)))
+        self.assertEqual(self.pc.encrypt_into(plaintext, ciphertext), len(ciphertext))
+
+        decrypted = bytearray(len(ciphertext))
+        written = self.pc.decrypt_into(bytes(ciphertext), decrypted)
+        self.assertEqual(bytes(decrypted[:written]), plaintext)
+
+        with self.assertRaises(ValueError):
+            self.pc.encrypt_into(plaintext, bytearray(16))
+
     def test_encrypt_decrypt_fields(self):
         record = {
             "id": "1234",
@@ -0,0 +1,87 @@
+//! The `#[polycrypt]` attribute, re-exported by polycrypt-rs under its `derive` feature.
+
+use proc_macro::TokenStream;
+use quote::ToTokens;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Field, Result};
+
+/// Encrypts the fields of a serde struct or enum marked `#[polycrypt(encrypt)]`.
+///
+/// Each marked field is serialized with `polycrypt_rs::crypto::encrypted`, so it keeps its
+/// plain type in Rust and is written in the same format as `encrypt_fields`. Place the
+/// attribute above `#[derive(Serialize, Deserialize)]` and (de)serialize inside
+/// `polycrypt_rs::crypto::encrypted::with_key`:
+///
+/// ```ignore
+/// #[polycrypt_rs::polycrypt]
+/// #[derive(Serialize, Deserialize)]
+/// struct Patient {
+///     id: String,
+///     #[polycrypt(encrypt)]
+///     name: String,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn polycrypt(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        return Error::new(
+            proc_macro2::Span::call_site(),
+            "#[polycrypt] takes no arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let mut input = parse_macro_input!(input as DeriveInput);
+    match expand(&mut input) {
+        Ok(()) => input.into_token_stream().into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &mut DeriveInput) -> Result<()> {
+    let fields: Vec<&mut Field> = match &mut input.data {
+        Data::Struct(data) => data.fields.iter_mut().collect(),
+        Data::Enum(data) => data
+            .variants
+            .iter_mut()
+            .flat_map(|variant| variant.fields.iter_mut())
+            .collect(),
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "#[polycrypt] supports structs and enums",
+            ))
+        }
+    };
+
+    for field in fields {
+        if take_encrypt_attr(field)? {
+            field
+                .attrs
+                .push(parse_quote!(#[serde(with = "::polycrypt_rs::crypto::encrypted")]));
+        }
+    }
+    Ok(())
+}
+
+// Removes the field's `#[polycrypt(...)]` attributes, returning whether one was `encrypt`.
+fn take_encrypt_attr(field: &mut Field) -> Result<bool> {
+    let mut encrypt = false;
+    let mut attrs = Vec::with_capacity(field.attrs.len());
+    for attr in field.attrs.drain(..) {
+        if !attr.path().is_ident("polycrypt") {
+            attrs.push(attr);
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("encrypt") {
+                encrypt = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `#[polycrypt(encrypt)]`"))
+            }
+        })?;
+    }
+    field.attrs = attrs;
+    Ok(encrypt)
+}
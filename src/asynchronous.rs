@@ -0,0 +1,229 @@
+//! Async variants of the encryption functions for tokio-based services, built with the `async`
+//! feature.
+//!
+//! The record and batch functions move their inputs onto tokio's blocking pool with
+//! `spawn_blocking`, so a large batch does not stall the runtime's worker threads; batches are
+//! still spread across cores by rayon inside that task. They must be called from within a tokio
+//! runtime:
+//!
+//! ```no_run
+//! # async fn run(records: Vec<serde_json::Value>, key: [u8; 32]) -> Result<(), polycrypt_rs::PolyCryptError> {
+//! use polycrypt_rs::asynchronous;
+//!
+//! let fields = vec!["name".to_string(), "dob".to_string()];
+//! let encrypted = asynchronous::encrypt_fields_in_batch(records, fields, &key).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`encrypt_stream`] and [`decrypt_stream`] encrypt from an `AsyncRead` into an `AsyncWrite`
+//! in constant memory. Their ciphertext has the same format as [`encryption::encrypt`], so a
+//! stream can be decrypted in one piece with [`encryption::decrypt`] and vice versa.
+
+use crate::crypto::encryption::{self, AES_BLOCK_SIZE};
+use crate::error::PolyCryptError;
+use crate::metrics::{self, Instant};
+use aes::Aes256;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use cipher::block_padding::Pkcs7;
+use rand::Rng;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::task;
+
+// Bytes encrypted or decrypted per read; a multiple of the block size.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Runs `f` on the blocking pool. A panic in `f` is resumed on the calling task.
+async fn blocking<T, F>(f: F) -> Result<T, PolyCryptError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, PolyCryptError> + Send + 'static,
+{
+    match task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(PolyCryptError::UnknownError(e.to_string())),
+    }
+}
+
+/// Async [`encryption::encrypt`].
+pub async fn encrypt(plaintext: Vec<u8>, key: &[u8; 32]) -> Result<Vec<u8>, PolyCryptError> {
+    let key = *key;
+    blocking(move || encryption::encrypt(&plaintext, &key)).await
+}
+
+/// Async [`encryption::decrypt`].
+pub async fn decrypt(ciphertext: Vec<u8>, key: &[u8; 32]) -> Result<Vec<u8>, PolyCryptError> {
+    let key = *key;
+    blocking(move || encryption::decrypt(&ciphertext, &key)).await
+}
+
+/// Async [`encryption::encrypt_fields`].
+pub async fn encrypt_fields(
+    record: Value,
+    fields_to_encrypt: Vec<String>,
+    key: &[u8; 32],
+) -> Result<Value, PolyCryptError> {
+    let key = *key;
+    blocking(move || encryption::encrypt_fields(&record, &fields_to_encrypt, &key)).await
+}
+
+/// Async [`encryption::decrypt_fields`].
+pub async fn decrypt_fields(
+    record: Value,
+    fields_to_decrypt: Vec<String>,
+    key: &[u8; 32],
+) -> Result<Value, PolyCryptError> {
+    let key = *key;
+    blocking(move || encryption::decrypt_fields(&record, &fields_to_decrypt, &key)).await
+}
+
+/// Async [`encryption::encrypt_fields_in_batch`].
+pub async fn encrypt_fields_in_batch(
+    records: Vec<Value>,
+    fields_to_encrypt: Vec<String>,
+    key: &[u8; 32],
+) -> Result<Vec<Value>, PolyCryptError> {
+    let key = *key;
+    blocking(move || encryption::encrypt_fields_in_batch(&records, &fields_to_encrypt, &key)).await
+}
+
+/// Async [`encryption::decrypt_fields_in_batch`].
+pub async fn decrypt_fields_in_batch(
+    records: Vec<Value>,
+    fields_to_decrypt: Vec<String>,
+    key: &[u8; 32],
+) -> Result<Vec<Value>, PolyCryptError> {
+    let key = *key;
+    blocking(move || encryption::decrypt_fields_in_batch(&records, &fields_to_decrypt, &key)).await
+}
+
+/// Encrypts everything read from `reader` into `writer` with a random IV, returning the number
+/// of bytes written. The writer is flushed but not shut down.
+pub async fn encrypt_stream<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    key: &[u8; 32],
+) -> Result<u64, PolyCryptError>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let start = Instant::now();
+    let result = encrypt_stream_inner(reader, writer, key).await;
+    metrics::record("encrypt_stream", start.elapsed(), result.is_ok());
+    result
+}
+
+async fn encrypt_stream_inner<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    key: &[u8; 32],
+) -> Result<u64, PolyCryptError>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut iv = [0u8; AES_BLOCK_SIZE];
+    rand::thread_rng().fill(&mut iv);
+    writer.write_all(&iv).await?;
+    let mut written = AES_BLOCK_SIZE as u64;
+
+    let mut cipher = cbc::Encryptor::<Aes256>::new(key.into(), (&iv).into());
+    // Room for the padding block after a partial chunk.
+    let mut buffer = vec![0u8; CHUNK_SIZE + AES_BLOCK_SIZE];
+    let mut filled = 0;
+    loop {
+        let n = reader.read(&mut buffer[filled..CHUNK_SIZE]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+        if filled == CHUNK_SIZE {
+            for block in buffer[..CHUNK_SIZE].chunks_exact_mut(AES_BLOCK_SIZE) {
+                cipher.encrypt_block_mut(block.into());
+            }
+            writer.write_all(&buffer[..CHUNK_SIZE]).await?;
+            written += CHUNK_SIZE as u64;
+            filled = 0;
+        }
+    }
+
+    let last = cipher
+        .encrypt_padded_mut::<Pkcs7>(&mut buffer, filled)
+        .map_err(|e| PolyCryptError::EncryptionError(e.to_string()))?;
+    writer.write_all(last).await?;
+    writer.flush().await?;
+    Ok(written + last.len() as u64)
+}
+
+/// Decrypts a ciphertext read from `reader` into `writer`, returning the number of plaintext
+/// bytes written. The writer is flushed but not shut down.
+///
+/// Plaintext is written as it is decrypted, so on failure `writer` may already hold part of
+/// it; discard the output of a failed call.
+pub async fn decrypt_stream<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    key: &[u8; 32],
+) -> Result<u64, PolyCryptError>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let start = Instant::now();
+    let result = decrypt_stream_inner(reader, writer, key).await;
+    metrics::record("decrypt_stream", start.elapsed(), result.is_ok());
+    result
+}
+
+async fn decrypt_stream_inner<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    key: &[u8; 32],
+) -> Result<u64, PolyCryptError>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut iv = [0u8; AES_BLOCK_SIZE];
+    reader
+        .read_exact(&mut iv)
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => {
+                PolyCryptError::DecryptionError("Ciphertext too short".to_string())
+            }
+            _ => e.into(),
+        })?;
+
+    let mut cipher = cbc::Decryptor::<Aes256>::new(key.into(), (&iv).into());
+    // The last block is held back until the end of the stream, since it carries the padding.
+    let mut buffer = vec![0u8; CHUNK_SIZE + AES_BLOCK_SIZE];
+    let mut filled = 0;
+    let mut written = 0u64;
+    loop {
+        let n = reader.read(&mut buffer[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+        if filled == buffer.len() {
+            for block in buffer[..CHUNK_SIZE].chunks_exact_mut(AES_BLOCK_SIZE) {
+                cipher.decrypt_block_mut(block.into());
+            }
+            writer.write_all(&buffer[..CHUNK_SIZE]).await?;
+            written += CHUNK_SIZE as u64;
+            buffer.copy_within(CHUNK_SIZE.., 0);
+            filled = AES_BLOCK_SIZE;
+        }
+    }
+
+    let last = cipher
+        .decrypt_padded_mut::<Pkcs7>(&mut buffer[..filled])
+        .map_err(|e| PolyCryptError::DecryptionError(e.to_string()))?;
+    writer.write_all(last).await?;
+    writer.flush().await?;
+    Ok(written + last.len() as u64)
+}
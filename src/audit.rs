@@ -0,0 +1,318 @@
+//! Tamper-evident, hash-chained audit log of decryptions.
+//!
+//! **Only [`PolyCrypt::decrypt_record_audited`](crate::PolyCrypt::decrypt_record_audited)
+//! writes to this log.** Every other decryption entry point is unaudited: `decrypt_record`,
+//! [`encryption::decrypt_fields`](crate::crypto::encryption::decrypt_fields) and
+//! `decrypt_fields_in_batch`, [`decrypt_and_mask_fields`](crate::transform::masking::decrypt_and_mask_fields),
+//! the FFI `decrypt_fields*` exports and the other bindings, and the sidecar server. A
+//! deployment that must account for every access has to expose only the audited API to its
+//! callers.
+
+use crate::error::PolyCryptError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// `prev_hash` of the first entry of a log.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Who accessed protected data, and why. Supplied by the caller on every audited call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditContext {
+    pub principal: String,
+    pub purpose: String,
+}
+
+impl AuditContext {
+    pub fn new(principal: impl Into<String>, purpose: impl Into<String>) -> Self {
+        Self {
+            principal: principal.into(),
+            purpose: purpose.into(),
+        }
+    }
+}
+
+/// One line of the audit log.
+///
+/// `hash` is the SHA-256 of `prev_hash` followed by the JSON of every other field, so
+/// editing, inserting, reordering or removing an entry breaks the chain from that point on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: String,
+    pub operation: String,
+    pub fields: Vec<String>,
+    pub key_id: Option<String>,
+    pub principal: String,
+    pub purpose: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// The position of the newest entry of a log. Storing it outside the log, e.g. in a
+/// separate system, also makes truncation of the newest entries detectable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditHead {
+    pub seq: u64,
+    pub hash: String,
+}
+
+// The hashed part of an entry; field order is fixed by the struct definition.
+#[derive(Serialize)]
+struct HashedEntry<'a> {
+    seq: u64,
+    timestamp: &'a str,
+    operation: &'a str,
+    fields: &'a [String],
+    key_id: Option<&'a str>,
+    principal: &'a str,
+    purpose: &'a str,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> String {
+        let hashed = HashedEntry {
+            seq: self.seq,
+            timestamp: &self.timestamp,
+            operation: &self.operation,
+            fields: &self.fields,
+            key_id: self.key_id.as_deref(),
+            principal: &self.principal,
+            purpose: &self.purpose,
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(serde_json::to_vec(&hashed).unwrap());
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Where audit entries are persisted.
+pub trait AuditSink: Send {
+    fn append(&mut self, entry: &AuditEntry) -> Result<(), PolyCryptError>;
+}
+
+impl AuditSink for Vec<AuditEntry> {
+    fn append(&mut self, entry: &AuditEntry) -> Result<(), PolyCryptError> {
+        self.push(entry.clone());
+        Ok(())
+    }
+}
+
+/// Appends entries to a file as JSON lines, flushing after each one.
+pub struct FileSink {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileSink {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, PolyCryptError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AuditSink for FileSink {
+    fn append(&mut self, entry: &AuditEntry) -> Result<(), PolyCryptError> {
+        let mut line = serde_json::to_vec(entry).unwrap();
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// A hash-chained log of accesses to protected data.
+pub struct AuditLog {
+    sink: Box<dyn AuditSink>,
+    head: Option<AuditHead>,
+}
+
+impl AuditLog {
+    /// Starts a new chain in `sink`.
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        Self {
+            sink: Box::new(sink),
+            head: None,
+        }
+    }
+
+    /// Continues the chain stored in the file at `path`, which is verified first, or starts
+    /// one if the file does not exist yet.
+    pub fn open_file<P: AsRef<Path>>(path: P) -> Result<Self, PolyCryptError> {
+        let path = path.as_ref();
+        let head = if path.exists() {
+            verify_file(path)?
+        } else {
+            None
+        };
+        Ok(Self {
+            sink: Box::new(FileSink::open(path)?),
+            head,
+        })
+    }
+
+    pub fn head(&self) -> Option<&AuditHead> {
+        self.head.as_ref()
+    }
+
+    /// Appends an entry for `operation` on `fields` and returns it.
+    pub fn record(
+        &mut self,
+        operation: &str,
+        fields: &[String],
+        key_id: Option<&str>,
+        context: &AuditContext,
+    ) -> Result<AuditEntry, PolyCryptError> {
+        let (seq, prev_hash) = match &self.head {
+            Some(head) => (head.seq + 1, head.hash.clone()),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        let mut entry = AuditEntry {
+            seq,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            operation: operation.to_string(),
+            fields: fields.to_vec(),
+            key_id: key_id.map(str::to_string),
+            principal: context.principal.clone(),
+            purpose: context.purpose.clone(),
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        self.sink.append(&entry)?;
+        self.head = Some(AuditHead {
+            seq,
+            hash: entry.hash.clone(),
+        });
+        Ok(entry)
+    }
+}
+
+/// Checks that `entries` form an unbroken chain starting at the genesis entry and returns
+/// the head, or `None` for an empty log.
+pub fn verify_entries(entries: &[AuditEntry]) -> Result<Option<AuditHead>, PolyCryptError> {
+    let mut prev_hash = GENESIS_HASH;
+    for (expected_seq, entry) in (0u64..).zip(entries) {
+        if entry.seq != expected_seq {
+            return Err(audit_error(format!(
+                "Expected entry {} but found entry {}",
+                expected_seq, entry.seq
+            )));
+        }
+        if entry.prev_hash != prev_hash {
+            return Err(audit_error(format!(
+                "Entry {} does not follow the previous entry",
+                entry.seq
+            )));
+        }
+        if entry.compute_hash() != entry.hash {
+            return Err(audit_error(format!(
+                "Entry {} has been modified",
+                entry.seq
+            )));
+        }
+        prev_hash = &entry.hash;
+    }
+
+    Ok(entries.last().map(|entry| AuditHead {
+        seq: entry.seq,
+        hash: entry.hash.clone(),
+    }))
+}
+
+/// Reads and verifies an audit log file written by [`FileSink`].
+pub fn verify_file<P: AsRef<Path>>(path: P) -> Result<Option<AuditHead>, PolyCryptError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            audit_error(format!(
+                "Line {} is not an audit entry: {}",
+                line_number + 1,
+                e
+            ))
+        })?;
+        entries.push(entry);
+    }
+    verify_entries(&entries)
+}
+
+fn audit_error(message: String) -> PolyCryptError {
+    PolyCryptError::AuditError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedSink(Arc<Mutex<Vec<AuditEntry>>>);
+
+    impl AuditSink for SharedSink {
+        fn append(&mut self, entry: &AuditEntry) -> Result<(), PolyCryptError> {
+            self.0.lock().unwrap().push(entry.clone());
+            Ok(())
+        }
+    }
+
+    fn record_three(log: &mut AuditLog) {
+        let context = AuditContext::new("dr.smith", "treatment");
+        for operation in ["decrypt_fields", "decrypt_record", "decrypt_fields"] {
+            log.record(operation, &["ssn".to_string()], Some("phi-2024"), &context)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_chain_verifies_and_detects_tampering() {
+        let sink = SharedSink::default();
+        let mut log = AuditLog::new(sink.clone());
+        record_three(&mut log);
+        let entries = sink.0.lock().unwrap().clone();
+        assert_eq!(verify_entries(&entries).unwrap().as_ref(), log.head());
+
+        let mut edited = entries.clone();
+        edited[1].principal = "someone.else".to_string();
+        assert!(verify_entries(&edited).is_err());
+
+        let mut removed = entries.clone();
+        removed.remove(1);
+        assert!(verify_entries(&removed).is_err());
+        assert!(verify_entries(&entries[1..]).is_err());
+
+        // Dropping the newest entry leaves a valid chain; only an anchored head catches it.
+        let truncated = verify_entries(&entries[..2]).unwrap();
+        assert_ne!(truncated.as_ref(), log.head());
+    }
+
+    #[test]
+    fn test_file_sink_continues_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+
+        record_three(&mut AuditLog::open_file(&path).unwrap());
+        let mut reopened = AuditLog::open_file(&path).unwrap();
+        assert_eq!(reopened.head().unwrap().seq, 2);
+        record_three(&mut reopened);
+        assert_eq!(verify_file(&path).unwrap().unwrap().seq, 5);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, contents.replacen("treatment", "billing", 1)).unwrap();
+        assert!(verify_file(&path).is_err());
+        assert!(AuditLog::open_file(&path).is_err());
+    }
+}
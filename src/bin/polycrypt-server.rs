@@ -0,0 +1,99 @@
+use clap::Parser;
+use polycrypt_rs::server::Server;
+use polycrypt_rs::Keyring;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Serve polycrypt-rs encryption over HTTP/JSON on localhost or a Unix socket.
+#[derive(Parser)]
+#[command(name = "polycrypt-server", version, about)]
+struct Args {
+    /// Keyring file, `{"primary": "<id>", "keys": {"<id>": "<base64 key>"}}`.
+    #[arg(long)]
+    keyring: PathBuf,
+    /// File holding the bearer token clients must send. Defaults to the
+    /// `POLYCRYPT_SERVER_TOKEN` environment variable.
+    #[arg(long)]
+    token_file: Option<PathBuf>,
+    /// TCP address to listen on. Must be a loopback address unless `--allow-remote` is given.
+    #[arg(long, default_value = "127.0.0.1:8700")]
+    listen: String,
+    /// Allow `--listen` addresses that are reachable from other hosts.
+    #[arg(long)]
+    allow_remote: bool,
+    /// Additional host name clients may use in the `Host` header, e.g. the server's DNS name.
+    #[arg(long)]
+    allowed_host: Vec<String>,
+    /// Listen on this Unix socket instead of TCP.
+    #[cfg(unix)]
+    #[arg(long, conflicts_with = "listen")]
+    unix: Option<PathBuf>,
+    /// Number of worker threads. Defaults to the number of CPUs.
+    #[arg(long)]
+    workers: Option<usize>,
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    let keyring = match Keyring::from_file(&args.keyring) {
+        Ok(keyring) => keyring,
+        Err(e) => {
+            eprintln!("polycrypt-server: cannot load keyring: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let token = match read_token(args.token_file.as_ref()) {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("polycrypt-server: cannot load token: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let bind_tcp = |keyring, token| {
+        if args.allow_remote {
+            Server::bind_remote(&args.listen, keyring, token).map_err(|e| e.to_string())
+        } else {
+            Server::bind(&args.listen, keyring, token)
+                .map_err(|e| format!("{}; pass --allow-remote to listen on it anyway", e))
+        }
+    };
+    #[cfg(unix)]
+    let server = match &args.unix {
+        Some(path) => Server::bind_unix(path, keyring, token).map_err(|e| e.to_string()),
+        None => bind_tcp(keyring, token),
+    };
+    #[cfg(not(unix))]
+    let server = bind_tcp(keyring, token);
+
+    let mut server = match server {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("polycrypt-server: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for host in &args.allowed_host {
+        server.allow_host(host);
+    }
+
+    let workers = args.workers.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1)
+    });
+    server.run(workers);
+    ExitCode::SUCCESS
+}
+
+fn read_token(path: Option<&PathBuf>) -> Result<String, String> {
+    let token = match path {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| e.to_string())?,
+        None => std::env::var("POLYCRYPT_SERVER_TOKEN")
+            .map_err(|_| "pass --token-file or set POLYCRYPT_SERVER_TOKEN".to_string())?,
+    };
+    Ok(token.trim().to_string())
+}
@@ -0,0 +1,279 @@
+use clap::{Args, Parser, Subcommand};
+use polycrypt_rs::crypto::encryption::{self, FieldOptions};
+use polycrypt_rs::crypto::envelope::{self, Algorithm, Header};
+use polycrypt_rs::crypto::keyring::{decode_key, write_key_file};
+use polycrypt_rs::crypto::{inspect, schema};
+use serde_json::Value;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+/// Encrypt and decrypt data and records with polycrypt-rs.
+#[derive(Parser)]
+#[command(name = "polycrypt", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a random 256-bit key, printed as base64.
+    Keygen {
+        /// Write the key to this file, readable by the owner only, instead of stdout.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Encrypt bytes into a polycrypt envelope.
+    Encrypt {
+        #[command(flatten)]
+        key: KeyArgs,
+        #[command(flatten)]
+        io: IoArgs,
+        /// Key id recorded in the envelope header.
+        #[arg(long)]
+        key_id: Option<String>,
+    },
+    /// Decrypt an envelope, or a raw IV-prefixed ciphertext, back to bytes.
+    Decrypt {
+        #[command(flatten)]
+        key: KeyArgs,
+        #[command(flatten)]
+        io: IoArgs,
+    },
+    /// Encrypt fields of JSON records.
+    EncryptFields {
+        #[command(flatten)]
+        key: KeyArgs,
+        #[command(flatten)]
+        records: RecordArgs,
+    },
+    /// Decrypt fields of JSON records.
+    DecryptFields {
+        #[command(flatten)]
+        key: KeyArgs,
+        #[command(flatten)]
+        records: RecordArgs,
+    },
+    /// Re-encrypt fields of JSON records from one key to another.
+    Rotate {
+        /// File holding the current base64 key.
+        #[arg(long)]
+        old_key_file: PathBuf,
+        /// File holding the new base64 key.
+        #[arg(long)]
+        new_key_file: PathBuf,
+        #[command(flatten)]
+        records: RecordArgs,
+    },
+    /// Show the header of an encrypted value without decrypting it.
+    Inspect {
+        /// Base64 field value to inspect. Reads an envelope from --in or stdin when omitted.
+        value: Option<String>,
+        /// Read the ciphertext from this file instead of stdin.
+        #[arg(long = "in")]
+        input: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct KeyArgs {
+    /// File holding the base64 key. Defaults to the POLYCRYPT_KEY environment variable.
+    #[arg(long)]
+    key_file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct IoArgs {
+    /// Read from this file instead of stdin.
+    #[arg(long = "in")]
+    input: Option<PathBuf>,
+    /// Write to this file instead of stdout.
+    #[arg(long)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct RecordArgs {
+    /// Comma-separated field paths, e.g. `name,address.zip,contacts[].phone`.
+    #[arg(long, value_delimiter = ',', required_unless_present = "schema")]
+    fields: Vec<String>,
+    /// Take the field paths from the `x-phi` annotations of this JSON Schema.
+    #[arg(long, conflicts_with = "fields")]
+    schema: Option<PathBuf>,
+    /// Treat input and output as newline-delimited JSON, one record per line.
+    #[arg(long)]
+    ndjson: bool,
+    #[command(flatten)]
+    io: IoArgs,
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("polycrypt: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> CliResult<()> {
+    match cli.command {
+        Command::Keygen { out } => {
+            let key: [u8; 32] = rand::random();
+            let encoded = base64::encode(key);
+            match out {
+                Some(path) => write_key_file(&path, (encoded + "\n").as_bytes())?,
+                None => println!("{}", encoded),
+            }
+        }
+        Command::Encrypt { key, io, key_id } => {
+            let key = key.load()?;
+            let plaintext = io.read_all()?;
+            let header = Header::new(Algorithm::Aes256Cbc, key_id.as_deref(), false);
+            let sealed = envelope::seal(&header, &encryption::encrypt(&plaintext, &key)?)?;
+            let mut writer = io.writer()?;
+            writer.write_all(&sealed)?;
+            writer.flush()?;
+        }
+        Command::Decrypt { key, io } => {
+            let key = key.load()?;
+            let ciphertext = io.read_all()?;
+            let payload = if envelope::is_envelope(&ciphertext) {
+                envelope::open(&ciphertext)?.1
+            } else {
+                &ciphertext[..]
+            };
+            let mut writer = io.writer()?;
+            writer.write_all(&encryption::decrypt(payload, &key)?)?;
+            writer.flush()?;
+        }
+        Command::EncryptFields { key, records } => {
+            let key = key.load()?;
+            let fields = records.fields()?;
+            records.transform(|record| Ok(encryption::encrypt_fields(record, &fields, &key)?))?;
+        }
+        Command::DecryptFields { key, records } => {
+            let key = key.load()?;
+            let fields = records.fields()?;
+            records.transform(|record| Ok(encryption::decrypt_fields(record, &fields, &key)?))?;
+        }
+        Command::Rotate {
+            old_key_file,
+            new_key_file,
+            records,
+        } => {
+            let old_key = read_key_file(&old_key_file)?;
+            let new_key = read_key_file(&new_key_file)?;
+            let fields = records.fields()?;
+            // Fail on any selected value that does not decrypt, rather than sealing
+            // ciphertext under the new key and losing it when the old key is retired.
+            let options = FieldOptions {
+                strict: true,
+                legacy: true,
+            };
+            records.transform(|record| {
+                let decrypted =
+                    encryption::decrypt_fields_with_options(record, &fields, &old_key, &options)?;
+                Ok(encryption::encrypt_fields(&decrypted, &fields, &new_key)?)
+            })?;
+        }
+        Command::Inspect { value, input } => {
+            let bytes = match value {
+                Some(value) => value.into_bytes(),
+                None => IoArgs { input, out: None }.read_all()?,
+            };
+            println!("{}", serde_json::to_string(&inspect::inspect(&bytes)?)?);
+        }
+    }
+    Ok(())
+}
+
+impl KeyArgs {
+    fn load(&self) -> CliResult<[u8; 32]> {
+        match &self.key_file {
+            Some(path) => read_key_file(path),
+            None => {
+                let encoded = std::env::var("POLYCRYPT_KEY")
+                    .map_err(|_| "no key given: pass --key-file or set POLYCRYPT_KEY")?;
+                Ok(decode_key(&encoded)?)
+            }
+        }
+    }
+}
+
+fn read_key_file(path: &Path) -> CliResult<[u8; 32]> {
+    let encoded = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read key file '{}': {}", path.display(), e))?;
+    Ok(decode_key(&encoded)?)
+}
+
+impl IoArgs {
+    fn reader(&self) -> CliResult<Box<dyn BufRead>> {
+        Ok(match &self.input {
+            Some(path) => Box::new(BufReader::new(File::open(path)?)),
+            None => Box::new(BufReader::new(io::stdin())),
+        })
+    }
+
+    fn writer(&self) -> CliResult<Box<dyn Write>> {
+        Ok(match &self.out {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(BufWriter::new(io::stdout())),
+        })
+    }
+
+    fn read_all(&self) -> CliResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.reader()?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl RecordArgs {
+    fn fields(&self) -> CliResult<Vec<String>> {
+        match &self.schema {
+            Some(path) => {
+                let schema: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+                Ok(schema::phi_fields(&schema)?)
+            }
+            None => Ok(self.fields.clone()),
+        }
+    }
+
+    /// Applies `f` to every record. JSON input may be a single record or an array of them;
+    /// NDJSON input is processed line by line.
+    fn transform<F>(&self, f: F) -> CliResult<()>
+    where
+        F: Fn(&Value) -> CliResult<Value>,
+    {
+        let mut writer = self.io.writer()?;
+        if self.ndjson {
+            for line in self.io.reader()?.lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                serde_json::to_writer(&mut writer, &f(&serde_json::from_str(&line)?)?)?;
+                writer.write_all(b"\n")?;
+            }
+        } else {
+            let input: Value = serde_json::from_slice(&self.io.read_all()?)?;
+            let output = match &input {
+                Value::Array(records) => {
+                    Value::Array(records.iter().map(&f).collect::<CliResult<Vec<Value>>>()?)
+                }
+                record => f(record)?,
+            };
+            serde_json::to_writer(&mut writer, &output)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
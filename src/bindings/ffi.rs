@@ -1,9 +1,15 @@
-use crate::crypto::encryption;
+use crate::crypto::{encryption, inspect, pseudonymize, schema};
 use crate::error::PolyCryptError;
-use serde_json::Value;
+use crate::logger::{self, LoggerConfig, Secret};
+use crate::metrics;
+use crate::transform::masking::{self, FieldMasks};
+use crate::Logger;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
 use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
 use std::slice;
+use std::time::Instant;
 
 #[repr(C)]
 pub struct ByteArray {
@@ -25,67 +31,172 @@ fn to_byte_array(vec: Vec<u8>) -> ByteArray {
     ByteArray { data: ptr, len }
 }
 
-fn to_ffi_result(result: Result<Vec<u8>, PolyCryptError>) -> FFIResult {
+/// `error_code` of a successful call.
+pub const ERROR_OK: i32 = 0;
+/// The operation itself failed, e.g. a value could not be decrypted or a field path is invalid.
+pub const ERROR_FAILED: i32 = -1;
+/// A string or JSON argument is not valid UTF-8.
+pub const ERROR_INVALID_UTF8: i32 = -2;
+/// A JSON argument could not be parsed or does not have the expected shape.
+pub const ERROR_INVALID_JSON: i32 = -3;
+/// A required pointer argument is null. Data pointers may be null when their length is 0.
+pub const ERROR_NULL_POINTER: i32 = -4;
+/// The key is not `key_len == 32` bytes long.
+pub const ERROR_INVALID_KEY: i32 = -5;
+
+// Why an export failed, mapped to its `error_code`.
+#[derive(Debug, thiserror::Error)]
+enum FfiError {
+    #[error("{0}")]
+    Failed(#[from] PolyCryptError),
+    #[error("Invalid UTF-8: {0}")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Null pointer argument")]
+    NullPointer,
+    #[error("Invalid key: expected 32 bytes, got {0}")]
+    InvalidKey(usize),
+}
+
+impl FfiError {
+    fn code(&self) -> i32 {
+        match self {
+            FfiError::Failed(_) => ERROR_FAILED,
+            FfiError::InvalidUtf8(_) => ERROR_INVALID_UTF8,
+            FfiError::InvalidJson(_) => ERROR_INVALID_JSON,
+            FfiError::NullPointer => ERROR_NULL_POINTER,
+            FfiError::InvalidKey(_) => ERROR_INVALID_KEY,
+        }
+    }
+}
+
+// Failures go through `Logger`, so hosts see them in the configured backend and their log
+// callback, redacted like every other entry, rather than on the process's stderr.
+fn log_failure(operation: &str, e: &FfiError) {
+    Logger::new(json!({"operation": operation})).error(
+        "FFI call failed",
+        Some(json!({"error": e.to_string(), "error_code": e.code()})),
+    );
+}
+
+fn to_ffi_result<E: Into<FfiError>>(operation: &str, result: Result<Vec<u8>, E>) -> FFIResult {
     match result {
         Ok(data) => FFIResult {
             data: to_byte_array(data),
-            error_code: 0,
+            error_code: ERROR_OK,
         },
         Err(e) => {
-            eprintln!("Error: {:?}", e);
+            let e = e.into();
+            log_failure(operation, &e);
             FFIResult {
                 data: ByteArray {
                     data: std::ptr::null_mut(),
                     len: 0,
                 },
-                error_code: -1,
+                error_code: e.code(),
             }
         }
     }
 }
 
-fn validate_key(key: *const u8) -> Result<[u8; 32], FFIResult> {
-    let key_slice = unsafe { slice::from_raw_parts(key, 32) };
-    if key_slice.len() != 32 {
-        return Err(FFIResult {
-            data: ByteArray {
-                data: std::ptr::null_mut(),
-                len: 0,
-            },
-            error_code: -1,
-        });
+// An empty slice for `len == 0`, whatever `data` is, so callers may pass null for empty input.
+fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], FfiError> {
+    if len == 0 {
+        return Ok(&[]);
     }
-    key_slice.try_into().map_err(|_| FFIResult {
-        data: ByteArray {
-            data: std::ptr::null_mut(),
-            len: 0,
-        },
-        error_code: -1,
-    })
+    if data.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    Ok(unsafe { slice::from_raw_parts(data, len) })
 }
 
-#[no_mangle]
-pub extern "C" fn encrypt(plaintext: *const u8, plaintext_len: usize, key: *const u8) -> FFIResult {
-    let plaintext_slice = unsafe { slice::from_raw_parts(plaintext, plaintext_len) };
+fn bytes_mut<'a>(data: *mut u8, len: usize) -> Result<&'a mut [u8], FfiError> {
+    if len == 0 {
+        return Ok(&mut []);
+    }
+    if data.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    Ok(unsafe { slice::from_raw_parts_mut(data, len) })
+}
 
-    let key_array = match validate_key(key) {
-        Ok(k) => k,
-        Err(e) => return e,
+fn c_str_bytes<'a>(s: *const c_char) -> Result<&'a [u8], FfiError> {
+    if s.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    Ok(unsafe { CStr::from_ptr(s).to_bytes() })
+}
+
+fn to_str(bytes: &[u8]) -> Result<&str, FfiError> {
+    Ok(std::str::from_utf8(bytes)?)
+}
+
+fn from_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, FfiError> {
+    Ok(serde_json::from_str(to_str(bytes)?)?)
+}
+
+fn validate_key(key: *const u8, key_len: usize) -> Result<Secret<[u8; 32]>, FfiError> {
+    if key.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    if key_len != 32 {
+        return Err(FfiError::InvalidKey(key_len));
+    }
+    let key_slice = unsafe { slice::from_raw_parts(key, 32) };
+    key_slice
+        .try_into()
+        .map(Secret::new)
+        .map_err(|_| FfiError::InvalidKey(key_len))
+}
+
+// Records the latency of an export, counting failed calls as errors.
+fn instrumented(
+    operation: &'static str,
+    f: impl FnOnce() -> Result<Vec<u8>, FfiError>,
+) -> FFIResult {
+    let start = Instant::now();
+    let result = to_ffi_result(operation, f());
+    metrics::record(operation, start.elapsed(), result.error_code == ERROR_OK);
+    result
+}
+
+// Like `instrumented`, for exports that return a bare error code.
+fn instrumented_code(operation: &'static str, f: impl FnOnce() -> Result<(), FfiError>) -> i32 {
+    let start = Instant::now();
+    let code = match f() {
+        Ok(()) => ERROR_OK,
+        Err(e) => {
+            log_failure(operation, &e);
+            e.code()
+        }
     };
+    metrics::record(operation, start.elapsed(), code == ERROR_OK);
+    code
+}
 
-    match encryption::encrypt(plaintext_slice, &key_array) {
-        Ok(encrypted_data) => FFIResult {
-            data: to_byte_array(encrypted_data),
-            error_code: 0,
-        },
-        Err(_) => FFIResult {
-            data: ByteArray {
-                data: std::ptr::null_mut(),
-                len: 0,
-            },
-            error_code: -1,
-        },
+fn write_len(written: *mut usize, len: usize) -> Result<(), FfiError> {
+    if written.is_null() {
+        return Err(FfiError::NullPointer);
     }
+    unsafe { *written = len };
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn encrypt(
+    plaintext: *const u8,
+    plaintext_len: usize,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.encrypt", || {
+        let key_array = validate_key(key, key_len)?;
+        Ok(encryption::encrypt(
+            bytes(plaintext, plaintext_len)?,
+            key_array.expose_secret(),
+        )?)
+    })
 }
 
 #[no_mangle]
@@ -93,27 +204,70 @@ pub extern "C" fn decrypt(
     ciphertext: *const u8,
     ciphertext_len: usize,
     key: *const u8,
+    key_len: usize,
 ) -> FFIResult {
-    let ciphertext_slice = unsafe { slice::from_raw_parts(ciphertext, ciphertext_len) };
+    instrumented("ffi.decrypt", || {
+        let key_array = validate_key(key, key_len)?;
+        Ok(encryption::decrypt(
+            bytes(ciphertext, ciphertext_len)?,
+            key_array.expose_secret(),
+        )?)
+    })
+}
 
-    let key_array = match validate_key(key) {
-        Ok(k) => k,
-        Err(e) => return e,
-    };
+/// Returns the number of bytes `encrypt` produces for `plaintext_len` bytes of plaintext, so
+/// callers can size the buffer passed to `encrypt_into`. Returns 0 if the length would overflow.
+#[no_mangle]
+pub extern "C" fn ciphertext_len(plaintext_len: usize) -> usize {
+    encryption::ciphertext_len(plaintext_len)
+}
 
-    match encryption::decrypt(ciphertext_slice, &key_array) {
-        Ok(decrypted_data) => FFIResult {
-            data: to_byte_array(decrypted_data),
-            error_code: 0,
-        },
-        Err(_) => FFIResult {
-            data: ByteArray {
-                data: std::ptr::null_mut(),
-                len: 0,
-            },
-            error_code: -1,
-        },
-    }
+/// Encrypts into the caller's `out` buffer of `out_len` bytes, which must hold at least
+/// `ciphertext_len(plaintext_len)` bytes, and stores the number of bytes written in `written`.
+/// Returns 0 on success and a negative error code on failure; nothing needs to be freed.
+#[no_mangle]
+pub extern "C" fn encrypt_into(
+    plaintext: *const u8,
+    plaintext_len: usize,
+    key: *const u8,
+    key_len: usize,
+    out: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> i32 {
+    instrumented_code("ffi.encrypt_into", || {
+        let key_array = validate_key(key, key_len)?;
+        let len = encryption::encrypt_into(
+            bytes(plaintext, plaintext_len)?,
+            key_array.expose_secret(),
+            bytes_mut(out, out_len)?,
+        )?;
+        write_len(written, len)
+    })
+}
+
+/// Decrypts into the caller's `out` buffer of `out_len` bytes, which must hold at least
+/// `ciphertext_len - 16` bytes, and stores the number of plaintext bytes in `written`.
+/// Returns 0 on success and a negative error code on failure; nothing needs to be freed.
+#[no_mangle]
+pub extern "C" fn decrypt_into(
+    ciphertext: *const u8,
+    ciphertext_len: usize,
+    key: *const u8,
+    key_len: usize,
+    out: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> i32 {
+    instrumented_code("ffi.decrypt_into", || {
+        let key_array = validate_key(key, key_len)?;
+        let len = encryption::decrypt_into(
+            bytes(ciphertext, ciphertext_len)?,
+            key_array.expose_secret(),
+            bytes_mut(out, out_len)?,
+        )?;
+        write_len(written, len)
+    })
 }
 
 #[no_mangle]
@@ -121,46 +275,86 @@ pub extern "C" fn encrypt_fields(
     record: *const c_char,
     fields_to_encrypt: *const c_char,
     key: *const u8,
+    key_len: usize,
 ) -> FFIResult {
-    let key_array = match validate_key(key) {
-        Ok(k) => k,
-        Err(e) => return e,
-    };
-
-    let record_str = unsafe { CStr::from_ptr(record).to_str().unwrap() };
-    let fields_str = unsafe { CStr::from_ptr(fields_to_encrypt).to_str().unwrap() };
-
-    let record: Value = serde_json::from_str(record_str).unwrap();
-    let fields: Vec<String> = serde_json::from_str(fields_str).unwrap();
+    instrumented("ffi.encrypt_fields", || {
+        encrypt_fields_json(
+            c_str_bytes(record)?,
+            c_str_bytes(fields_to_encrypt)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
 
-    let result = encryption::encrypt_fields(&record, &fields, &key_array)
-        .map(|encrypted| serde_json::to_vec(&encrypted).unwrap());
+/// Like `encrypt_fields`, with the record and field list JSON passed as pointer and length.
+#[no_mangle]
+pub extern "C" fn encrypt_fields_bytes(
+    record: *const u8,
+    record_len: usize,
+    fields_to_encrypt: *const u8,
+    fields_len: usize,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.encrypt_fields_bytes", || {
+        encrypt_fields_json(
+            bytes(record, record_len)?,
+            bytes(fields_to_encrypt, fields_len)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
 
-    to_ffi_result(result)
+fn encrypt_fields_json(record: &[u8], fields: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, FfiError> {
+    let record: Value = from_json(record)?;
+    let fields: Vec<String> = from_json(fields)?;
+    let encrypted = encryption::encrypt_fields(&record, &fields, key)?;
+    Ok(serde_json::to_vec(&encrypted).unwrap())
 }
 
+/// Decrypts fields of a JSON record. Like every decryption export, it writes no audit
+/// entries; see [`crate::audit`].
 #[no_mangle]
 pub extern "C" fn decrypt_fields(
     encrypted: *const u8,
     encrypted_len: usize,
     fields_to_decrypt: *const c_char,
     key: *const u8,
+    key_len: usize,
 ) -> FFIResult {
-    let key_array = match validate_key(key) {
-        Ok(k) => k,
-        Err(e) => return e,
-    };
-
-    let encrypted_slice = unsafe { slice::from_raw_parts(encrypted, encrypted_len) };
-    let fields_str = unsafe { CStr::from_ptr(fields_to_decrypt).to_str().unwrap() };
-
-    let encrypted_value: Value = serde_json::from_slice(encrypted_slice).unwrap();
-    let fields: Vec<String> = serde_json::from_str(fields_str).unwrap();
+    instrumented("ffi.decrypt_fields", || {
+        decrypt_fields_json(
+            bytes(encrypted, encrypted_len)?,
+            c_str_bytes(fields_to_decrypt)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
 
-    let result = encryption::decrypt_fields(&encrypted_value, &fields, &key_array)
-        .map(|decrypted| serde_json::to_vec(&decrypted).unwrap());
+/// Like `decrypt_fields`, with the field list JSON passed as pointer and length.
+#[no_mangle]
+pub extern "C" fn decrypt_fields_bytes(
+    encrypted: *const u8,
+    encrypted_len: usize,
+    fields_to_decrypt: *const u8,
+    fields_len: usize,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.decrypt_fields_bytes", || {
+        decrypt_fields_json(
+            bytes(encrypted, encrypted_len)?,
+            bytes(fields_to_decrypt, fields_len)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
 
-    to_ffi_result(result)
+fn decrypt_fields_json(record: &[u8], fields: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, FfiError> {
+    let record: Value = from_json(record)?;
+    let fields: Vec<String> = from_json(fields)?;
+    let decrypted = encryption::decrypt_fields(&record, &fields, key)?;
+    Ok(serde_json::to_vec(&decrypted).unwrap())
 }
 
 #[no_mangle]
@@ -168,22 +362,46 @@ pub extern "C" fn encrypt_fields_in_batch(
     records: *const c_char,
     fields_to_encrypt: *const c_char,
     key: *const u8,
+    key_len: usize,
 ) -> FFIResult {
-    let key_array = match validate_key(key) {
-        Ok(k) => k,
-        Err(e) => return e,
-    };
-
-    let records_str = unsafe { CStr::from_ptr(records).to_str().unwrap() };
-    let fields_str = unsafe { CStr::from_ptr(fields_to_encrypt).to_str().unwrap() };
-
-    let records: Vec<Value> = serde_json::from_str(records_str).unwrap();
-    let fields: Vec<String> = serde_json::from_str(fields_str).unwrap();
+    instrumented("ffi.encrypt_fields_in_batch", || {
+        encrypt_fields_in_batch_json(
+            c_str_bytes(records)?,
+            c_str_bytes(fields_to_encrypt)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
 
-    let result = encryption::encrypt_fields_in_batch(&records, &fields, &key_array)
-        .map(|encrypted_records| serde_json::to_vec(&encrypted_records).unwrap());
+/// Like `encrypt_fields_in_batch`, with the records and field list JSON passed as pointer
+/// and length.
+#[no_mangle]
+pub extern "C" fn encrypt_fields_in_batch_bytes(
+    records: *const u8,
+    records_len: usize,
+    fields_to_encrypt: *const u8,
+    fields_len: usize,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.encrypt_fields_in_batch_bytes", || {
+        encrypt_fields_in_batch_json(
+            bytes(records, records_len)?,
+            bytes(fields_to_encrypt, fields_len)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
 
-    to_ffi_result(result)
+fn encrypt_fields_in_batch_json(
+    records: &[u8],
+    fields: &[u8],
+    key: &[u8; 32],
+) -> Result<Vec<u8>, FfiError> {
+    let records: Vec<Value> = from_json(records)?;
+    let fields: Vec<String> = from_json(fields)?;
+    let encrypted = encryption::encrypt_fields_in_batch(&records, &fields, key)?;
+    Ok(serde_json::to_vec(&encrypted).unwrap())
 }
 
 #[no_mangle]
@@ -192,44 +410,231 @@ pub extern "C" fn decrypt_fields_in_batch(
     encrypted_len: usize,
     fields_to_decrypt: *const c_char,
     key: *const u8,
+    key_len: usize,
 ) -> FFIResult {
-    let key_array = match validate_key(key) {
-        Ok(k) => k,
-        Err(e) => return e,
-    };
+    instrumented("ffi.decrypt_fields_in_batch", || {
+        decrypt_fields_in_batch_json(
+            bytes(encrypted, encrypted_len)?,
+            c_str_bytes(fields_to_decrypt)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
 
-    let encrypted_slice = unsafe { slice::from_raw_parts(encrypted, encrypted_len) };
-    let fields_str = unsafe { CStr::from_ptr(fields_to_decrypt).to_str().unwrap() };
+/// Like `decrypt_fields_in_batch`, with the field list JSON passed as pointer and length.
+#[no_mangle]
+pub extern "C" fn decrypt_fields_in_batch_bytes(
+    encrypted: *const u8,
+    encrypted_len: usize,
+    fields_to_decrypt: *const u8,
+    fields_len: usize,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.decrypt_fields_in_batch_bytes", || {
+        decrypt_fields_in_batch_json(
+            bytes(encrypted, encrypted_len)?,
+            bytes(fields_to_decrypt, fields_len)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
 
-    let encrypted_records: Vec<Value> = serde_json::from_slice(encrypted_slice).unwrap();
-    let fields: Vec<String> = serde_json::from_str(fields_str).unwrap();
+fn decrypt_fields_in_batch_json(
+    records: &[u8],
+    fields: &[u8],
+    key: &[u8; 32],
+) -> Result<Vec<u8>, FfiError> {
+    let records: Vec<Value> = from_json(records)?;
+    let fields: Vec<String> = from_json(fields)?;
+    let decrypted = encryption::decrypt_fields_in_batch(&records, &fields, key)?;
+    Ok(serde_json::to_vec(&decrypted).unwrap())
+}
 
-    let result = encryption::decrypt_fields_in_batch(&encrypted_records, &fields, &key_array)
-        .map(|decrypted_records| serde_json::to_vec(&decrypted_records).unwrap());
+#[no_mangle]
+pub extern "C" fn mask_fields(record: *const c_char, masks: *const c_char) -> FFIResult {
+    instrumented("ffi.mask_fields", || {
+        mask_fields_json(c_str_bytes(record)?, c_str_bytes(masks)?)
+    })
+}
 
-    to_ffi_result(result)
+/// Like `mask_fields`, with the record and masks JSON passed as pointer and length.
+#[no_mangle]
+pub extern "C" fn mask_fields_bytes(
+    record: *const u8,
+    record_len: usize,
+    masks: *const u8,
+    masks_len: usize,
+) -> FFIResult {
+    instrumented("ffi.mask_fields_bytes", || {
+        mask_fields_json(bytes(record, record_len)?, bytes(masks, masks_len)?)
+    })
+}
+
+fn mask_fields_json(record: &[u8], masks: &[u8]) -> Result<Vec<u8>, FfiError> {
+    let record: Value = from_json(record)?;
+    let masks: FieldMasks = from_json(masks)?;
+    let masked = masking::mask_fields(&record, &masks)?;
+    Ok(serde_json::to_vec(&masked).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn decrypt_and_mask_fields(
+    encrypted: *const u8,
+    encrypted_len: usize,
+    masks: *const c_char,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.decrypt_and_mask_fields", || {
+        decrypt_and_mask_fields_json(
+            bytes(encrypted, encrypted_len)?,
+            c_str_bytes(masks)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
+
+/// Like `decrypt_and_mask_fields`, with the masks JSON passed as pointer and length.
+#[no_mangle]
+pub extern "C" fn decrypt_and_mask_fields_bytes(
+    encrypted: *const u8,
+    encrypted_len: usize,
+    masks: *const u8,
+    masks_len: usize,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.decrypt_and_mask_fields_bytes", || {
+        decrypt_and_mask_fields_json(
+            bytes(encrypted, encrypted_len)?,
+            bytes(masks, masks_len)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
+
+fn decrypt_and_mask_fields_json(
+    record: &[u8],
+    masks: &[u8],
+    key: &[u8; 32],
+) -> Result<Vec<u8>, FfiError> {
+    let record: Value = from_json(record)?;
+    let masks: FieldMasks = from_json(masks)?;
+    let masked = masking::decrypt_and_mask_fields(&record, &masks, key)?;
+    Ok(serde_json::to_vec(&masked).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn pseudonymize_fields(
+    record: *const c_char,
+    fields_to_pseudonymize: *const c_char,
+    domain: *const c_char,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.pseudonymize_fields", || {
+        pseudonymize_fields_json(
+            c_str_bytes(record)?,
+            c_str_bytes(fields_to_pseudonymize)?,
+            c_str_bytes(domain)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
+
+/// Like `pseudonymize_fields`, with the record JSON, field list JSON and domain passed as
+/// pointer and length.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn pseudonymize_fields_bytes(
+    record: *const u8,
+    record_len: usize,
+    fields_to_pseudonymize: *const u8,
+    fields_len: usize,
+    domain: *const u8,
+    domain_len: usize,
+    key: *const u8,
+    key_len: usize,
+) -> FFIResult {
+    instrumented("ffi.pseudonymize_fields_bytes", || {
+        pseudonymize_fields_json(
+            bytes(record, record_len)?,
+            bytes(fields_to_pseudonymize, fields_len)?,
+            bytes(domain, domain_len)?,
+            validate_key(key, key_len)?.expose_secret(),
+        )
+    })
+}
+
+fn pseudonymize_fields_json(
+    record: &[u8],
+    fields: &[u8],
+    domain: &[u8],
+    key: &[u8; 32],
+) -> Result<Vec<u8>, FfiError> {
+    let record: Value = from_json(record)?;
+    let fields: Vec<String> = from_json(fields)?;
+    let domain = to_str(domain)?;
+    let pseudonymized = pseudonymize::pseudonymize_fields(&record, &fields, domain, key)?;
+    Ok(serde_json::to_vec(&pseudonymized).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn inspect_ciphertext(ciphertext: *const u8, ciphertext_len: usize) -> FFIResult {
+    instrumented("ffi.inspect_ciphertext", || {
+        let info = inspect::inspect(bytes(ciphertext, ciphertext_len)?)?;
+        Ok(serde_json::to_vec(&info).unwrap())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn phi_fields_from_schema(schema: *const c_char) -> FFIResult {
+    instrumented("ffi.phi_fields_from_schema", || {
+        phi_fields_from_schema_json(c_str_bytes(schema)?)
+    })
+}
+
+/// Like `phi_fields_from_schema`, with the schema JSON passed as pointer and length.
+#[no_mangle]
+pub extern "C" fn phi_fields_from_schema_bytes(schema: *const u8, schema_len: usize) -> FFIResult {
+    instrumented("ffi.phi_fields_from_schema_bytes", || {
+        phi_fields_from_schema_json(bytes(schema, schema_len)?)
+    })
+}
+
+fn phi_fields_from_schema_json(schema: &[u8]) -> Result<Vec<u8>, FfiError> {
+    let schema: Value = from_json(schema)?;
+    let fields = schema::phi_fields(&schema)?;
+    Ok(serde_json::to_vec(&fields).unwrap())
 }
 
 #[no_mangle]
 pub extern "C" fn free_ffi_result(result: FFIResult) {
     if !result.data.data.is_null() {
         unsafe {
-            let _ = Box::from_raw(slice::from_raw_parts_mut(result.data.data, result.data.len));
+            let _ = Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                result.data.data,
+                result.data.len,
+            ));
         }
     }
 }
 
-// Add these new functions at the end of the file
-
 #[no_mangle]
 pub extern "C" fn free_byte_array(arr: ByteArray) {
     if !arr.data.is_null() {
         unsafe {
-            let _ = Box::from_raw(slice::from_raw_parts_mut(arr.data, arr.len));
+            let _ = Box::from_raw(std::ptr::slice_from_raw_parts_mut(arr.data, arr.len));
         }
     }
 }
 
+/// Frees a string allocated by this library. `s` must be null or a string it returned that
+/// has not been freed yet.
+// The exports are called from C, where `unsafe fn` adds nothing; the contract is documented
+// instead.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn free_c_char(s: *mut c_char) {
     if !s.is_null() {
@@ -239,9 +644,79 @@ pub extern "C" fn free_c_char(s: *mut c_char) {
     }
 }
 
+/// Returns every recorded operation metric as a JSON array, see [`metrics::snapshot`].
+#[no_mangle]
+pub extern "C" fn metrics_snapshot() -> FFIResult {
+    instrumented("ffi.metrics_snapshot", || {
+        Ok(serde_json::to_vec(&metrics::snapshot()).unwrap())
+    })
+}
+
+/// Configures the library's logging from a JSON [`LoggerConfig`], or with the defaults
+/// (`info`, JSON lines on stderr) when `config` is null. May be called again to reconfigure.
+#[no_mangle]
+pub extern "C" fn init_logger(config: *const c_char) -> FFIResult {
+    instrumented("ffi.init_logger", || {
+        if config.is_null() {
+            return init_default_logger();
+        }
+        init_logger_json(c_str_bytes(config)?)
+    })
+}
+
+/// Like `init_logger`, with the config JSON passed as pointer and length. A null `config`
+/// selects the defaults.
 #[no_mangle]
-pub extern "C" fn init_logger() {
-    // Initialize the logger here
-    // You may need to add a dependency on the `log` crate and implement this function
-    // For now, we'll leave it as a no-op
+pub extern "C" fn init_logger_bytes(config: *const u8, config_len: usize) -> FFIResult {
+    instrumented("ffi.init_logger_bytes", || {
+        if config.is_null() {
+            return init_default_logger();
+        }
+        init_logger_json(bytes(config, config_len)?)
+    })
+}
+
+fn init_default_logger() -> Result<Vec<u8>, FfiError> {
+    logger::init(LoggerConfig::default())?;
+    Ok(Vec::new())
+}
+
+fn init_logger_json(config: &[u8]) -> Result<Vec<u8>, FfiError> {
+    let config = from_json::<LoggerConfig>(config)?;
+    logger::init(config)?;
+    Ok(Vec::new())
+}
+
+/// Called with the level, message and context JSON of each library log entry, plus the
+/// `user_data` given at registration. May be called from any thread; the strings are only
+/// valid for the duration of the call.
+pub type LogCallback = extern "C" fn(
+    level: *const c_char,
+    message: *const c_char,
+    context: *const c_char,
+    user_data: *mut c_void,
+);
+
+/// Routes library log entries to `callback`, replacing any previous one, or stops routing
+/// them when `callback` is null. The callback must not call `set_log_callback`.
+#[no_mangle]
+pub extern "C" fn set_log_callback(callback: Option<LogCallback>, user_data: *mut c_void) {
+    instrumented_code("ffi.set_log_callback", || {
+        let handler = callback.map(|callback| {
+            let user_data = user_data as usize;
+            Box::new(move |level: &str, message: &str, context: &Value| {
+                let level = CString::new(level).unwrap_or_default();
+                let message = CString::new(message).unwrap_or_default();
+                let context = CString::new(context.to_string()).unwrap();
+                callback(
+                    level.as_ptr(),
+                    message.as_ptr(),
+                    context.as_ptr(),
+                    user_data as *mut c_void,
+                );
+            }) as logger::EntryHandler
+        });
+        logger::set_entry_handler(handler);
+        Ok(())
+    });
 }
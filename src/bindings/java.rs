@@ -0,0 +1,200 @@
+//! JNI exports for `com.polycrypt.PolyCrypt`, built with the `java` feature.
+//!
+//! Data is passed as `byte[]` and records as JSON strings, matching the Go and Python wrappers.
+//! A `PolyCryptError` is thrown as the matching `com.polycrypt.*Exception`, null arguments as
+//! `NullPointerException` and malformed JSON as `IllegalArgumentException`. The Java side lives
+//! in `examples/java`.
+
+use crate::crypto::encryption;
+use crate::error::PolyCryptError;
+use jni::objects::{JByteArray, JClass, JObjectArray, JString};
+use jni::JNIEnv;
+use serde_json::Value;
+
+enum JavaError {
+    PolyCrypt(PolyCryptError),
+    Jni(jni::errors::Error),
+    Json(serde_json::Error),
+}
+
+impl From<PolyCryptError> for JavaError {
+    fn from(error: PolyCryptError) -> Self {
+        JavaError::PolyCrypt(error)
+    }
+}
+
+impl From<jni::errors::Error> for JavaError {
+    fn from(error: jni::errors::Error) -> Self {
+        JavaError::Jni(error)
+    }
+}
+
+impl From<serde_json::Error> for JavaError {
+    fn from(error: serde_json::Error) -> Self {
+        JavaError::Json(error)
+    }
+}
+
+fn exception_class(error: &PolyCryptError) -> &'static str {
+    match error {
+        PolyCryptError::EncryptionError(_) => "com/polycrypt/EncryptionException",
+        PolyCryptError::DecryptionError(_)
+        | PolyCryptError::Base64DecodeError(_)
+        | PolyCryptError::Utf8Error(_) => "com/polycrypt/DecryptionException",
+        PolyCryptError::InvalidKeyError(_) => "com/polycrypt/InvalidKeyException",
+        PolyCryptError::InvalidPathError(_) => "com/polycrypt/InvalidPathException",
+        _ => "com/polycrypt/PolyCryptException",
+    }
+}
+
+// Runs `f` and turns its error into a pending Java exception, returning a null reference.
+fn throwing<'local, T: Default>(
+    env: &mut JNIEnv<'local>,
+    f: impl FnOnce(&mut JNIEnv<'local>) -> Result<T, JavaError>,
+) -> T {
+    match f(env) {
+        Ok(value) => value,
+        Err(error) => {
+            let (class, message) = match error {
+                // The JVM already has an exception pending for this call.
+                JavaError::Jni(jni::errors::Error::JavaException) => return T::default(),
+                JavaError::Jni(
+                    error @ (jni::errors::Error::NullPtr(_) | jni::errors::Error::NullDeref(_)),
+                ) => ("java/lang/NullPointerException", error.to_string()),
+                JavaError::Jni(error) => ("com/polycrypt/PolyCryptException", error.to_string()),
+                JavaError::Json(error) => (
+                    "java/lang/IllegalArgumentException",
+                    format!("Invalid JSON: {}", error),
+                ),
+                JavaError::PolyCrypt(error) => (exception_class(&error), error.to_string()),
+            };
+            let _ = env.throw_new(class, message);
+            T::default()
+        }
+    }
+}
+
+fn key_array(env: &JNIEnv, key: &JByteArray) -> Result<[u8; 32], JavaError> {
+    let key = env.convert_byte_array(key)?;
+    key.as_slice().try_into().map_err(|_| {
+        JavaError::PolyCrypt(PolyCryptError::InvalidKeyError(format!(
+            "expected 32 bytes, got {}",
+            key.len()
+        )))
+    })
+}
+
+fn to_fields(env: &mut JNIEnv, fields: &JObjectArray) -> Result<Vec<String>, JavaError> {
+    let len = env.get_array_length(fields)?;
+    let mut result = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        let field = JString::from(env.get_object_array_element(fields, i)?);
+        result.push(env.get_string(&field)?.into());
+    }
+    Ok(result)
+}
+
+fn to_json(env: &mut JNIEnv, json: &JString) -> Result<Value, JavaError> {
+    let json: String = env.get_string(json)?.into();
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// `static byte[] encrypt(byte[] plaintext, byte[] key)`
+#[no_mangle]
+pub extern "system" fn Java_com_polycrypt_PolyCrypt_encrypt<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    plaintext: JByteArray<'local>,
+    key: JByteArray<'local>,
+) -> JByteArray<'local> {
+    throwing(&mut env, |env| {
+        let plaintext = env.convert_byte_array(&plaintext)?;
+        let encrypted = encryption::encrypt(&plaintext, &key_array(env, &key)?)?;
+        Ok(env.byte_array_from_slice(&encrypted)?)
+    })
+}
+
+/// `static byte[] decrypt(byte[] ciphertext, byte[] key)`
+#[no_mangle]
+pub extern "system" fn Java_com_polycrypt_PolyCrypt_decrypt<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    ciphertext: JByteArray<'local>,
+    key: JByteArray<'local>,
+) -> JByteArray<'local> {
+    throwing(&mut env, |env| {
+        let ciphertext = env.convert_byte_array(&ciphertext)?;
+        let decrypted = encryption::decrypt(&ciphertext, &key_array(env, &key)?)?;
+        Ok(env.byte_array_from_slice(&decrypted)?)
+    })
+}
+
+/// `static String encryptFields(String recordJson, String[] fields, byte[] key)`
+#[no_mangle]
+pub extern "system" fn Java_com_polycrypt_PolyCrypt_encryptFields<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    record: JString<'local>,
+    fields: JObjectArray<'local>,
+    key: JByteArray<'local>,
+) -> JString<'local> {
+    throwing(&mut env, |env| {
+        let record = to_json(env, &record)?;
+        let fields = to_fields(env, &fields)?;
+        let encrypted = encryption::encrypt_fields(&record, &fields, &key_array(env, &key)?)?;
+        Ok(env.new_string(encrypted.to_string())?)
+    })
+}
+
+/// `static String decryptFields(String recordJson, String[] fields, byte[] key)`
+#[no_mangle]
+pub extern "system" fn Java_com_polycrypt_PolyCrypt_decryptFields<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    record: JString<'local>,
+    fields: JObjectArray<'local>,
+    key: JByteArray<'local>,
+) -> JString<'local> {
+    throwing(&mut env, |env| {
+        let record = to_json(env, &record)?;
+        let fields = to_fields(env, &fields)?;
+        let decrypted = encryption::decrypt_fields(&record, &fields, &key_array(env, &key)?)?;
+        Ok(env.new_string(decrypted.to_string())?)
+    })
+}
+
+/// `static String encryptFieldsInBatch(String recordsJson, String[] fields, byte[] key)`
+#[no_mangle]
+pub extern "system" fn Java_com_polycrypt_PolyCrypt_encryptFieldsInBatch<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    records: JString<'local>,
+    fields: JObjectArray<'local>,
+    key: JByteArray<'local>,
+) -> JString<'local> {
+    throwing(&mut env, |env| {
+        let records: Vec<Value> = serde_json::from_value(to_json(env, &records)?)?;
+        let fields = to_fields(env, &fields)?;
+        let encrypted =
+            encryption::encrypt_fields_in_batch(&records, &fields, &key_array(env, &key)?)?;
+        Ok(env.new_string(serde_json::to_string(&encrypted)?)?)
+    })
+}
+
+/// `static String decryptFieldsInBatch(String recordsJson, String[] fields, byte[] key)`
+#[no_mangle]
+pub extern "system" fn Java_com_polycrypt_PolyCrypt_decryptFieldsInBatch<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    records: JString<'local>,
+    fields: JObjectArray<'local>,
+    key: JByteArray<'local>,
+) -> JString<'local> {
+    throwing(&mut env, |env| {
+        let records: Vec<Value> = serde_json::from_value(to_json(env, &records)?)?;
+        let fields = to_fields(env, &fields)?;
+        let decrypted =
+            encryption::decrypt_fields_in_batch(&records, &fields, &key_array(env, &key)?)?;
+        Ok(env.new_string(serde_json::to_string(&decrypted)?)?)
+    })
+}
@@ -1 +1,9 @@
 pub mod ffi;
+#[cfg(feature = "java")]
+pub mod java;
+#[cfg(feature = "node")]
+pub mod node;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
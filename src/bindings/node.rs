@@ -0,0 +1,281 @@
+//! Node.js addon built on N-API, enabled by the `node` feature.
+//!
+//! Data is passed as `Buffer`s and records as plain objects. Every operation has an `*Async`
+//! variant that runs on the libuv worker pool and returns a `Promise`, so large batches do not
+//! block the event loop. Type definitions live in `examples/node/index.d.ts`.
+
+use crate::crypto::encryption;
+use crate::error::PolyCryptError;
+use crate::logger::Secret;
+use napi::bindgen_prelude::{AsyncTask, Buffer};
+use napi::{Env, Error, JsUnknown, Result, Status, Task};
+use napi_derive::napi;
+use serde_json::Value;
+
+impl From<PolyCryptError> for Error {
+    fn from(error: PolyCryptError) -> Self {
+        Error::new(Status::GenericFailure, error.to_string())
+    }
+}
+
+fn key_array(key: &[u8]) -> Result<[u8; 32]> {
+    key.try_into().map_err(|_| invalid_key(key.len()))
+}
+
+fn invalid_key(len: usize) -> Error {
+    Error::new(
+        Status::InvalidArg,
+        format!("Invalid key: expected 32 bytes, got {}", len),
+    )
+}
+
+// The key of a task, or the length of an invalid one. Keys are checked when the task runs,
+// so an invalid key rejects the promise like any other error.
+type TaskKey = std::result::Result<Secret<[u8; 32]>, usize>;
+
+fn task_key(key: &[u8]) -> TaskKey {
+    key.try_into().map(Secret::new).map_err(|_| key.len())
+}
+
+fn expose_key(key: &TaskKey) -> Result<&[u8; 32]> {
+    key.as_ref()
+        .map(Secret::expose_secret)
+        .map_err(|len| invalid_key(*len))
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Encrypt,
+    Decrypt,
+}
+
+/// Encrypts or decrypts a whole buffer.
+pub struct CryptTask {
+    operation: Operation,
+    data: Vec<u8>,
+    key: TaskKey,
+}
+
+impl Task for CryptTask {
+    type Output = Vec<u8>;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let key = expose_key(&self.key)?;
+        let result = match self.operation {
+            Operation::Encrypt => encryption::encrypt(&self.data, key),
+            Operation::Decrypt => encryption::decrypt(&self.data, key),
+        };
+        Ok(result?)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output.into())
+    }
+}
+
+/// Encrypts or decrypts fields of a single record.
+pub struct FieldsTask {
+    operation: Operation,
+    record: Value,
+    fields: Vec<String>,
+    key: TaskKey,
+}
+
+impl Task for FieldsTask {
+    type Output = Value;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let key = expose_key(&self.key)?;
+        let result = match self.operation {
+            Operation::Encrypt => encryption::encrypt_fields(&self.record, &self.fields, key),
+            Operation::Decrypt => encryption::decrypt_fields(&self.record, &self.fields, key),
+        };
+        Ok(result?)
+    }
+
+    fn resolve(&mut self, env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        env.to_js_value(&output)
+    }
+}
+
+/// Encrypts or decrypts fields of many records.
+pub struct BatchTask {
+    operation: Operation,
+    records: Vec<Value>,
+    fields: Vec<String>,
+    key: TaskKey,
+}
+
+impl Task for BatchTask {
+    type Output = Vec<Value>;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let key = expose_key(&self.key)?;
+        let result = match self.operation {
+            Operation::Encrypt => {
+                encryption::encrypt_fields_in_batch(&self.records, &self.fields, key)
+            }
+            Operation::Decrypt => {
+                encryption::decrypt_fields_in_batch(&self.records, &self.fields, key)
+            }
+        };
+        Ok(result?)
+    }
+
+    fn resolve(&mut self, env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        env.to_js_value(&output)
+    }
+}
+
+fn crypt(operation: Operation, data: &[u8], key: &[u8]) -> Result<Buffer> {
+    CryptTask {
+        operation,
+        data: data.to_vec(),
+        key: task_key(key),
+    }
+    .compute()
+    .map(Buffer::from)
+}
+
+/// encrypt(plaintext: Buffer, key: Buffer): Buffer
+#[napi]
+pub fn encrypt(plaintext: Buffer, key: Buffer) -> Result<Buffer> {
+    crypt(Operation::Encrypt, &plaintext, &key)
+}
+
+/// decrypt(ciphertext: Buffer, key: Buffer): Buffer
+#[napi]
+pub fn decrypt(ciphertext: Buffer, key: Buffer) -> Result<Buffer> {
+    crypt(Operation::Decrypt, &ciphertext, &key)
+}
+
+/// encryptFields(record: object, fields: string[], key: Buffer): object
+#[napi]
+pub fn encrypt_fields(record: Value, fields: Vec<String>, key: Buffer) -> Result<Value> {
+    Ok(encryption::encrypt_fields(
+        &record,
+        &fields,
+        &key_array(&key)?,
+    )?)
+}
+
+/// decryptFields(record: object, fields: string[], key: Buffer): object
+#[napi]
+pub fn decrypt_fields(record: Value, fields: Vec<String>, key: Buffer) -> Result<Value> {
+    Ok(encryption::decrypt_fields(
+        &record,
+        &fields,
+        &key_array(&key)?,
+    )?)
+}
+
+/// encryptFieldsInBatch(records: object[], fields: string[], key: Buffer): object[]
+#[napi]
+pub fn encrypt_fields_in_batch(
+    records: Vec<Value>,
+    fields: Vec<String>,
+    key: Buffer,
+) -> Result<Vec<Value>> {
+    Ok(encryption::encrypt_fields_in_batch(
+        &records,
+        &fields,
+        &key_array(&key)?,
+    )?)
+}
+
+/// decryptFieldsInBatch(records: object[], fields: string[], key: Buffer): object[]
+#[napi]
+pub fn decrypt_fields_in_batch(
+    records: Vec<Value>,
+    fields: Vec<String>,
+    key: Buffer,
+) -> Result<Vec<Value>> {
+    Ok(encryption::decrypt_fields_in_batch(
+        &records,
+        &fields,
+        &key_array(&key)?,
+    )?)
+}
+
+/// encryptAsync(plaintext: Buffer, key: Buffer): Promise<Buffer>
+#[napi]
+pub fn encrypt_async(plaintext: Buffer, key: Buffer) -> AsyncTask<CryptTask> {
+    AsyncTask::new(CryptTask {
+        operation: Operation::Encrypt,
+        data: plaintext.to_vec(),
+        key: task_key(&key),
+    })
+}
+
+/// decryptAsync(ciphertext: Buffer, key: Buffer): Promise<Buffer>
+#[napi]
+pub fn decrypt_async(ciphertext: Buffer, key: Buffer) -> AsyncTask<CryptTask> {
+    AsyncTask::new(CryptTask {
+        operation: Operation::Decrypt,
+        data: ciphertext.to_vec(),
+        key: task_key(&key),
+    })
+}
+
+/// encryptFieldsAsync(record: object, fields: string[], key: Buffer): Promise<object>
+#[napi]
+pub fn encrypt_fields_async(
+    record: Value,
+    fields: Vec<String>,
+    key: Buffer,
+) -> AsyncTask<FieldsTask> {
+    AsyncTask::new(FieldsTask {
+        operation: Operation::Encrypt,
+        record,
+        fields,
+        key: task_key(&key),
+    })
+}
+
+/// decryptFieldsAsync(record: object, fields: string[], key: Buffer): Promise<object>
+#[napi]
+pub fn decrypt_fields_async(
+    record: Value,
+    fields: Vec<String>,
+    key: Buffer,
+) -> AsyncTask<FieldsTask> {
+    AsyncTask::new(FieldsTask {
+        operation: Operation::Decrypt,
+        record,
+        fields,
+        key: task_key(&key),
+    })
+}
+
+/// encryptFieldsInBatchAsync(records: object[], fields: string[], key: Buffer): Promise<object[]>
+#[napi]
+pub fn encrypt_fields_in_batch_async(
+    records: Vec<Value>,
+    fields: Vec<String>,
+    key: Buffer,
+) -> AsyncTask<BatchTask> {
+    AsyncTask::new(BatchTask {
+        operation: Operation::Encrypt,
+        records,
+        fields,
+        key: task_key(&key),
+    })
+}
+
+/// decryptFieldsInBatchAsync(records: object[], fields: string[], key: Buffer): Promise<object[]>
+#[napi]
+pub fn decrypt_fields_in_batch_async(
+    records: Vec<Value>,
+    fields: Vec<String>,
+    key: Buffer,
+) -> AsyncTask<BatchTask> {
+    AsyncTask::new(BatchTask {
+        operation: Operation::Decrypt,
+        records,
+        fields,
+        key: task_key(&key),
+    })
+}
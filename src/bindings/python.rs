@@ -0,0 +1,399 @@
+//! Native Python extension module, built with the `python` feature.
+//!
+//! Records are passed as `dict`s and data as `bytes`. Errors are raised as subclasses of
+//! `polycrypt_rs.PolyCryptError`, and batch operations run without holding the GIL.
+
+use crate::crypto::encryption;
+use crate::crypto::keyring::Keyring;
+use crate::crypto::policy::EncryptionPolicy;
+use crate::error::PolyCryptError;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use pyo3::IntoPyObjectExt;
+use serde_json::{Map, Value};
+
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+
+    create_exception!(polycrypt_rs, PolyCryptError, PyException);
+    create_exception!(polycrypt_rs, EncryptionError, PolyCryptError);
+    create_exception!(polycrypt_rs, DecryptionError, PolyCryptError);
+    create_exception!(polycrypt_rs, InvalidKeyError, PolyCryptError);
+    create_exception!(polycrypt_rs, InvalidPathError, PolyCryptError);
+    create_exception!(polycrypt_rs, PolicyError, PolyCryptError);
+    create_exception!(polycrypt_rs, ShreddedSubjectError, PolyCryptError);
+}
+
+impl From<PolyCryptError> for PyErr {
+    fn from(error: PolyCryptError) -> Self {
+        let message = error.to_string();
+        match error {
+            PolyCryptError::EncryptionError(_) => exceptions::EncryptionError::new_err(message),
+            PolyCryptError::DecryptionError(_)
+            | PolyCryptError::Base64DecodeError(_)
+            | PolyCryptError::Utf8Error(_) => exceptions::DecryptionError::new_err(message),
+            PolyCryptError::InvalidKeyError(_) => exceptions::InvalidKeyError::new_err(message),
+            PolyCryptError::InvalidPathError(_) => exceptions::InvalidPathError::new_err(message),
+            PolyCryptError::SchemaError(_) | PolyCryptError::PolicyError(_) => {
+                exceptions::PolicyError::new_err(message)
+            }
+            PolyCryptError::ShreddedSubjectError(_) => {
+                exceptions::ShreddedSubjectError::new_err(message)
+            }
+            _ => exceptions::PolyCryptError::new_err(message),
+        }
+    }
+}
+
+fn key_array(key: &[u8]) -> PyResult<[u8; 32]> {
+    key.try_into().map_err(|_| {
+        exceptions::InvalidKeyError::new_err(format!(
+            "Invalid key: expected 32 bytes, got {}",
+            key.len()
+        ))
+    })
+}
+
+fn to_value(object: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if object.is_none() {
+        Ok(Value::Null)
+    } else if let Ok(value) = object.cast::<PyBool>() {
+        Ok(Value::Bool(value.is_true()))
+    } else if let Ok(value) = object.cast::<PyInt>() {
+        match value.extract::<i64>() {
+            Ok(number) => Ok(Value::from(number)),
+            Err(_) => Ok(Value::from(value.extract::<u64>()?)),
+        }
+    } else if let Ok(value) = object.cast::<PyFloat>() {
+        Ok(serde_json::Number::from_f64(value.value())
+            .map(Value::Number)
+            .unwrap_or(Value::Null))
+    } else if let Ok(value) = object.cast::<PyString>() {
+        Ok(Value::String(value.to_str()?.to_string()))
+    } else if let Ok(dict) = object.cast::<PyDict>() {
+        let mut map = Map::new();
+        for (key, value) in dict.iter() {
+            let key = key
+                .cast::<PyString>()
+                .map_err(|_| PyTypeError::new_err("Record keys must be strings"))?;
+            map.insert(key.to_str()?.to_string(), to_value(&value)?);
+        }
+        Ok(Value::Object(map))
+    } else if let Ok(list) = object.cast::<PyList>() {
+        list.iter().map(|item| to_value(&item)).collect()
+    } else if let Ok(tuple) = object.cast::<PyTuple>() {
+        tuple.iter().map(|item| to_value(&item)).collect()
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "Cannot convert {} to JSON",
+            object.get_type().name()?
+        )))
+    }
+}
+
+fn to_object<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    match value {
+        Value::Null => Ok(py.None().into_bound(py)),
+        Value::Bool(value) => value.into_bound_py_any(py),
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(value), _) => value.into_bound_py_any(py),
+            (_, Some(value)) => value.into_bound_py_any(py),
+            _ => number.as_f64().unwrap_or_default().into_bound_py_any(py),
+        },
+        Value::String(value) => value.into_bound_py_any(py),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(to_object(py, item)?)?;
+            }
+            Ok(list.into_any())
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, to_object(py, value)?)?;
+            }
+            Ok(dict.into_any())
+        }
+    }
+}
+
+fn to_records(records: &Bound<'_, PyAny>) -> PyResult<Vec<Value>> {
+    match to_value(records)? {
+        Value::Array(records) => Ok(records),
+        _ => Err(PyTypeError::new_err("Expected a list of records")),
+    }
+}
+
+fn to_list<'py>(py: Python<'py>, records: &[Value]) -> PyResult<Bound<'py, PyAny>> {
+    let list = PyList::empty(py);
+    for record in records {
+        list.append(to_object(py, record)?)?;
+    }
+    Ok(list.into_any())
+}
+
+/// encrypt(plaintext: bytes, key: bytes) -> bytes
+#[pyfunction]
+#[pyo3(name = "encrypt")]
+fn py_encrypt<'py>(py: Python<'py>, plaintext: &[u8], key: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let encrypted = encryption::encrypt(plaintext, &key_array(key)?)?;
+    Ok(PyBytes::new(py, &encrypted))
+}
+
+/// decrypt(ciphertext: bytes, key: bytes) -> bytes
+#[pyfunction]
+#[pyo3(name = "decrypt")]
+fn py_decrypt<'py>(
+    py: Python<'py>,
+    ciphertext: &[u8],
+    key: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+    let decrypted = encryption::decrypt(ciphertext, &key_array(key)?)?;
+    Ok(PyBytes::new(py, &decrypted))
+}
+
+/// encrypt_fields(record: dict, fields: list[str], key: bytes) -> dict
+#[pyfunction]
+#[pyo3(name = "encrypt_fields")]
+fn py_encrypt_fields<'py>(
+    py: Python<'py>,
+    record: &Bound<'py, PyAny>,
+    fields: Vec<String>,
+    key: &[u8],
+) -> PyResult<Bound<'py, PyAny>> {
+    let encrypted = encryption::encrypt_fields(&to_value(record)?, &fields, &key_array(key)?)?;
+    to_object(py, &encrypted)
+}
+
+/// decrypt_fields(record: dict, fields: list[str], key: bytes) -> dict
+#[pyfunction]
+#[pyo3(name = "decrypt_fields")]
+fn py_decrypt_fields<'py>(
+    py: Python<'py>,
+    record: &Bound<'py, PyAny>,
+    fields: Vec<String>,
+    key: &[u8],
+) -> PyResult<Bound<'py, PyAny>> {
+    let decrypted = encryption::decrypt_fields(&to_value(record)?, &fields, &key_array(key)?)?;
+    to_object(py, &decrypted)
+}
+
+/// encrypt_fields_in_batch(records: list[dict], fields: list[str], key: bytes) -> list[dict]
+#[pyfunction]
+#[pyo3(name = "encrypt_fields_in_batch")]
+fn py_encrypt_fields_in_batch<'py>(
+    py: Python<'py>,
+    records: &Bound<'py, PyAny>,
+    fields: Vec<String>,
+    key: &[u8],
+) -> PyResult<Bound<'py, PyAny>> {
+    let records = to_records(records)?;
+    let key = key_array(key)?;
+    let encrypted = py.detach(|| encryption::encrypt_fields_in_batch(&records, &fields, &key))?;
+    to_list(py, &encrypted)
+}
+
+/// decrypt_fields_in_batch(records: list[dict], fields: list[str], key: bytes) -> list[dict]
+#[pyfunction]
+#[pyo3(name = "decrypt_fields_in_batch")]
+fn py_decrypt_fields_in_batch<'py>(
+    py: Python<'py>,
+    records: &Bound<'py, PyAny>,
+    fields: Vec<String>,
+    key: &[u8],
+) -> PyResult<Bound<'py, PyAny>> {
+    let records = to_records(records)?;
+    let key = key_array(key)?;
+    let decrypted = py.detach(|| encryption::decrypt_fields_in_batch(&records, &fields, &key))?;
+    to_list(py, &decrypted)
+}
+
+/// Named keys for policy-driven record encryption.
+#[pyclass(name = "Keyring")]
+struct PyKeyring {
+    inner: Keyring,
+}
+
+#[pymethods]
+impl PyKeyring {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: Keyring::new(),
+        }
+    }
+
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        Ok(Self {
+            inner: Keyring::from_json(json)?,
+        })
+    }
+
+    #[staticmethod]
+    fn from_file(path: &str) -> PyResult<Self> {
+        Ok(Self {
+            inner: Keyring::from_file(path)?,
+        })
+    }
+
+    fn insert(&mut self, key_id: &str, key: &[u8]) -> PyResult<()> {
+        self.inner.insert(key_id, key_array(key)?);
+        Ok(())
+    }
+
+    fn set_primary(&mut self, key_id: &str) -> PyResult<()> {
+        Ok(self.inner.set_primary(key_id)?)
+    }
+
+    #[getter]
+    fn primary_key_id(&self) -> Option<&str> {
+        self.inner.primary_key_id()
+    }
+
+    fn key_ids(&self) -> Vec<String> {
+        self.inner.key_ids().map(str::to_string).collect()
+    }
+
+    fn __contains__(&self, key_id: &str) -> bool {
+        self.inner.contains(key_id)
+    }
+}
+
+/// Policy-driven record encryption, see `polycrypt_rs::PolyCrypt`.
+#[pyclass(name = "PolyCrypt")]
+struct PyPolyCrypt {
+    inner: crate::PolyCrypt,
+}
+
+#[pymethods]
+impl PyPolyCrypt {
+    #[new]
+    #[pyo3(signature = (context=None))]
+    fn new(context: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let context = match context {
+            Some(context) => to_value(context)?,
+            None => Value::Object(Map::new()),
+        };
+        Ok(Self {
+            inner: crate::PolyCrypt::new(context),
+        })
+    }
+
+    /// Installs a policy given as a dict, validated against `keyring`.
+    fn load_policy(&mut self, policy: &Bound<'_, PyAny>, keyring: &PyKeyring) -> PyResult<()> {
+        let policy = EncryptionPolicy::from_json(&to_value(policy)?.to_string())?;
+        Ok(self.inner.load_policy(policy, keyring.inner.clone())?)
+    }
+
+    /// Installs a policy from a JSON or TOML file, validated against `keyring`.
+    fn load_policy_file(&mut self, path: &str, keyring: &PyKeyring) -> PyResult<()> {
+        let policy = EncryptionPolicy::from_file(path)?;
+        Ok(self.inner.load_policy(policy, keyring.inner.clone())?)
+    }
+
+    fn encrypt_record<'py>(
+        &self,
+        py: Python<'py>,
+        record_type: &str,
+        record: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let encrypted = self.inner.encrypt_record(record_type, &to_value(record)?)?;
+        to_object(py, &encrypted)
+    }
+
+    fn decrypt_record<'py>(
+        &self,
+        py: Python<'py>,
+        record_type: &str,
+        record: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let decrypted = self.inner.decrypt_record(record_type, &to_value(record)?)?;
+        to_object(py, &decrypted)
+    }
+
+    fn mask_record<'py>(
+        &self,
+        py: Python<'py>,
+        record_type: &str,
+        record: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let masked = self.inner.mask_record(record_type, &to_value(record)?)?;
+        to_object(py, &masked)
+    }
+
+    fn encrypt_records<'py>(
+        &self,
+        py: Python<'py>,
+        record_type: &str,
+        records: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let records = to_records(records)?;
+        let encrypted = py.detach(|| {
+            records
+                .iter()
+                .map(|record| self.inner.encrypt_record(record_type, record))
+                .collect::<Result<Vec<Value>, PolyCryptError>>()
+        })?;
+        to_list(py, &encrypted)
+    }
+
+    fn decrypt_records<'py>(
+        &self,
+        py: Python<'py>,
+        record_type: &str,
+        records: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let records = to_records(records)?;
+        let decrypted = py.detach(|| {
+            records
+                .iter()
+                .map(|record| self.inner.decrypt_record(record_type, record))
+                .collect::<Result<Vec<Value>, PolyCryptError>>()
+        })?;
+        to_list(py, &decrypted)
+    }
+}
+
+#[pymodule]
+fn polycrypt_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add(
+        "PolyCryptError",
+        py.get_type::<exceptions::PolyCryptError>(),
+    )?;
+    m.add(
+        "EncryptionError",
+        py.get_type::<exceptions::EncryptionError>(),
+    )?;
+    m.add(
+        "DecryptionError",
+        py.get_type::<exceptions::DecryptionError>(),
+    )?;
+    m.add(
+        "InvalidKeyError",
+        py.get_type::<exceptions::InvalidKeyError>(),
+    )?;
+    m.add(
+        "InvalidPathError",
+        py.get_type::<exceptions::InvalidPathError>(),
+    )?;
+    m.add("PolicyError", py.get_type::<exceptions::PolicyError>())?;
+    m.add(
+        "ShreddedSubjectError",
+        py.get_type::<exceptions::ShreddedSubjectError>(),
+    )?;
+
+    m.add_function(wrap_pyfunction!(py_encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(py_decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(py_encrypt_fields, m)?)?;
+    m.add_function(wrap_pyfunction!(py_decrypt_fields, m)?)?;
+    m.add_function(wrap_pyfunction!(py_encrypt_fields_in_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(py_decrypt_fields_in_batch, m)?)?;
+    m.add_class::<PyKeyring>()?;
+    m.add_class::<PyPolyCrypt>()?;
+    Ok(())
+}
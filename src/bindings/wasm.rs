@@ -0,0 +1,100 @@
+//! WebAssembly exports, built with the `wasm` feature for `wasm32-unknown-unknown`.
+//!
+//! Data is passed as `Uint8Array`s and records as plain objects. Failures are thrown as JS
+//! `Error`s carrying the `PolyCryptError` message. Randomness comes from the host's
+//! `crypto.getRandomValues` through getrandom's `js` backend.
+
+use crate::crypto::encryption;
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+fn key_array(key: &[u8]) -> Result<[u8; 32], JsError> {
+    key.try_into().map_err(|_| {
+        JsError::new(&format!(
+            "Invalid key: expected 32 bytes, got {}",
+            key.len()
+        ))
+    })
+}
+
+fn from_js<T: serde::de::DeserializeOwned>(value: JsValue) -> Result<T, JsError> {
+    Ok(serde_wasm_bindgen::from_value(value)?)
+}
+
+// Without the JSON-compatible serializer, objects would come back as JS `Map`s.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+/// encrypt(plaintext: Uint8Array, key: Uint8Array): Uint8Array
+#[wasm_bindgen]
+pub fn encrypt(plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(encryption::encrypt(plaintext, &key_array(key)?)?)
+}
+
+/// decrypt(ciphertext: Uint8Array, key: Uint8Array): Uint8Array
+#[wasm_bindgen]
+pub fn decrypt(ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(encryption::decrypt(ciphertext, &key_array(key)?)?)
+}
+
+/// encryptFields(record: object, fields: string[], key: Uint8Array): object
+#[wasm_bindgen(js_name = encryptFields)]
+pub fn encrypt_fields(
+    record: JsValue,
+    fields: Vec<String>,
+    key: &[u8],
+) -> Result<JsValue, JsError> {
+    let record: Value = from_js(record)?;
+    to_js(&encryption::encrypt_fields(
+        &record,
+        &fields,
+        &key_array(key)?,
+    )?)
+}
+
+/// decryptFields(record: object, fields: string[], key: Uint8Array): object
+#[wasm_bindgen(js_name = decryptFields)]
+pub fn decrypt_fields(
+    record: JsValue,
+    fields: Vec<String>,
+    key: &[u8],
+) -> Result<JsValue, JsError> {
+    let record: Value = from_js(record)?;
+    to_js(&encryption::decrypt_fields(
+        &record,
+        &fields,
+        &key_array(key)?,
+    )?)
+}
+
+/// encryptFieldsInBatch(records: object[], fields: string[], key: Uint8Array): object[]
+#[wasm_bindgen(js_name = encryptFieldsInBatch)]
+pub fn encrypt_fields_in_batch(
+    records: JsValue,
+    fields: Vec<String>,
+    key: &[u8],
+) -> Result<JsValue, JsError> {
+    let records: Vec<Value> = from_js(records)?;
+    to_js(&encryption::encrypt_fields_in_batch(
+        &records,
+        &fields,
+        &key_array(key)?,
+    )?)
+}
+
+/// decryptFieldsInBatch(records: object[], fields: string[], key: Uint8Array): object[]
+#[wasm_bindgen(js_name = decryptFieldsInBatch)]
+pub fn decrypt_fields_in_batch(
+    records: JsValue,
+    fields: Vec<String>,
+    key: &[u8],
+) -> Result<JsValue, JsError> {
+    let records: Vec<Value> = from_js(records)?;
+    to_js(&encryption::decrypt_fields_in_batch(
+        &records,
+        &fields,
+        &key_array(key)?,
+    )?)
+}
@@ -0,0 +1,170 @@
+//! Typed field encryption through serde.
+//!
+//! [`Encrypted<T>`] holds a plaintext value that serializes as ciphertext and deserializes by
+//! decrypting, in the same format [`encrypt_fields`](crate::crypto::encryption::encrypt_fields)
+//! produces: a string becomes a base64 envelope and an array of strings an array of them.
+//! `null`, e.g. an `Option` that is `None`, is written and read as `null`.
+//! Plain fields can opt in with `#[serde(with = "polycrypt_rs::crypto::encrypted")]`, or with
+//! `#[polycrypt(encrypt)]` under the `derive` feature's `#[polycrypt]` attribute.
+//!
+//! Serde has no way to pass a key to a field, so (de)serialization must run inside
+//! [`with_key`], which makes the key available to the current thread:
+//!
+//! ```
+//! use polycrypt_rs::crypto::encrypted::{self, Encrypted};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Patient {
+//!     id: String,
+//!     name: Encrypted<String>,
+//!     #[serde(with = "polycrypt_rs::crypto::encrypted")]
+//!     phones: Vec<String>,
+//! }
+//!
+//! let key = [0u8; 32];
+//! let patient = Patient {
+//!     id: "1234".to_string(),
+//!     name: Encrypted::new("John Doe".to_string()),
+//!     phones: vec!["555-0100".to_string()],
+//! };
+//! let json = encrypted::with_key(&key, || serde_json::to_string(&patient)).unwrap();
+//! let decrypted: Patient = encrypted::with_key(&key, || serde_json::from_str(&json)).unwrap();
+//! assert_eq!(*decrypted.name, "John Doe");
+//! ```
+//!
+//! Unlike `decrypt_fields`, deserialization is strict: a value that is not encrypted is an
+//! error rather than being accepted as plaintext.
+
+use crate::crypto::encryption::{self, FieldCipher, FieldOptions};
+use crate::logger::Secret;
+use crate::transform::masking::REDACTED;
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+const NO_KEY: &str = "No encryption key in scope; (de)serialize inside `encrypted::with_key`";
+
+thread_local! {
+    static KEY: RefCell<Option<Secret<[u8; 32]>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `key` as the key used by [`Encrypted`] and [`serialize`]/[`deserialize`] on
+/// this thread. Calls may be nested; the previous key is restored when `f` returns or panics.
+pub fn with_key<R>(key: &[u8; 32], f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Secret<[u8; 32]>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            KEY.with(|key| *key.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(KEY.with(|current| current.replace(Some(Secret::new(*key)))));
+    f()
+}
+
+fn current_key() -> Option<Secret<[u8; 32]>> {
+    KEY.with(|key| key.borrow().clone())
+}
+
+/// Serializes `value` as ciphertext, for `#[serde(with = "polycrypt_rs::crypto::encrypted")]`.
+/// `value` must serialize to a string, an array of strings, or `null`.
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + ?Sized,
+    S: Serializer,
+{
+    let key = current_key().ok_or_else(|| ser::Error::custom(NO_KEY))?;
+    let value = serde_json::to_value(value).map_err(ser::Error::custom)?;
+    if value.is_null() {
+        return serializer.serialize_none();
+    }
+    let cipher = FieldCipher {
+        key: key.expose_secret(),
+        key_id: None,
+        deterministic: false,
+        strict: false,
+    };
+    encryption::encrypt_value(&value, &cipher)
+        .map_err(ser::Error::custom)?
+        .serialize(serializer)
+}
+
+/// Deserializes a value written by [`serialize`], decrypting it first. Values other than
+/// `null` must be encrypted.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: DeserializeOwned,
+    D: Deserializer<'de>,
+{
+    let key = current_key().ok_or_else(|| de::Error::custom(NO_KEY))?;
+    let value = Value::deserialize(deserializer)?;
+    if value.is_null() {
+        return T::deserialize(value).map_err(de::Error::custom);
+    }
+    let strict = FieldOptions {
+        strict: true,
+        ..FieldOptions::default()
+    };
+    let decrypted = encryption::decrypt_value(&value, &|_| Ok(key.expose_secret()), &strict)
+        .map_err(de::Error::custom)?;
+    T::deserialize(decrypted).map_err(de::Error::custom)
+}
+
+/// A plaintext value that is encrypted when serialized and decrypted when deserialized, with
+/// the key given to [`with_key`]. Its `Debug` output is redacted.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Encrypted<T>(T);
+
+impl<T> Encrypted<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Encrypted<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Encrypted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Encrypted<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> fmt::Debug for Encrypted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Encrypted({})", REDACTED)
+    }
+}
+
+impl<T: Serialize> Serialize for Encrypted<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Encrypted<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(Self)
+    }
+}
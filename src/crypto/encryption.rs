@@ -1,55 +1,195 @@
+use crate::crypto::envelope::{self, Algorithm, Header};
+use crate::crypto::path::FieldPath;
 use crate::error::PolyCryptError;
+use crate::metrics::{self, Instant};
 use crate::Logger;
 use aes::Aes256;
 use base64;
 use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use cipher::block_padding::Pkcs7;
+use hmac::{Hmac, Mac};
 use log::debug;
 use rand::Rng;
 use serde_json::{json, Value};
+use sha2::Sha256;
 
-const AES_BLOCK_SIZE: usize = 16;
+pub(crate) const AES_BLOCK_SIZE: usize = 16;
 
-// comment out info logging for encrypt/decrypt since it is called a lot and logging is expensive and not useful for production
+// Labels used to derive independent subkeys from a field key, so the same key is never
+// used directly for both AES and HMAC.
+const SIV_KEY_LABEL: &[u8] = b"polycrypt-rs/siv";
+const BLIND_INDEX_KEY_LABEL: &[u8] = b"polycrypt-rs/blind-index";
+
+type HmacSha256 = Hmac<Sha256>;
+
+// encrypt/decrypt run once per value, so they only log failures and trace at debug level.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(algorithm = "aes-256-cbc"))
+)]
 pub fn encrypt(plaintext: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, PolyCryptError> {
-    let logger = Logger::new(json!({"operation": "encryption"}));
-    // logger.info("Starting encryption", Some(json!({"plaintext_length": plaintext.len()})));
+    metrics::observe("encrypt", || {
+        let mut rng = rand::thread_rng();
+        let mut iv = [0u8; AES_BLOCK_SIZE];
+        rng.fill(&mut iv);
+
+        encrypt_with_iv(plaintext, key, &iv)
+    })
+}
+
+/// Encrypts `plaintext` with an IV derived from the plaintext itself, so equal inputs under
+/// the same key always produce equal ciphertexts. The output is decrypted with [`decrypt`].
+///
+/// Deterministic ciphertexts leak equality; only use this for fields that must be joinable
+/// or searchable by exact match.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(algorithm = "aes-256-cbc"))
+)]
+pub fn encrypt_deterministic(plaintext: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, PolyCryptError> {
+    metrics::observe("encrypt_deterministic", || {
+        let digest = hmac_sha256(&derive_subkey(key, SIV_KEY_LABEL), plaintext);
+        let mut iv = [0u8; AES_BLOCK_SIZE];
+        iv.copy_from_slice(&digest[..AES_BLOCK_SIZE]);
+
+        encrypt_with_iv(plaintext, key, &iv)
+    })
+}
+
+/// Computes a keyed, hex-encoded blind index of `value` that can be stored next to its
+/// ciphertext and queried by exact match without decrypting.
+pub fn blind_index(value: &[u8], key: &[u8; 32]) -> String {
+    let start = Instant::now();
+    let index = hex::encode(hmac_sha256(
+        &derive_subkey(key, BLIND_INDEX_KEY_LABEL),
+        value,
+    ));
+    metrics::record("blind_index", start.elapsed(), true);
+    index
+}
+
+pub(crate) fn derive_subkey(key: &[u8; 32], label: &[u8]) -> [u8; 32] {
+    hmac_sha256(key, label)
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// Length of the output of [`encrypt`] for `plaintext_len` bytes of plaintext: the IV followed
+/// by the PKCS#7-padded ciphertext. Returns 0 if the length does not fit in a `usize`.
+pub fn ciphertext_len(plaintext_len: usize) -> usize {
+    (plaintext_len / AES_BLOCK_SIZE + 1)
+        .checked_mul(AES_BLOCK_SIZE)
+        .and_then(|padded| padded.checked_add(AES_BLOCK_SIZE))
+        .unwrap_or(0)
+}
+
+/// Like [`encrypt`], but writes the ciphertext into `out` instead of allocating, returning the
+/// number of bytes written. `out` must hold at least [`ciphertext_len`] bytes.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(algorithm = "aes-256-cbc"))
+)]
+pub fn encrypt_into(
+    plaintext: &[u8],
+    key: &[u8; 32],
+    out: &mut [u8],
+) -> Result<usize, PolyCryptError> {
+    metrics::observe("encrypt_into", || {
+        let mut rng = rand::thread_rng();
+        let mut iv = [0u8; AES_BLOCK_SIZE];
+        rng.fill(&mut iv);
+
+        encrypt_with_iv_into(plaintext, key, &iv, out)
+    })
+}
+
+fn encrypt_with_iv(
+    plaintext: &[u8],
+    key: &[u8; 32],
+    iv: &[u8; AES_BLOCK_SIZE],
+) -> Result<Vec<u8>, PolyCryptError> {
+    let mut result = vec![0u8; ciphertext_len(plaintext.len())];
+    let len = encrypt_with_iv_into(plaintext, key, iv, &mut result)?;
+    result.truncate(len);
+    Ok(result)
+}
 
-    let mut rng = rand::thread_rng();
-    let mut iv = [0u8; AES_BLOCK_SIZE];
-    rng.fill(&mut iv);
-    // logger.info("IV generated", None);
+fn encrypt_with_iv_into(
+    plaintext: &[u8],
+    key: &[u8; 32],
+    iv: &[u8; AES_BLOCK_SIZE],
+    out: &mut [u8],
+) -> Result<usize, PolyCryptError> {
+    let required = ciphertext_len(plaintext.len());
+    if required == 0 {
+        return Err(PolyCryptError::EncryptionError(
+            "Plaintext too large to encrypt".to_string(),
+        ));
+    }
+    if out.len() < required {
+        return Err(PolyCryptError::EncryptionError(format!(
+            "Output buffer too small: need {} bytes, got {}",
+            required,
+            out.len()
+        )));
+    }
 
-    let cipher = cbc::Encryptor::<Aes256>::new(key.into(), &iv.into());
-    let mut buffer = vec![0u8; plaintext.len() + AES_BLOCK_SIZE];
+    let (iv_out, buffer) = out.split_at_mut(AES_BLOCK_SIZE);
+    iv_out.copy_from_slice(iv);
+    let cipher = cbc::Encryptor::<Aes256>::new(key.into(), iv.into());
     let ciphertext_len = cipher
-        .encrypt_padded_b2b_mut::<Pkcs7>(plaintext, &mut buffer)
+        .encrypt_padded_b2b_mut::<Pkcs7>(plaintext, buffer)
         .map_err(|e| {
-            logger.error("Encryption failed", Some(json!({"error": e.to_string()})));
+            Logger::new(json!({"operation": "encryption"}))
+                .error("Encryption failed", Some(json!({"error": e.to_string()})));
             PolyCryptError::EncryptionError(e.to_string())
         })?
         .len();
 
-    let mut result = Vec::with_capacity(iv.len() + ciphertext_len);
-    result.extend_from_slice(&iv);
-    result.extend_from_slice(&buffer[..ciphertext_len]);
-
-    /*
-    logger.info("Encryption completed", Some(json!({
-        "plaintext_length": plaintext.len(),
-        "ciphertext_length": result.len()
-    })));
-    */
+    Ok(AES_BLOCK_SIZE + ciphertext_len)
+}
 
-    // If using zeroize: buffer.zeroize();
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(algorithm = "aes-256-cbc"))
+)]
+pub fn decrypt(ciphertext: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, PolyCryptError> {
+    metrics::observe("decrypt", || decrypt_payload(ciphertext, key))
+}
 
-    Ok(result)
+/// Like [`decrypt`], but writes the plaintext into `out` instead of allocating, returning the
+/// number of bytes written. `out` must hold at least `ciphertext.len() - 16` bytes; a buffer
+/// as long as the ciphertext always suffices.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(algorithm = "aes-256-cbc"))
+)]
+pub fn decrypt_into(
+    ciphertext: &[u8],
+    key: &[u8; 32],
+    out: &mut [u8],
+) -> Result<usize, PolyCryptError> {
+    metrics::observe("decrypt_into", || {
+        decrypt_payload_into(ciphertext, key, out)
+    })
 }
 
-pub fn decrypt(ciphertext: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, PolyCryptError> {
-    let logger = Logger::new(json!({"operation": "decryption"}));
-    // logger.info("Starting decryption", Some(json!({"ciphertext_length": ciphertext.len()})));
+fn decrypt_payload(ciphertext: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, PolyCryptError> {
+    let mut buffer = vec![0u8; ciphertext.len().saturating_sub(AES_BLOCK_SIZE)];
+    let plaintext_len = decrypt_payload_into(ciphertext, key, &mut buffer)?;
+    buffer.truncate(plaintext_len);
+    Ok(buffer)
+}
 
+fn decrypt_payload_into(
+    ciphertext: &[u8],
+    key: &[u8; 32],
+    out: &mut [u8],
+) -> Result<usize, PolyCryptError> {
     if ciphertext.len() < AES_BLOCK_SIZE {
         return Err(PolyCryptError::DecryptionError(
             "Ciphertext too short".to_string(),
@@ -57,32 +197,86 @@ pub fn decrypt(ciphertext: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, PolyCryptEr
     }
 
     let (iv, ciphertext) = ciphertext.split_at(AES_BLOCK_SIZE);
+    if out.len() < ciphertext.len() {
+        return Err(PolyCryptError::DecryptionError(format!(
+            "Output buffer too small: need {} bytes, got {}",
+            ciphertext.len(),
+            out.len()
+        )));
+    }
+
     let cipher = cbc::Decryptor::<Aes256>::new(key.into(), iv.into());
-    let mut buffer = ciphertext.to_vec();
     let plaintext_len = cipher
-        .decrypt_padded_mut::<Pkcs7>(&mut buffer)
+        .decrypt_padded_b2b_mut::<Pkcs7>(ciphertext, out)
         .map_err(|e| {
-            logger.error("Decryption failed", Some(json!({"error": e.to_string()})));
+            Logger::new(json!({"operation": "decryption"}))
+                .error("Decryption failed", Some(json!({"error": e.to_string()})));
             PolyCryptError::DecryptionError(e.to_string())
         })?
         .len();
 
-    buffer.truncate(plaintext_len);
+    Ok(plaintext_len)
+}
 
-    /*
-    logger.info("Decryption completed", Some(json!({
-        "ciphertext_length": ciphertext.len(),
-        "plaintext_length": buffer.len()
-    })));
-    */
+/// Options for the field-level functions.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldOptions {
+    /// Fail on values that are already encrypted when encrypting, or not encrypted when
+    /// decrypting, instead of leaving them unchanged.
+    pub strict: bool,
+    /// When decrypting, also accept unmarked ciphertexts written before values carried an
+    /// envelope. On by default so stored data keeps decrypting after an upgrade. Plaintext
+    /// such as a hex digest can have the same shape, so unless `strict` is set, values that
+    /// fail to decrypt as legacy ciphertexts are left unchanged; turn this off once all
+    /// stored data has been re-encrypted.
+    pub legacy: bool,
+}
 
-    Ok(buffer)
+impl Default for FieldOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            legacy: true,
+        }
+    }
 }
 
+/// Decrypts the fields named by `fields_to_decrypt`, which may be nested or array paths as
+/// described by [`FieldPath`]. Values that are not encrypted are left unchanged.
+///
+/// Not audited; see [`crate::audit`].
 pub fn decrypt_fields(
     record: &Value,
     fields_to_decrypt: &[String],
     key: &[u8; 32],
+) -> Result<Value, PolyCryptError> {
+    decrypt_fields_with_options(record, fields_to_decrypt, key, &FieldOptions::default())
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "decrypt_fields",
+        skip_all,
+        fields(field_count = fields_to_decrypt.len())
+    )
+)]
+pub fn decrypt_fields_with_options(
+    record: &Value,
+    fields_to_decrypt: &[String],
+    key: &[u8; 32],
+    options: &FieldOptions,
+) -> Result<Value, PolyCryptError> {
+    metrics::observe("decrypt_fields", || {
+        decrypt_record_fields(record, fields_to_decrypt, key, options)
+    })
+}
+
+fn decrypt_record_fields(
+    record: &Value,
+    fields_to_decrypt: &[String],
+    key: &[u8; 32],
+    options: &FieldOptions,
 ) -> Result<Value, PolyCryptError> {
     let logger = Logger::new(json!({"operation": "decrypt_fields"}));
     logger.info(
@@ -90,39 +284,47 @@ pub fn decrypt_fields(
         Some(json!({"fields": fields_to_decrypt})),
     );
 
-    let mut decrypted_record = record.clone();
-
-    for field in fields_to_decrypt {
-        if let Some(encrypted_value) = record.get(field) {
-            debug!("Decrypting field: {}", field);
-            let decrypted_value = if encrypted_value.is_array() {
-                let array = encrypted_value.as_array().unwrap();
-                let decrypted_array: Result<Vec<String>, PolyCryptError> = array
-                    .iter()
-                    .map(|item| {
-                        let ciphertext = base64::decode(item.as_str().unwrap())?;
-                        let decrypted = decrypt(&ciphertext, key)?;
-                        Ok(String::from_utf8(decrypted)?)
-                    })
-                    .collect();
-                Value::Array(decrypted_array?.into_iter().map(Value::String).collect())
-            } else {
-                let ciphertext = base64::decode(encrypted_value.as_str().unwrap())?;
-                let decrypted = decrypt(&ciphertext, key)?;
-                Value::String(String::from_utf8(decrypted)?)
-            };
-            decrypted_record[field] = decrypted_value;
-        }
-    }
+    let decrypted_record = decrypt_paths(record, fields_to_decrypt, &|_| Ok(key), options)?;
 
     logger.info("Field decryption completed", None);
     Ok(decrypted_record)
 }
 
+/// Encrypts the fields named by `fields_to_encrypt`, which may be nested or array paths as
+/// described by [`FieldPath`]. Values that are already encrypted are left unchanged, so a
+/// record can safely go through the same pipeline twice.
 pub fn encrypt_fields(
     record: &Value,
     fields_to_encrypt: &[String],
     key: &[u8; 32],
+) -> Result<Value, PolyCryptError> {
+    encrypt_fields_with_options(record, fields_to_encrypt, key, &FieldOptions::default())
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "encrypt_fields",
+        skip_all,
+        fields(field_count = fields_to_encrypt.len())
+    )
+)]
+pub fn encrypt_fields_with_options(
+    record: &Value,
+    fields_to_encrypt: &[String],
+    key: &[u8; 32],
+    options: &FieldOptions,
+) -> Result<Value, PolyCryptError> {
+    metrics::observe("encrypt_fields", || {
+        encrypt_record_fields(record, fields_to_encrypt, key, options)
+    })
+}
+
+fn encrypt_record_fields(
+    record: &Value,
+    fields_to_encrypt: &[String],
+    key: &[u8; 32],
+    options: &FieldOptions,
 ) -> Result<Value, PolyCryptError> {
     let logger = Logger::new(json!({"operation": "encrypt_fields"}));
     logger.info(
@@ -130,43 +332,176 @@ pub fn encrypt_fields(
         Some(json!({"fields": fields_to_encrypt})),
     );
 
+    let cipher = FieldCipher {
+        key,
+        key_id: None,
+        deterministic: false,
+        strict: options.strict,
+    };
+    let encrypted_record = encrypt_paths(record, fields_to_encrypt, &cipher)?;
+
+    logger.info("Field encryption completed", None);
+    Ok(encrypted_record)
+}
+
+/// Encrypts the values at `fields` of a copy of `record` with [`encrypt_value`].
+pub(crate) fn encrypt_paths(
+    record: &Value,
+    fields: &[String],
+    cipher: &FieldCipher,
+) -> Result<Value, PolyCryptError> {
     let mut encrypted_record = record.clone();
+    for field in fields {
+        metrics::observe_field("encrypt_field", field, || {
+            FieldPath::parse(field)?.visit_mut(&mut encrypted_record, &mut |value: &mut Value| {
+                *value = encrypt_value(value, cipher)?;
+                Ok(())
+            })
+        })?;
+    }
+    Ok(encrypted_record)
+}
+
+/// Decrypts the values at `fields` of a copy of `record` with [`decrypt_value`].
+pub(crate) fn decrypt_paths<'k, F>(
+    record: &Value,
+    fields: &[String],
+    resolve_key: &F,
+    options: &FieldOptions,
+) -> Result<Value, PolyCryptError>
+where
+    F: Fn(Option<&str>) -> Result<&'k [u8; 32], PolyCryptError>,
+{
+    let mut decrypted_record = record.clone();
+    for field in fields {
+        debug!("Decrypting field: {}", field);
+        metrics::observe_field("decrypt_field", field, || {
+            FieldPath::parse(field)?.visit_mut(&mut decrypted_record, &mut |value: &mut Value| {
+                *value = decrypt_value(value, resolve_key, options)?;
+                Ok(())
+            })
+        })?;
+    }
+    Ok(decrypted_record)
+}
 
-    for field in fields_to_encrypt {
-        if let Some(plaintext_value) = record.get(field) {
-            let encrypted_value = if plaintext_value.is_array() {
-                let array = plaintext_value.as_array().unwrap();
-                let encrypted_array: Result<Vec<String>, PolyCryptError> = array
-                    .iter()
-                    .map(|item| {
-                        let plaintext = item.as_str().unwrap().as_bytes();
-                        let encrypted = encrypt(plaintext, key)?;
-                        Ok(base64::encode(encrypted))
-                    })
-                    .collect();
-                Value::Array(encrypted_array?.into_iter().map(Value::String).collect())
+/// How [`encrypt_value`] encrypts and labels a value.
+pub(crate) struct FieldCipher<'a> {
+    pub key: &'a [u8; 32],
+    pub key_id: Option<&'a str>,
+    pub deterministic: bool,
+    pub strict: bool,
+}
+
+/// Encrypts a string, or each string of an array, into a base64 envelope.
+pub(crate) fn encrypt_value(value: &Value, cipher: &FieldCipher) -> Result<Value, PolyCryptError> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| encrypt_value(item, cipher))
+            .collect::<Result<Vec<Value>, PolyCryptError>>()
+            .map(Value::Array),
+        Value::String(plaintext) if envelope::is_encrypted_value(plaintext) => {
+            if cipher.strict {
+                return Err(PolyCryptError::EncryptionError(
+                    "Value is already encrypted".to_string(),
+                ));
+            }
+            Ok(value.clone())
+        }
+        Value::String(plaintext) => {
+            let payload = if cipher.deterministic {
+                encrypt_deterministic(plaintext.as_bytes(), cipher.key)?
             } else {
-                let plaintext = plaintext_value.as_str().unwrap().as_bytes();
-                let encrypted = encrypt(plaintext, key)?;
-                Value::String(base64::encode(encrypted))
+                encrypt(plaintext.as_bytes(), cipher.key)?
             };
-            encrypted_record[field] = encrypted_value;
+            let header = Header::new(Algorithm::Aes256Cbc, cipher.key_id, cipher.deterministic);
+            Ok(Value::String(base64::encode(envelope::seal(
+                &header, &payload,
+            )?)))
         }
+        _ => Err(PolyCryptError::EncryptionError(
+            "Only string values and arrays of strings can be encrypted".to_string(),
+        )),
     }
+}
 
-    logger.info("Field encryption completed", None);
-    Ok(encrypted_record)
+/// Reverses [`encrypt_value`]. `resolve_key` receives the key id recorded in the envelope,
+/// if any. With [`FieldOptions::legacy`], the default, unmarked values that have the shape
+/// of a pre-envelope ciphertext are decrypted as such; any other unmarked value is plaintext.
+pub(crate) fn decrypt_value<'k, F>(
+    value: &Value,
+    resolve_key: &F,
+    options: &FieldOptions,
+) -> Result<Value, PolyCryptError>
+where
+    F: Fn(Option<&str>) -> Result<&'k [u8; 32], PolyCryptError>,
+{
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| decrypt_value(item, resolve_key, options))
+            .collect::<Result<Vec<Value>, PolyCryptError>>()
+            .map(Value::Array),
+        Value::String(encoded) => {
+            let bytes = base64::decode(encoded).unwrap_or_default();
+            if envelope::is_envelope(&bytes) {
+                let (header, payload) = envelope::open(&bytes)?;
+                let decrypted = decrypt(payload, resolve_key(header.key_id.as_deref())?)?;
+                Ok(Value::String(String::from_utf8(decrypted)?))
+            } else if options.legacy && envelope::is_legacy_ciphertext(&bytes) {
+                let key = resolve_key(None)?;
+                match decrypt(&bytes, key).and_then(|decrypted| Ok(String::from_utf8(decrypted)?)) {
+                    Ok(plaintext) => Ok(Value::String(plaintext)),
+                    Err(e) if options.strict => Err(e),
+                    // Plaintext that merely looks like a legacy ciphertext.
+                    Err(_) => Ok(value.clone()),
+                }
+            } else if options.strict {
+                Err(PolyCryptError::DecryptionError(
+                    "Value is not encrypted".to_string(),
+                ))
+            } else {
+                Ok(value.clone())
+            }
+        }
+        _ if options.strict => Err(PolyCryptError::DecryptionError(
+            "Encrypted values must be base64 strings or arrays of them".to_string(),
+        )),
+        _ => Ok(value.clone()),
+    }
 }
 
+/// Decrypts the same fields of every record, as [`decrypt_fields`] does. Not audited; see
+/// [`crate::audit`].
 pub fn decrypt_fields_in_batch(
     records: &[Value],
     fields_to_decrypt: &[String],
     key: &[u8; 32],
 ) -> Result<Vec<Value>, PolyCryptError> {
-    records
-        .iter()
-        .map(|record| decrypt_fields(record, fields_to_decrypt, key))
-        .collect()
+    decrypt_fields_in_batch_with_options(records, fields_to_decrypt, key, &FieldOptions::default())
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "decrypt_fields_in_batch",
+        skip_all,
+        fields(record_count = records.len(), field_count = fields_to_decrypt.len())
+    )
+)]
+pub fn decrypt_fields_in_batch_with_options(
+    records: &[Value],
+    fields_to_decrypt: &[String],
+    key: &[u8; 32],
+    options: &FieldOptions,
+) -> Result<Vec<Value>, PolyCryptError> {
+    metrics::observe("decrypt_fields_in_batch", || {
+        records
+            .iter()
+            .map(|record| decrypt_fields_with_options(record, fields_to_decrypt, key, options))
+            .collect()
+    })
 }
 
 pub fn encrypt_fields_in_batch(
@@ -174,10 +509,29 @@ pub fn encrypt_fields_in_batch(
     fields_to_encrypt: &[String],
     key: &[u8; 32],
 ) -> Result<Vec<Value>, PolyCryptError> {
-    records
-        .iter()
-        .map(|record| encrypt_fields(record, fields_to_encrypt, key))
-        .collect()
+    encrypt_fields_in_batch_with_options(records, fields_to_encrypt, key, &FieldOptions::default())
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "encrypt_fields_in_batch",
+        skip_all,
+        fields(record_count = records.len(), field_count = fields_to_encrypt.len())
+    )
+)]
+pub fn encrypt_fields_in_batch_with_options(
+    records: &[Value],
+    fields_to_encrypt: &[String],
+    key: &[u8; 32],
+    options: &FieldOptions,
+) -> Result<Vec<Value>, PolyCryptError> {
+    metrics::observe("encrypt_fields_in_batch", || {
+        records
+            .iter()
+            .map(|record| encrypt_fields_with_options(record, fields_to_encrypt, key, options))
+            .collect()
+    })
 }
 
 #[cfg(test)]
@@ -197,6 +551,43 @@ mod tests {
         assert_eq!(decrypted, plaintext);
     }
 
+    #[test]
+    fn test_encrypt_decrypt_into() {
+        let key = [0u8; 32];
+        for plaintext in [&b""[..], b"Hello, world!", &[7u8; 16], &[7u8; 100]] {
+            assert_eq!(
+                ciphertext_len(plaintext.len()),
+                encrypt(plaintext, &key).unwrap().len()
+            );
+
+            let mut ciphertext = vec![0u8; ciphertext_len(plaintext.len())];
+            let written = encrypt_into(plaintext, &key, &mut ciphertext).unwrap();
+            assert_eq!(written, ciphertext.len());
+            assert_eq!(decrypt(&ciphertext, &key).unwrap(), plaintext);
+
+            let mut decrypted = vec![0u8; ciphertext.len()];
+            let written = decrypt_into(&ciphertext, &key, &mut decrypted).unwrap();
+            assert_eq!(&decrypted[..written], plaintext);
+        }
+
+        let mut small = [0u8; 31];
+        assert!(encrypt_into(b"Hello, world!", &key, &mut small).is_err());
+        let ciphertext = encrypt(b"Hello, world!", &key).unwrap();
+        assert!(decrypt_into(&ciphertext, &key, &mut small[..15]).is_err());
+    }
+
+    #[test]
+    fn test_ciphertext_len_overflow() {
+        assert_eq!(ciphertext_len(usize::MAX), 0);
+        assert_eq!(ciphertext_len(usize::MAX - (AES_BLOCK_SIZE - 1)), 0);
+        assert_eq!(
+            ciphertext_len(usize::MAX - 2 * AES_BLOCK_SIZE),
+            usize::MAX - (AES_BLOCK_SIZE - 1)
+        );
+        assert_eq!(ciphertext_len(15), 32);
+        assert_eq!(ciphertext_len(16), 48);
+    }
+
     #[test]
     fn test_encrypt_decrypt_fields() {
         let key = [0u8; 32]; // Use a fixed key for testing
@@ -244,6 +635,89 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_encrypt_fields_is_idempotent() {
+        let key = [0u8; 32];
+        let record = json!({"name": "John Doe", "tags": ["a", "b"], "count": 3});
+        let fields = vec!["name".to_string(), "tags".to_string()];
+
+        let once = encrypt_fields(&record, &fields, &key).unwrap();
+        assert!(envelope::is_encrypted_value(once["name"].as_str().unwrap()));
+        let twice = encrypt_fields(&once, &fields, &key).unwrap();
+        assert_eq!(once, twice);
+        assert_eq!(decrypt_fields(&twice, &fields, &key).unwrap(), record);
+
+        // Plaintext values pass through decryption untouched.
+        assert_eq!(decrypt_fields(&record, &fields, &key).unwrap(), record);
+
+        let strict = FieldOptions {
+            strict: true,
+            ..FieldOptions::default()
+        };
+        assert!(encrypt_fields_with_options(&once, &fields, &key, &strict).is_err());
+        assert!(decrypt_fields_with_options(&record, &fields, &key, &strict).is_err());
+        assert!(
+            encrypt_fields_with_options(&record, &["count".to_string()], &key, &strict).is_err()
+        );
+    }
+
+    #[test]
+    fn test_decrypt_fields_accepts_legacy_values() {
+        let key = [0u8; 32];
+        let legacy = json!({"name": base64::encode(encrypt(b"John Doe", &key).unwrap())});
+        let fields = vec!["name".to_string()];
+
+        let decrypted = decrypt_fields(&legacy, &fields, &key).unwrap();
+        assert_eq!(decrypted, json!({"name": "John Doe"}));
+        let strict = FieldOptions {
+            strict: true,
+            ..FieldOptions::default()
+        };
+        let decrypted = decrypt_fields_with_options(&legacy, &fields, &key, &strict).unwrap();
+        assert_eq!(decrypted, json!({"name": "John Doe"}));
+
+        // Once migrated, legacy ciphertexts can be treated as plaintext.
+        let envelopes_only = FieldOptions {
+            legacy: false,
+            ..FieldOptions::default()
+        };
+        assert_eq!(
+            decrypt_fields_with_options(&legacy, &fields, &key, &envelopes_only).unwrap(),
+            legacy
+        );
+    }
+
+    #[test]
+    fn test_decrypt_fields_passes_through_digest_shaped_plaintext() {
+        let key = [0u8; 32];
+        // A hex SHA-256 digest is valid base64 of 48 bytes, the shape of a legacy ciphertext.
+        let record = json!({
+            "checksum": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        });
+        let fields = vec!["checksum".to_string()];
+
+        assert_eq!(decrypt_fields(&record, &fields, &key).unwrap(), record);
+        let strict = FieldOptions {
+            strict: true,
+            ..FieldOptions::default()
+        };
+        assert!(decrypt_fields_with_options(&record, &fields, &key, &strict).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_deterministic() {
+        let key = [0u8; 32];
+
+        let first = encrypt_deterministic(b"123-45-6789", &key).unwrap();
+        let second = encrypt_deterministic(b"123-45-6789", &key).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, encrypt_deterministic(b"123-45-6780", &key).unwrap());
+        assert_eq!(decrypt(&first, &key).unwrap(), b"123-45-6789");
+
+        assert_eq!(blind_index(b"a", &key), blind_index(b"a", &key));
+        assert_ne!(blind_index(b"a", &key), blind_index(b"a", &[1u8; 32]));
+    }
+
     #[test]
     fn test_decryption_error() {
         let invalid_ciphertext = vec![0u8; 15]; // Too short for valid ciphertext
@@ -0,0 +1,178 @@
+use crate::error::PolyCryptError;
+use serde::{Deserialize, Serialize};
+
+/// Leading bytes of every envelope.
+pub const MAGIC: &[u8; 2] = b"PC";
+pub const FORMAT_VERSION: u8 = 1;
+/// Base64 form of [`MAGIC`] followed by [`FORMAT_VERSION`]; every encrypted field value
+/// starts with it.
+pub const ENCRYPTED_VALUE_PREFIX: &str = "UEMB";
+
+const AES_BLOCK_SIZE: usize = 16;
+const FLAG_DETERMINISTIC: u8 = 0b0000_0001;
+// magic + version + algorithm + flags + key id length
+const FIXED_HEADER_LEN: usize = 6;
+
+/// Cipher that produced a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Algorithm {
+    #[default]
+    #[serde(rename = "aes-256-cbc")]
+    Aes256Cbc,
+}
+
+impl Algorithm {
+    pub fn id(self) -> u8 {
+        match self {
+            Algorithm::Aes256Cbc => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Algorithm::Aes256Cbc),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Aes256Cbc => "aes-256-cbc",
+        }
+    }
+
+    pub fn nonce_len(self) -> usize {
+        match self {
+            Algorithm::Aes256Cbc => AES_BLOCK_SIZE,
+        }
+    }
+}
+
+/// Metadata stored in front of an encrypted payload.
+///
+/// Layout: `"PC" | version | algorithm | flags | key id length | key id | payload`, where the
+/// payload is the IV-prefixed ciphertext produced by
+/// [`encrypt`](crate::crypto::encryption::encrypt).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub algorithm: Algorithm,
+    pub deterministic: bool,
+    pub key_id: Option<String>,
+}
+
+impl Header {
+    pub fn new(algorithm: Algorithm, key_id: Option<&str>, deterministic: bool) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            algorithm,
+            deterministic,
+            key_id: key_id.map(str::to_string),
+        }
+    }
+}
+
+/// Prepends `header` to `payload`.
+pub fn seal(header: &Header, payload: &[u8]) -> Result<Vec<u8>, PolyCryptError> {
+    let key_id = header.key_id.as_deref().unwrap_or("").as_bytes();
+    let key_id_len = u8::try_from(key_id.len()).map_err(|_| {
+        PolyCryptError::EncryptionError("Key id must be at most 255 bytes long".to_string())
+    })?;
+    let flags = if header.deterministic {
+        FLAG_DETERMINISTIC
+    } else {
+        0
+    };
+
+    let mut sealed = Vec::with_capacity(FIXED_HEADER_LEN + key_id.len() + payload.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&[header.version, header.algorithm.id(), flags, key_id_len]);
+    sealed.extend_from_slice(key_id);
+    sealed.extend_from_slice(payload);
+    Ok(sealed)
+}
+
+/// Splits an envelope into its header and payload.
+pub fn open(bytes: &[u8]) -> Result<(Header, &[u8]), PolyCryptError> {
+    parse(bytes).ok_or_else(|| {
+        PolyCryptError::DecryptionError("Value is not a polycrypt envelope".to_string())
+    })
+}
+
+pub fn is_envelope(bytes: &[u8]) -> bool {
+    parse(bytes).is_some()
+}
+
+/// Whether a field value is a base64 envelope, i.e. already encrypted.
+pub fn is_encrypted_value(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_VALUE_PREFIX)
+        && base64::decode(value).is_ok_and(|bytes| is_envelope(&bytes))
+}
+
+/// Whether `bytes` have the shape of a pre-envelope ciphertext: a 16-byte IV followed by
+/// at least one AES block.
+pub fn is_legacy_ciphertext(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 * AES_BLOCK_SIZE && bytes.len().is_multiple_of(AES_BLOCK_SIZE)
+}
+
+fn parse(bytes: &[u8]) -> Option<(Header, &[u8])> {
+    let (fixed, rest) = bytes.split_at_checked(FIXED_HEADER_LEN)?;
+    let [m0, m1, version, algorithm, flags, key_id_len] = fixed.try_into().ok()?;
+    if [m0, m1] != *MAGIC || version != FORMAT_VERSION || flags & !FLAG_DETERMINISTIC != 0 {
+        return None;
+    }
+    let algorithm = Algorithm::from_id(algorithm)?;
+    let (key_id, payload) = rest.split_at_checked(usize::from(key_id_len))?;
+    let key_id = std::str::from_utf8(key_id).ok()?;
+    if !is_legacy_ciphertext(payload) {
+        return None;
+    }
+
+    let header = Header {
+        version,
+        algorithm,
+        deterministic: flags & FLAG_DETERMINISTIC != 0,
+        key_id: (!key_id.is_empty()).then(|| key_id.to_string()),
+    };
+    Some((header, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let payload = [7u8; 32];
+        let header = Header::new(Algorithm::Aes256Cbc, Some("phi-2024"), true);
+
+        let sealed = seal(&header, &payload).unwrap();
+        assert!(base64::encode(&sealed).starts_with(ENCRYPTED_VALUE_PREFIX));
+        assert!(is_encrypted_value(&base64::encode(&sealed)));
+
+        let (opened, opened_payload) = open(&sealed).unwrap();
+        assert_eq!(opened, header);
+        assert_eq!(opened_payload, payload);
+
+        let anonymous = seal(&Header::new(Algorithm::Aes256Cbc, None, false), &payload).unwrap();
+        assert_eq!(open(&anonymous).unwrap().0.key_id, None);
+    }
+
+    #[test]
+    fn test_rejects_non_envelopes() {
+        assert!(!is_envelope(&[0u8; 48]));
+        assert!(!is_envelope(b"PC"));
+        assert!(!is_encrypted_value("UEMBlooks like a prefix but is not"));
+        assert!(!is_encrypted_value("John Doe"));
+
+        let mut sealed = seal(&Header::new(Algorithm::Aes256Cbc, None, false), &[0u8; 32]).unwrap();
+        sealed.truncate(sealed.len() - 1);
+        assert!(open(&sealed).is_err());
+
+        assert!(seal(
+            &Header::new(Algorithm::Aes256Cbc, Some(&"k".repeat(256)), false),
+            &[0u8; 32]
+        )
+        .is_err());
+    }
+}
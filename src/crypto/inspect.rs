@@ -0,0 +1,129 @@
+use crate::crypto::envelope::{self, Algorithm};
+use crate::error::PolyCryptError;
+use serde::Serialize;
+
+/// How a ciphertext is framed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CiphertextFormat {
+    /// A polycrypt envelope with a header.
+    Envelope,
+    /// A bare IV-prefixed AES-256-CBC ciphertext, as written before envelopes existed.
+    Legacy,
+}
+
+/// What can be told about a ciphertext without decrypting it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CiphertextInfo {
+    pub format: CiphertextFormat,
+    /// Envelope format version; `0` for legacy ciphertexts.
+    pub version: u8,
+    pub algorithm: Algorithm,
+    pub key_id: Option<String>,
+    pub deterministic: bool,
+    pub nonce_len: usize,
+    /// Length of the encrypted data, excluding the header and nonce.
+    pub payload_len: usize,
+    /// Whether the input was a base64 field value rather than binary ciphertext.
+    pub base64: bool,
+}
+
+/// Describes `ciphertext`, which may be binary output of
+/// [`encrypt`](crate::crypto::encryption::encrypt) or an envelope, or a base64 field value
+/// produced by [`encrypt_fields`](crate::crypto::encryption::encrypt_fields).
+///
+/// Legacy ciphertexts carry no header, so they are only recognized by their shape: a
+/// 16-byte IV followed by whole AES blocks. Anything else is reported as an error.
+pub fn inspect(ciphertext: &[u8]) -> Result<CiphertextInfo, PolyCryptError> {
+    if envelope::is_envelope(ciphertext) {
+        return inspect_binary(ciphertext, false);
+    }
+    if let Some(decoded) = decode_base64_text(ciphertext) {
+        if let Ok(info) = inspect_binary(&decoded, true) {
+            return Ok(info);
+        }
+    }
+    inspect_binary(ciphertext, false)
+}
+
+fn inspect_binary(bytes: &[u8], base64: bool) -> Result<CiphertextInfo, PolyCryptError> {
+    if let Ok((header, payload)) = envelope::open(bytes) {
+        let nonce_len = header.algorithm.nonce_len();
+        return Ok(CiphertextInfo {
+            format: CiphertextFormat::Envelope,
+            version: header.version,
+            algorithm: header.algorithm,
+            key_id: header.key_id,
+            deterministic: header.deterministic,
+            nonce_len,
+            payload_len: payload.len().saturating_sub(nonce_len),
+            base64,
+        });
+    }
+    if envelope::is_legacy_ciphertext(bytes) {
+        let nonce_len = Algorithm::Aes256Cbc.nonce_len();
+        return Ok(CiphertextInfo {
+            format: CiphertextFormat::Legacy,
+            version: 0,
+            algorithm: Algorithm::Aes256Cbc,
+            key_id: None,
+            deterministic: false,
+            nonce_len,
+            payload_len: bytes.len() - nonce_len,
+            base64,
+        });
+    }
+    Err(PolyCryptError::DecryptionError(
+        "Value is not a recognized ciphertext".to_string(),
+    ))
+}
+
+fn decode_base64_text(bytes: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(bytes).ok()?.trim();
+    base64::decode(text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::encryption;
+    use serde_json::json;
+
+    #[test]
+    fn test_inspect_envelopes_and_field_values() {
+        let key = [0u8; 32];
+        let encrypted =
+            encryption::encrypt_fields(&json!({"name": "John Doe"}), &["name".to_string()], &key)
+                .unwrap();
+        let info = inspect(encrypted["name"].as_str().unwrap().as_bytes()).unwrap();
+        assert_eq!(info.format, CiphertextFormat::Envelope);
+        assert_eq!(info.version, envelope::FORMAT_VERSION);
+        assert_eq!(info.algorithm, Algorithm::Aes256Cbc);
+        assert_eq!(info.nonce_len, 16);
+        assert_eq!(info.payload_len, 16);
+        assert!(info.base64);
+
+        let header = envelope::Header::new(Algorithm::Aes256Cbc, Some("phi-2024"), true);
+        let sealed = envelope::seal(&header, &encryption::encrypt(b"x", &key).unwrap()).unwrap();
+        let info = inspect(&sealed).unwrap();
+        assert_eq!(info.key_id.as_deref(), Some("phi-2024"));
+        assert!(info.deterministic);
+        assert!(!info.base64);
+    }
+
+    #[test]
+    fn test_inspect_legacy_and_garbage() {
+        let legacy = encryption::encrypt(&[1u8; 20], &[0u8; 32]).unwrap();
+        let info = inspect(&legacy).unwrap();
+        assert_eq!(info.format, CiphertextFormat::Legacy);
+        assert_eq!(info.version, 0);
+        assert_eq!(info.payload_len, 32);
+
+        let info = inspect(base64::encode(&legacy).as_bytes()).unwrap();
+        assert_eq!(info.format, CiphertextFormat::Legacy);
+        assert!(info.base64);
+
+        assert!(inspect(b"John Doe").is_err());
+        assert!(inspect(&[0u8; 17]).is_err());
+    }
+}
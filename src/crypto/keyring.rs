@@ -0,0 +1,173 @@
+use crate::error::PolyCryptError;
+use crate::logger::Secret;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// A set of named 32-byte keys. Policies and ciphertexts refer to keys by id, never by value.
+#[derive(Clone, Default)]
+pub struct Keyring {
+    keys: HashMap<String, Secret<[u8; 32]>>,
+    primary: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct KeyringFile {
+    primary: Option<String>,
+    keys: HashMap<String, String>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a keyring document of the form
+    /// `{"primary": "k1", "keys": {"k1": "<base64 32-byte key>"}}`.
+    pub fn from_json(json: &str) -> Result<Self, PolyCryptError> {
+        let file: KeyringFile = serde_json::from_str(json)
+            .map_err(|e| PolyCryptError::InvalidKeyError(format!("Invalid keyring: {}", e)))?;
+
+        let mut keyring = Self::new();
+        for (key_id, encoded) in file.keys {
+            let key = decode_key(&encoded)
+                .map_err(|e| PolyCryptError::InvalidKeyError(format!("Key '{}': {}", key_id, e)))?;
+            keyring.insert(key_id, key);
+        }
+        if let Some(primary) = file.primary {
+            keyring.set_primary(&primary)?;
+        }
+        Ok(keyring)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PolyCryptError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn insert(&mut self, key_id: impl Into<String>, key: [u8; 32]) {
+        self.keys.insert(key_id.into(), Secret::new(key));
+    }
+
+    pub fn set_primary(&mut self, key_id: &str) -> Result<(), PolyCryptError> {
+        if !self.contains(key_id) {
+            return Err(unknown_key(key_id));
+        }
+        self.primary = Some(key_id.to_string());
+        Ok(())
+    }
+
+    pub fn primary_key_id(&self) -> Option<&str> {
+        self.primary.as_deref()
+    }
+
+    pub fn contains(&self, key_id: &str) -> bool {
+        self.keys.contains_key(key_id)
+    }
+
+    pub fn get(&self, key_id: &str) -> Result<&[u8; 32], PolyCryptError> {
+        self.keys
+            .get(key_id)
+            .map(Secret::expose_secret)
+            .ok_or_else(|| unknown_key(key_id))
+    }
+
+    pub fn key_ids(&self) -> impl Iterator<Item = &str> {
+        self.keys.keys().map(String::as_str)
+    }
+}
+
+// Key material is deliberately left out of debug output.
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut key_ids: Vec<&str> = self.key_ids().collect();
+        key_ids.sort_unstable();
+        f.debug_struct("Keyring")
+            .field("keys", &key_ids)
+            .field("primary", &self.primary)
+            .finish()
+    }
+}
+
+/// Decodes a base64 key and checks that it is exactly 32 bytes long.
+pub fn decode_key(encoded: &str) -> Result<[u8; 32], PolyCryptError> {
+    let bytes = base64::decode(encoded.trim())?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        PolyCryptError::InvalidKeyError(format!("Key must be 32 bytes long, got {}", bytes.len()))
+    })
+}
+
+/// Replaces `path` with `contents`, readable by the owner only on Unix. The contents are
+/// written and synced to a new file next to `path`, which is then renamed over it, so a
+/// crash or a full disk leaves either the old file or the new one, never a truncated one.
+pub fn write_key_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Key file path has no file name",
+        )
+    })?);
+    temp_name.push(format!(".{:08x}.tmp", rand::random::<u32>()));
+    let temp_path = dir.join(temp_name);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options.open(&temp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    written?;
+    // Make the rename itself durable.
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn unknown_key(key_id: &str) -> PolyCryptError {
+    PolyCryptError::InvalidKeyError(format!("Unknown key id '{}'", key_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyring_from_json() {
+        let json = format!(
+            r#"{{"primary": "k2", "keys": {{"k1": "{}", "k2": "{}"}}}}"#,
+            base64::encode([1u8; 32]),
+            base64::encode([2u8; 32])
+        );
+        let keyring = Keyring::from_json(&json).unwrap();
+
+        assert_eq!(keyring.primary_key_id(), Some("k2"));
+        assert_eq!(keyring.get("k1").unwrap(), &[1u8; 32]);
+        assert!(keyring.get("k3").is_err());
+    }
+
+    #[test]
+    fn test_keyring_rejects_short_keys_and_unknown_primary() {
+        let short = format!(r#"{{"keys": {{"k1": "{}"}}}}"#, base64::encode([1u8; 16]));
+        assert!(Keyring::from_json(&short).is_err());
+
+        let unknown_primary = format!(
+            r#"{{"primary": "nope", "keys": {{"k1": "{}"}}}}"#,
+            base64::encode([1u8; 32])
+        );
+        assert!(Keyring::from_json(&unknown_primary).is_err());
+    }
+}
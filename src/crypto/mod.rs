@@ -1 +1,10 @@
+pub mod encrypted;
 pub mod encryption;
+pub mod envelope;
+pub mod inspect;
+pub mod keyring;
+pub mod path;
+pub mod policy;
+pub mod pseudonymize;
+pub mod schema;
+pub mod shredding;
@@ -0,0 +1,236 @@
+use crate::error::PolyCryptError;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// A field selector inside a JSON record.
+///
+/// Segments are separated by `.` and `[]` descends into every element of an array, so
+/// `address.zip` selects a nested field and `contacts[].phone` selects the `phone` of each
+/// contact. A key that itself contains `.`, `[`, `]` or `\\` is written with a backslash before
+/// each of those characters, so `dose\\.mg` selects the top-level field `dose.mg`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Each,
+}
+
+impl FieldPath {
+    pub fn parse(path: &str) -> Result<Self, PolyCryptError> {
+        let invalid = || PolyCryptError::InvalidPathError(format!("'{}'", path));
+
+        let mut segments = Vec::new();
+        let mut chars = path.chars().peekable();
+        loop {
+            let mut key = String::new();
+            while let Some(c) = chars.next_if(|c| !matches!(c, '.' | '[')) {
+                match c {
+                    '\\' => match chars.next() {
+                        Some(escaped @ ('.' | '[' | ']' | '\\')) => key.push(escaped),
+                        _ => return Err(invalid()),
+                    },
+                    ']' => return Err(invalid()),
+                    c => key.push(c),
+                }
+            }
+            let mut each = 0;
+            while chars.next_if_eq(&'[').is_some() {
+                if chars.next() != Some(']') {
+                    return Err(invalid());
+                }
+                each += 1;
+            }
+            if key.is_empty() && (segments.is_empty() || each == 0) {
+                return Err(invalid());
+            }
+            if !key.is_empty() {
+                segments.push(Segment::Key(key));
+            }
+            segments.extend(std::iter::repeat_n(Segment::Each, each));
+            match chars.next() {
+                None => break,
+                Some('.') => {}
+                Some(_) => return Err(invalid()),
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// The last key of the path, or `None` when the path ends in `[]`.
+    pub fn leaf_key(&self) -> Option<&str> {
+        match self.segments.last() {
+            Some(Segment::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// The path of a field named `name` next to this path's leaf, e.g. `contacts[].phone_bidx`
+    /// for `contacts[].phone`. `None` when the path ends in `[]`.
+    pub fn sibling(&self, name: &str) -> Option<FieldPath> {
+        self.leaf_key()?;
+        let mut segments = self.segments.clone();
+        segments.pop();
+        segments.push(Segment::Key(name.to_string()));
+        Some(Self { segments })
+    }
+
+    /// Calls `f` on every value selected by the path. Missing fields and type mismatches
+    /// along the way are skipped, matching how absent top-level fields are treated.
+    pub fn visit_mut<F>(&self, value: &mut Value, f: &mut F) -> Result<(), PolyCryptError>
+    where
+        F: FnMut(&mut Value) -> Result<(), PolyCryptError>,
+    {
+        visit(&self.segments, value, f)
+    }
+
+    /// Calls `f` with every object that directly contains the path's leaf key, together with
+    /// that key. Used to write sibling fields such as blind indexes.
+    pub fn visit_parents_mut<F>(&self, value: &mut Value, f: &mut F) -> Result<(), PolyCryptError>
+    where
+        F: FnMut(&mut Map<String, Value>, &str) -> Result<(), PolyCryptError>,
+    {
+        let Some((Segment::Key(leaf), parent)) = self.segments.split_last() else {
+            return Ok(());
+        };
+        visit(
+            parent,
+            value,
+            &mut |parent_value: &mut Value| match parent_value.as_object_mut() {
+                Some(object) if object.contains_key(leaf) => f(object, leaf),
+                _ => Ok(()),
+            },
+        )
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if i == 0 => write!(f, "{}", escape_key(key))?,
+                Segment::Key(key) => write!(f, ".{}", escape_key(key))?,
+                Segment::Each => write!(f, "[]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escapes a literal key for use as a path segment, e.g. `dose.mg` becomes `dose\\.mg`.
+pub fn escape_key(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for c in key.chars() {
+        if matches!(c, '.' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn visit<F>(segments: &[Segment], value: &mut Value, f: &mut F) -> Result<(), PolyCryptError>
+where
+    F: FnMut(&mut Value) -> Result<(), PolyCryptError>,
+{
+    let Some((segment, rest)) = segments.split_first() else {
+        return f(value);
+    };
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(object)) => match object.get_mut(key) {
+            Some(child) => visit(rest, child, f),
+            None => Ok(()),
+        },
+        (Segment::Each, Value::Array(items)) => {
+            items.iter_mut().try_for_each(|item| visit(rest, item, f))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_display() {
+        for path in [
+            "name",
+            "address.zip",
+            "contacts[].phone",
+            "matrix[][]",
+            "a.b[].c",
+            r"dose\.mg",
+            r"notes\[1\].text[]",
+            r"a\\b",
+        ] {
+            assert_eq!(FieldPath::parse(path).unwrap().to_string(), path);
+        }
+        for path in [
+            "", ".a", "a.", "a..b", "[]", "a[0]", "a[]b", "a]", r"a\", r"a\b",
+        ] {
+            assert!(
+                FieldPath::parse(path).is_err(),
+                "{} should be invalid",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_visit_mut_nested_and_arrays() {
+        let mut record = json!({
+            "address": {"zip": "12345"},
+            "contacts": [{"phone": "1"}, {"email": "x"}, {"phone": "2"}],
+            "missing": null
+        });
+        let mut seen = Vec::new();
+        for path in ["address.zip", "contacts[].phone", "missing.field", "absent"] {
+            FieldPath::parse(path)
+                .unwrap()
+                .visit_mut(&mut record, &mut |value: &mut Value| {
+                    seen.push(value.clone());
+                    *value = json!("x");
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        assert_eq!(seen, [json!("12345"), json!("1"), json!("2")]);
+        assert_eq!(
+            record["contacts"],
+            json!([{"phone": "x"}, {"email": "x"}, {"phone": "x"}])
+        );
+    }
+
+    #[test]
+    fn test_escaped_keys_are_literal() {
+        let mut record = json!({"dose.mg": "5", "dose": {"mg": "10"}, "a[]": ["x"]});
+        for (path, expected) in [
+            (r"dose\.mg", json!("5")),
+            ("dose.mg", json!("10")),
+            (r"a\[\]", json!(["x"])),
+        ] {
+            let mut seen = Vec::new();
+            FieldPath::parse(path)
+                .unwrap()
+                .visit_mut(&mut record, &mut |value: &mut Value| {
+                    seen.push(value.clone());
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(seen, [expected], "{}", path);
+        }
+
+        for key in ["dose.mg", "a[]", r"back\slash", "plain"] {
+            let path = FieldPath::parse(&escape_key(key)).unwrap();
+            assert_eq!(path.leaf_key(), Some(key));
+            assert_eq!(path.to_string(), escape_key(key));
+        }
+    }
+}
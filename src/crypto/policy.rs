@@ -0,0 +1,599 @@
+use crate::crypto::encryption::{self, FieldCipher, FieldOptions};
+use crate::crypto::envelope;
+pub use crate::crypto::envelope::Algorithm;
+use crate::crypto::keyring::Keyring;
+use crate::crypto::path::FieldPath;
+use crate::crypto::pseudonymize;
+use crate::error::PolyCryptError;
+use crate::transform::masking::{self, MaskingMode};
+use crate::Logger;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+
+/// How a single field of a record type is protected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldRule {
+    pub path: String,
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    /// Key used for this field. Falls back to the keyring's primary key when omitted.
+    #[serde(default)]
+    pub key_id: Option<String>,
+    /// Use [`encryption::encrypt_deterministic`] so equal values encrypt identically.
+    #[serde(default)]
+    pub deterministic: bool,
+    /// Name of a field next to `path` that receives a blind index of the plaintext.
+    #[serde(default)]
+    pub blind_index: Option<String>,
+    /// Masking applied by [`EncryptionPolicy::mask_record`].
+    #[serde(default)]
+    pub mask: Option<MaskingMode>,
+    /// Replace the value with its keyed pseudonym in this domain instead of encrypting it.
+    /// Pseudonyms are not reversible, so [`EncryptionPolicy::decrypt_record`] leaves them as
+    /// they are.
+    #[serde(default)]
+    pub pseudonymize: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordPolicy {
+    pub fields: Vec<FieldRule>,
+}
+
+/// Maps record types to the field rules that protect them, so every service encrypts the
+/// same fields the same way.
+///
+/// ```json
+/// {
+///   "record_types": {
+///     "patient": {
+///       "fields": [
+///         {"path": "name", "key_id": "phi-2024"},
+///         {"path": "ssn", "deterministic": true, "blind_index": "ssn_bidx", "mask": {"keep_last": 4}}
+///       ]
+///     }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptionPolicy {
+    pub record_types: BTreeMap<String, RecordPolicy>,
+}
+
+impl EncryptionPolicy {
+    pub fn from_json(json: &str) -> Result<Self, PolyCryptError> {
+        let policy: Self = serde_json::from_str(json)
+            .map_err(|e| PolyCryptError::PolicyError(format!("Invalid JSON policy: {}", e)))?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, PolyCryptError> {
+        let policy: Self = toml::from_str(toml)
+            .map_err(|e| PolyCryptError::PolicyError(format!("Invalid TOML policy: {}", e)))?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Loads a policy, choosing the format from the `.json` or `.toml` extension.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PolyCryptError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&contents),
+            Some("toml") => Self::from_toml(&contents),
+            _ => Err(PolyCryptError::PolicyError(format!(
+                "Unsupported policy file '{}', expected .json or .toml",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Checks the policy for structural mistakes that would silently leave data unprotected.
+    pub fn validate(&self) -> Result<(), PolyCryptError> {
+        if self.record_types.is_empty() {
+            return Err(policy_error("Policy defines no record types"));
+        }
+
+        for (record_type, record_policy) in &self.record_types {
+            if record_policy.fields.is_empty() {
+                return Err(policy_error(format!(
+                    "Record type '{}' has no field rules",
+                    record_type
+                )));
+            }
+
+            let mut targets = HashSet::new();
+            for rule in &record_policy.fields {
+                let path = FieldPath::parse(&rule.path).map_err(|_| {
+                    policy_error(format!(
+                        "Record type '{}' has an invalid field path '{}'",
+                        record_type, rule.path
+                    ))
+                })?;
+                if !targets.insert(path.to_string()) {
+                    return Err(policy_error(format!(
+                        "Record type '{}' writes '{}' more than once",
+                        record_type, rule.path
+                    )));
+                }
+                if rule.pseudonymize.is_some() && (rule.deterministic || rule.blind_index.is_some())
+                {
+                    return Err(policy_error(format!(
+                        "Field '{}' of '{}' is pseudonymized and cannot also be deterministic or blind indexed",
+                        rule.path, record_type
+                    )));
+                }
+                if let Some(index_field) = &rule.blind_index {
+                    let index_path = match path.sibling(index_field) {
+                        Some(index_path) if is_plain_key(index_field) => index_path,
+                        _ => {
+                            return Err(policy_error(format!(
+                                "Field '{}' of '{}' has an invalid blind index '{}'",
+                                rule.path, record_type, index_field
+                            )))
+                        }
+                    };
+                    if !targets.insert(index_path.to_string()) {
+                        return Err(policy_error(format!(
+                            "Record type '{}' writes '{}' more than once",
+                            record_type, index_path
+                        )));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every key referenced by the policy is available in `keyring`.
+    pub fn validate_against(&self, keyring: &Keyring) -> Result<(), PolyCryptError> {
+        self.validate()?;
+        for (record_type, record_policy) in &self.record_types {
+            for rule in &record_policy.fields {
+                resolve_key_id(rule, keyring)
+                    .and_then(|key_id| keyring.get(key_id))
+                    .map_err(|e| {
+                        policy_error(format!("Field '{}' of '{}': {}", rule.path, record_type, e))
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record_policy(&self, record_type: &str) -> Result<&RecordPolicy, PolyCryptError> {
+        self.record_types
+            .get(record_type)
+            .ok_or_else(|| policy_error(format!("Unknown record type '{}'", record_type)))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(record_type = record_type, field_count = tracing::field::Empty, key_id = tracing::field::Empty)
+        )
+    )]
+    pub fn encrypt_record(
+        &self,
+        record_type: &str,
+        record: &Value,
+        keyring: &Keyring,
+    ) -> Result<Value, PolyCryptError> {
+        let record_policy = self.record_policy(record_type)?;
+        #[cfg(feature = "tracing")]
+        record_span_fields(record_policy, keyring);
+        let logger = Logger::new(json!({"operation": "encrypt_record"}));
+        logger.info(
+            "Starting record encryption",
+            Some(json!({"record_type": record_type, "fields": record_policy.paths()})),
+        );
+
+        let mut encrypted_record = record.clone();
+        for rule in &record_policy.fields {
+            let path = FieldPath::parse(&rule.path)?;
+            let key_id = resolve_key_id(rule, keyring)?;
+            if let Some(domain) = &rule.pseudonymize {
+                let key = keyring.get(key_id)?;
+                path.visit_mut(&mut encrypted_record, &mut |value: &mut Value| {
+                    *value = pseudonymize::pseudonymize_value(value, domain, key)?;
+                    Ok(())
+                })?;
+                continue;
+            }
+            let cipher = FieldCipher {
+                key: keyring.get(key_id)?,
+                key_id: Some(key_id),
+                deterministic: rule.deterministic,
+                strict: false,
+            };
+            if let Some(index_field) = &rule.blind_index {
+                path.visit_parents_mut(&mut encrypted_record, &mut |parent, leaf| {
+                    // Re-encrypting a record keeps the index computed from the plaintext.
+                    if !contains_encrypted(&parent[leaf]) {
+                        let index = blind_index_value(&parent[leaf], cipher.key)?;
+                        parent.insert(index_field.clone(), index);
+                    }
+                    Ok(())
+                })?;
+            }
+            path.visit_mut(&mut encrypted_record, &mut |value: &mut Value| {
+                *value = encryption::encrypt_value(value, &cipher)?;
+                Ok(())
+            })?;
+        }
+
+        logger.info("Record encryption completed", None);
+        Ok(encrypted_record)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(record_type = record_type, field_count = tracing::field::Empty, key_id = tracing::field::Empty)
+        )
+    )]
+    pub fn decrypt_record(
+        &self,
+        record_type: &str,
+        record: &Value,
+        keyring: &Keyring,
+    ) -> Result<Value, PolyCryptError> {
+        self.decrypt_record_tracked(record_type, record, keyring)
+            .map(|(decrypted_record, _)| decrypted_record)
+    }
+
+    /// Like [`EncryptionPolicy::decrypt_record`], also returning the paths of the fields that
+    /// held ciphertext, grouped by the key id that decrypted them.
+    pub(crate) fn decrypt_record_tracked(
+        &self,
+        record_type: &str,
+        record: &Value,
+        keyring: &Keyring,
+    ) -> Result<(Value, BTreeMap<String, Vec<String>>), PolyCryptError> {
+        let record_policy = self.record_policy(record_type)?;
+        #[cfg(feature = "tracing")]
+        record_span_fields(record_policy, keyring);
+        let logger = Logger::new(json!({"operation": "decrypt_record"}));
+        logger.info(
+            "Starting record decryption",
+            Some(json!({"record_type": record_type, "fields": record_policy.paths()})),
+        );
+
+        let mut decrypted_record = record.clone();
+        let mut decrypted_paths: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for rule in record_policy
+            .fields
+            .iter()
+            .filter(|rule| rule.pseudonymize.is_none())
+        {
+            let rule_key_id = resolve_key_id(rule, keyring)?;
+            let used_key_ids = RefCell::new(BTreeSet::new());
+            // Envelopes name the key they were encrypted with, which may be an older key
+            // than the one the rule currently points at.
+            let resolve_key = |key_id: Option<&str>| {
+                let key_id = key_id.unwrap_or(rule_key_id);
+                used_key_ids.borrow_mut().insert(key_id.to_string());
+                keyring.get(key_id)
+            };
+            FieldPath::parse(&rule.path)?.visit_mut(
+                &mut decrypted_record,
+                &mut |value: &mut Value| {
+                    *value =
+                        encryption::decrypt_value(value, &resolve_key, &FieldOptions::default())?;
+                    Ok(())
+                },
+            )?;
+            for key_id in used_key_ids.into_inner() {
+                decrypted_paths
+                    .entry(key_id)
+                    .or_default()
+                    .push(rule.path.clone());
+            }
+        }
+
+        logger.info("Record decryption completed", None);
+        Ok((decrypted_record, decrypted_paths))
+    }
+
+    /// Applies each field's masking to a plaintext record. Fields without a mask are left
+    /// untouched.
+    pub fn mask_record(&self, record_type: &str, record: &Value) -> Result<Value, PolyCryptError> {
+        let record_policy = self.record_policy(record_type)?;
+
+        let mut masked_record = record.clone();
+        for rule in &record_policy.fields {
+            if let Some(mode) = &rule.mask {
+                FieldPath::parse(&rule.path)?.visit_mut(
+                    &mut masked_record,
+                    &mut |value: &mut Value| {
+                        *value = masking::mask_value(value, mode);
+                        Ok(())
+                    },
+                )?;
+            }
+        }
+        Ok(masked_record)
+    }
+}
+
+impl RecordPolicy {
+    pub fn paths(&self) -> Vec<&str> {
+        self.fields.iter().map(|rule| rule.path.as_str()).collect()
+    }
+}
+
+fn resolve_key_id<'a>(
+    rule: &'a FieldRule,
+    keyring: &'a Keyring,
+) -> Result<&'a str, PolyCryptError> {
+    rule.key_id
+        .as_deref()
+        .or_else(|| keyring.primary_key_id())
+        .ok_or_else(|| {
+            PolyCryptError::InvalidKeyError(
+                "No key id given and the keyring has no primary key".to_string(),
+            )
+        })
+}
+
+// Fills in the record span's field count and the key ids its rules resolve to.
+#[cfg(feature = "tracing")]
+fn record_span_fields(record_policy: &RecordPolicy, keyring: &Keyring) {
+    let key_ids: std::collections::BTreeSet<&str> = record_policy
+        .fields
+        .iter()
+        .filter_map(|rule| resolve_key_id(rule, keyring).ok())
+        .collect();
+    let span = tracing::Span::current();
+    span.record("field_count", record_policy.fields.len());
+    span.record("key_id", key_ids.into_iter().collect::<Vec<_>>().join(","));
+}
+
+fn contains_encrypted(value: &Value) -> bool {
+    match value {
+        Value::String(s) => envelope::is_encrypted_value(s),
+        Value::Array(items) => items.iter().any(contains_encrypted),
+        _ => false,
+    }
+}
+
+fn blind_index_value(value: &Value, key: &[u8; 32]) -> Result<Value, PolyCryptError> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| blind_index_value(item, key))
+            .collect::<Result<Vec<Value>, PolyCryptError>>()
+            .map(Value::Array),
+        Value::String(s) => Ok(Value::String(encryption::blind_index(s.as_bytes(), key))),
+        _ => Err(PolyCryptError::EncryptionError(
+            "Only string values and arrays of strings can be blind indexed".to_string(),
+        )),
+    }
+}
+
+fn is_plain_key(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['.', '[', ']'])
+}
+
+fn policy_error(message: impl Into<String>) -> PolyCryptError {
+    PolyCryptError::PolicyError(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"{
+        "record_types": {
+            "patient": {
+                "fields": [
+                    {"path": "name", "key_id": "k1"},
+                    {"path": "ssn", "deterministic": true, "blind_index": "ssn_bidx", "mask": {"keep_last": 4}},
+                    {"path": "allergies", "mask": "redact"}
+                ]
+            }
+        }
+    }"#;
+
+    fn keyring() -> Keyring {
+        let mut keyring = Keyring::new();
+        keyring.insert("k1", [1u8; 32]);
+        keyring.insert("k2", [2u8; 32]);
+        keyring.set_primary("k2").unwrap();
+        keyring
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_record() {
+        let policy = EncryptionPolicy::from_json(POLICY).unwrap();
+        let keyring = keyring();
+        policy.validate_against(&keyring).unwrap();
+
+        let record = json!({
+            "id": "1",
+            "name": "John Doe",
+            "ssn": "123-45-6789",
+            "allergies": ["peanuts"]
+        });
+
+        let encrypted = policy.encrypt_record("patient", &record, &keyring).unwrap();
+        assert_eq!(encrypted["id"], record["id"]);
+        assert_ne!(encrypted["name"], record["name"]);
+        assert_eq!(
+            encrypted["ssn_bidx"],
+            json!(encryption::blind_index(b"123-45-6789", &[2u8; 32]))
+        );
+
+        // Deterministic fields encrypt identically across runs.
+        let again = policy.encrypt_record("patient", &record, &keyring).unwrap();
+        assert_eq!(encrypted["ssn"], again["ssn"]);
+        assert_ne!(encrypted["name"], again["name"]);
+
+        let decrypted = policy
+            .decrypt_record("patient", &encrypted, &keyring)
+            .unwrap();
+        assert_eq!(decrypted["name"], record["name"]);
+        assert_eq!(decrypted["ssn"], record["ssn"]);
+        assert_eq!(decrypted["allergies"], record["allergies"]);
+
+        let masked = policy.mask_record("patient", &decrypted).unwrap();
+        assert_eq!(masked["ssn"], json!("***-**-6789"));
+        assert_eq!(masked["allergies"], json!([masking::REDACTED]));
+        assert_eq!(masked["name"], record["name"]);
+    }
+
+    #[test]
+    fn test_nested_paths_and_blind_index() {
+        let policy = EncryptionPolicy::from_json(
+            r#"{"record_types": {"visit": {"fields": [
+                {"path": "patient.ssn", "blind_index": "ssn_bidx"},
+                {"path": "contacts[].phone", "mask": {"keep_last": 2}}
+            ]}}}"#,
+        )
+        .unwrap();
+        let keyring = keyring();
+        let record = json!({
+            "patient": {"ssn": "123-45-6789"},
+            "contacts": [{"phone": "555-0100"}, {"email": "a@b.c"}]
+        });
+
+        let encrypted = policy.encrypt_record("visit", &record, &keyring).unwrap();
+        assert!(encrypted["patient"]["ssn_bidx"].is_string());
+        assert_ne!(
+            encrypted["contacts"][0]["phone"],
+            record["contacts"][0]["phone"]
+        );
+        assert_eq!(encrypted["contacts"][1], record["contacts"][1]);
+
+        let decrypted = policy
+            .decrypt_record("visit", &encrypted, &keyring)
+            .unwrap();
+        assert_eq!(decrypted["contacts"], record["contacts"]);
+        assert_eq!(
+            policy.mask_record("visit", &decrypted).unwrap()["contacts"][0]["phone"],
+            json!("***-**00")
+        );
+    }
+
+    #[test]
+    fn test_decrypt_record_uses_envelope_key_id() {
+        let policy = EncryptionPolicy::from_json(POLICY).unwrap();
+        let mut keyring = keyring();
+        let record = json!({"name": "John Doe", "ssn": "123-45-6789"});
+        let encrypted = policy.encrypt_record("patient", &record, &keyring).unwrap();
+
+        // Re-running the pipeline leaves ciphertexts and blind indexes alone.
+        let again = policy
+            .encrypt_record("patient", &encrypted, &keyring)
+            .unwrap();
+        assert_eq!(again, encrypted);
+
+        // Moving the primary key on does not strand values encrypted under the old one.
+        keyring.insert("k3", [3u8; 32]);
+        keyring.set_primary("k3").unwrap();
+        let decrypted = policy
+            .decrypt_record("patient", &encrypted, &keyring)
+            .unwrap();
+        assert_eq!(
+            decrypted,
+            json!({"name": "John Doe", "ssn": "123-45-6789", "ssn_bidx": encrypted["ssn_bidx"]})
+        );
+    }
+
+    #[test]
+    fn test_pseudonymized_fields() {
+        let policy = EncryptionPolicy::from_json(
+            r#"{"record_types": {"claim": {"fields": [
+                {"path": "patient_id", "key_id": "k1", "pseudonymize": "claims-2024"},
+                {"path": "name"}
+            ]}}}"#,
+        )
+        .unwrap();
+        let keyring = keyring();
+        let record = json!({"patient_id": "1234", "name": "John Doe"});
+
+        let encrypted = policy.encrypt_record("claim", &record, &keyring).unwrap();
+        assert_eq!(
+            encrypted["patient_id"],
+            json!(pseudonymize::pseudonymize(
+                b"1234",
+                "claims-2024",
+                &[1u8; 32]
+            ))
+        );
+        assert_eq!(
+            policy
+                .encrypt_record("claim", &encrypted, &keyring)
+                .unwrap(),
+            encrypted
+        );
+
+        let decrypted = policy
+            .decrypt_record("claim", &encrypted, &keyring)
+            .unwrap();
+        assert_eq!(decrypted["patient_id"], encrypted["patient_id"]);
+        assert_eq!(decrypted["name"], record["name"]);
+    }
+
+    #[test]
+    fn test_policy_from_toml() {
+        let toml = r#"
+            [[record_types.patient.fields]]
+            path = "name"
+            key_id = "k1"
+            algorithm = "aes-256-cbc"
+
+            [[record_types.patient.fields]]
+            path = "dob"
+            mask = "redact"
+        "#;
+        let policy = EncryptionPolicy::from_toml(toml).unwrap();
+        assert_eq!(
+            policy.record_policy("patient").unwrap().paths(),
+            ["name", "dob"]
+        );
+    }
+
+    #[test]
+    fn test_policy_validation_errors() {
+        let invalid = [
+            r#"{"record_types": {}}"#,
+            r#"{"record_types": {"patient": {"fields": []}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": ""}]}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": "a"}, {"path": "a"}]}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": "a", "blind_index": "b"}, {"path": "b"}]}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": "a", "algorithm": "rot13"}]}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": "a", "colour": "red"}]}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": "a..b"}]}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": "a[]", "blind_index": "b"}]}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": "a.b", "blind_index": "c"}, {"path": "a.c"}]}}}"#,
+            r#"{"record_types": {"patient": {"fields": [{"path": "a", "pseudonymize": "d", "deterministic": true}]}}}"#,
+        ];
+        for json in invalid {
+            assert!(
+                matches!(
+                    EncryptionPolicy::from_json(json),
+                    Err(PolyCryptError::PolicyError(_))
+                ),
+                "expected policy error for {}",
+                json
+            );
+        }
+
+        let policy = EncryptionPolicy::from_json(
+            r#"{"record_types": {"patient": {"fields": [{"path": "a", "key_id": "missing"}]}}}"#,
+        )
+        .unwrap();
+        assert!(policy.validate_against(&keyring()).is_err());
+        assert!(policy.record_policy("visit").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::crypto::encryption::{derive_subkey, hmac_sha256};
+use crate::crypto::path::FieldPath;
+use crate::error::PolyCryptError;
+use serde_json::Value;
+
+const PSEUDONYM_KEY_LABEL: &[u8] = b"polycrypt-rs/pseudonym";
+
+/// Leading characters of every pseudonym, so pseudonymized values are recognized and never
+/// pseudonymized twice.
+pub const PSEUDONYM_PREFIX: &str = "ps1_";
+
+/// Computes the pseudonym of `value` within `domain`.
+///
+/// The same value, domain and key always give the same pseudonym, so datasets produced with
+/// the same domain can be joined on it. Different domains give unrelated pseudonyms, and
+/// without the key a pseudonym cannot be reversed or recomputed from a guessed value.
+pub fn pseudonymize(value: &[u8], domain: &str, key: &[u8; 32]) -> String {
+    let domain_key = hmac_sha256(&derive_subkey(key, PSEUDONYM_KEY_LABEL), domain.as_bytes());
+    format!(
+        "{}{}",
+        PSEUDONYM_PREFIX,
+        hex::encode(hmac_sha256(&domain_key, value))
+    )
+}
+
+/// Whether `value` has the shape of a pseudonym produced by [`pseudonymize`].
+pub fn is_pseudonym(value: &str) -> bool {
+    value.strip_prefix(PSEUDONYM_PREFIX).is_some_and(|digest| {
+        digest.len() == 64
+            && digest
+                .bytes()
+                .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    })
+}
+
+/// Replaces the given fields of a record with their pseudonyms. Numbers are pseudonymized
+/// through their decimal form, so `1234` and `"1234"` map to the same pseudonym.
+pub fn pseudonymize_fields(
+    record: &Value,
+    fields: &[String],
+    domain: &str,
+    key: &[u8; 32],
+) -> Result<Value, PolyCryptError> {
+    let mut pseudonymized_record = record.clone();
+    for field in fields {
+        FieldPath::parse(field)?.visit_mut(
+            &mut pseudonymized_record,
+            &mut |value: &mut Value| {
+                *value = pseudonymize_value(value, domain, key)?;
+                Ok(())
+            },
+        )?;
+    }
+    Ok(pseudonymized_record)
+}
+
+pub fn pseudonymize_fields_in_batch(
+    records: &[Value],
+    fields: &[String],
+    domain: &str,
+    key: &[u8; 32],
+) -> Result<Vec<Value>, PolyCryptError> {
+    records
+        .iter()
+        .map(|record| pseudonymize_fields(record, fields, domain, key))
+        .collect()
+}
+
+pub(crate) fn pseudonymize_value(
+    value: &Value,
+    domain: &str,
+    key: &[u8; 32],
+) -> Result<Value, PolyCryptError> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::String(s) if is_pseudonym(s) => Ok(value.clone()),
+        Value::String(s) => Ok(Value::String(pseudonymize(s.as_bytes(), domain, key))),
+        Value::Number(n) => Ok(Value::String(pseudonymize(
+            n.to_string().as_bytes(),
+            domain,
+            key,
+        ))),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| pseudonymize_value(item, domain, key))
+            .collect::<Result<Vec<Value>, PolyCryptError>>()
+            .map(Value::Array),
+        _ => Err(PolyCryptError::EncryptionError(
+            "Only strings, numbers and arrays of them can be pseudonymized".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pseudonyms_are_consistent_per_domain() {
+        let key = [0u8; 32];
+        let pseudonym = pseudonymize(b"patient-1", "claims", &key);
+        assert!(is_pseudonym(&pseudonym));
+        assert_eq!(pseudonym, pseudonymize(b"patient-1", "claims", &key));
+        assert_ne!(pseudonym, pseudonymize(b"patient-2", "claims", &key));
+        assert_ne!(pseudonym, pseudonymize(b"patient-1", "labs", &key));
+        assert_ne!(pseudonym, pseudonymize(b"patient-1", "claims", &[1u8; 32]));
+        assert!(!is_pseudonym("patient-1"));
+    }
+
+    #[test]
+    fn test_pseudonymize_fields() {
+        let key = [0u8; 32];
+        let records = vec![
+            json!({"patient_id": "1234", "visit": {"mrn": 42}, "notes": "kept"}),
+            json!({"patient_id": 1234, "visit": {"mrn": null}}),
+        ];
+        let fields = vec!["patient_id".to_string(), "visit.mrn".to_string()];
+
+        let pseudonymized =
+            pseudonymize_fields_in_batch(&records, &fields, "claims", &key).unwrap();
+        assert_eq!(
+            pseudonymized[0]["patient_id"],
+            pseudonymized[1]["patient_id"]
+        );
+        assert_eq!(
+            pseudonymized[0]["visit"]["mrn"],
+            json!(pseudonymize(b"42", "claims", &key))
+        );
+        assert_eq!(pseudonymized[0]["notes"], "kept");
+        assert_eq!(pseudonymized[1]["visit"]["mrn"], Value::Null);
+
+        let again = pseudonymize_fields(&pseudonymized[0], &fields, "claims", &key).unwrap();
+        assert_eq!(again, pseudonymized[0]);
+
+        assert!(
+            pseudonymize_fields(&json!({"patient_id": true}), &fields, "claims", &key).is_err()
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use crate::crypto::path::escape_key;
+use crate::error::PolyCryptError;
+use serde_json::Value;
+
+/// Schema keyword that marks a property as PHI.
+pub const PHI_KEYWORD: &str = "x-phi";
+
+/// Derives the field paths to encrypt from a JSON Schema whose PHI properties are annotated
+/// with `"x-phi": true`. The result can be passed straight to
+/// [`encrypt_fields`](crate::crypto::encryption::encrypt_fields) and the batch functions.
+pub fn phi_fields(schema: &Value) -> Result<Vec<String>, PolyCryptError> {
+    fields_marked_with(schema, PHI_KEYWORD)
+}
+
+/// Like [`phi_fields`], with a custom annotation keyword.
+///
+/// Nested objects become dotted paths and array items become `[]` segments (see
+/// [`FieldPath`](crate::crypto::path::FieldPath)); property names containing path syntax are
+/// escaped. Local `$ref`s and
+/// `allOf`/`anyOf`/`oneOf` are followed. Recursive references are rejected when they lead
+/// to annotated properties, since those could never all be listed.
+pub fn fields_marked_with(schema: &Value, keyword: &str) -> Result<Vec<String>, PolyCryptError> {
+    let mut walker = Walker {
+        root: schema,
+        keyword,
+        refs: Vec::new(),
+        fields: Vec::new(),
+    };
+    walker.walk(schema, "")?;
+    Ok(walker.fields)
+}
+
+struct Walker<'a> {
+    root: &'a Value,
+    keyword: &'a str,
+    refs: Vec<&'a str>,
+    fields: Vec<String>,
+}
+
+impl<'a> Walker<'a> {
+    fn walk(&mut self, node: &'a Value, path: &str) -> Result<(), PolyCryptError> {
+        if let Some(reference) = node.get("$ref").and_then(Value::as_str) {
+            let target = self.resolve(reference)?;
+            if self.refs.contains(&reference) {
+                // A recursive schema has no finite set of paths. That is only acceptable
+                // when the recursion cannot reach a marked property.
+                if contains_marker(target, self.keyword) {
+                    return Err(schema_error(format!(
+                        "Recursive $ref '{}' at '{}' contains '{}' properties",
+                        reference, path, self.keyword
+                    )));
+                }
+                return Ok(());
+            }
+            self.refs.push(reference);
+            self.walk(target, path)?;
+            self.refs.pop();
+        }
+
+        if node.get(self.keyword).and_then(Value::as_bool) == Some(true) {
+            if path.is_empty() {
+                return Err(schema_error(format!(
+                    "'{}' must annotate a property, not the whole record",
+                    self.keyword
+                )));
+            }
+            if !self.fields.iter().any(|field| field == path) {
+                self.fields.push(path.to_string());
+            }
+            return Ok(());
+        }
+
+        if let Some(properties) = node.get("properties").and_then(Value::as_object) {
+            for (name, property) in properties {
+                if name.is_empty() {
+                    return Err(schema_error(format!(
+                        "Property '{}' cannot be addressed by a field path",
+                        name
+                    )));
+                }
+                let child = if path.is_empty() {
+                    escape_key(name)
+                } else {
+                    format!("{}.{}", path, escape_key(name))
+                };
+                self.walk(property, &child)?;
+            }
+        }
+
+        let item_path = format!("{}[]", path);
+        match node.get("items") {
+            Some(Value::Array(items)) => {
+                for item in items {
+                    self.walk(item, &item_path)?;
+                }
+            }
+            Some(items @ Value::Object(_)) => self.walk(items, &item_path)?,
+            _ => {}
+        }
+        if let Some(items) = node.get("prefixItems").and_then(Value::as_array) {
+            for item in items {
+                self.walk(item, &item_path)?;
+            }
+        }
+
+        for combinator in ["allOf", "anyOf", "oneOf"] {
+            if let Some(schemas) = node.get(combinator).and_then(Value::as_array) {
+                for schema in schemas {
+                    self.walk(schema, path)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, reference: &str) -> Result<&'a Value, PolyCryptError> {
+        reference
+            .strip_prefix('#')
+            .and_then(|pointer| self.root.pointer(pointer))
+            .ok_or_else(|| schema_error(format!("Cannot resolve $ref '{}'", reference)))
+    }
+}
+
+fn contains_marker(node: &Value, keyword: &str) -> bool {
+    match node {
+        Value::Object(object) => object.iter().any(|(key, value)| {
+            (key == keyword && value.as_bool() == Some(true)) || contains_marker(value, keyword)
+        }),
+        Value::Array(items) => items.iter().any(|item| contains_marker(item, keyword)),
+        _ => false,
+    }
+}
+
+fn schema_error(message: String) -> PolyCryptError {
+    PolyCryptError::SchemaError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_phi_fields_nested_arrays_and_refs() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "string"},
+                "name": {"type": "string", "x-phi": true},
+                "address": {
+                    "type": "object",
+                    "properties": {
+                        "street": {"type": "string", "x-phi": true},
+                        "state": {"type": "string"}
+                    }
+                },
+                "medications": {"type": "array", "items": {"type": "string"}, "x-phi": true},
+                "contacts": {"type": "array", "items": {"$ref": "#/$defs/contact"}},
+                "guardian": {"allOf": [{"$ref": "#/$defs/contact"}]}
+            },
+            "$defs": {
+                "contact": {
+                    "type": "object",
+                    "properties": {
+                        "phone": {"type": "string", "x-phi": true},
+                        "relationship": {"type": "string"}
+                    }
+                }
+            }
+        });
+
+        assert_eq!(
+            phi_fields(&schema).unwrap(),
+            [
+                "address.street",
+                "contacts[].phone",
+                "guardian.phone",
+                "medications",
+                "name"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_keyword_and_errors() {
+        let schema = json!({"properties": {"ssn": {"x-sensitive": true}, "name": {"x-phi": true}}});
+        assert_eq!(fields_marked_with(&schema, "x-sensitive").unwrap(), ["ssn"]);
+
+        assert!(phi_fields(&json!({"x-phi": true})).is_err());
+        assert!(phi_fields(&json!({"properties": {"a": {"$ref": "#/$defs/missing"}}})).is_err());
+        assert!(phi_fields(&json!({"properties": {"": {"x-phi": true}}})).is_err());
+        assert_eq!(
+            phi_fields(&json!({"properties": {"a.b": {"x-phi": true}}})).unwrap(),
+            [r"a\.b"]
+        );
+
+        let recursive = json!({
+            "properties": {"root": {"$ref": "#/$defs/node"}},
+            "$defs": {"node": {"properties": {
+                "label": {"type": "string"},
+                "children": {"items": {"$ref": "#/$defs/node"}}
+            }}}
+        });
+        assert!(phi_fields(&recursive).unwrap().is_empty());
+        let mut recursive_phi = recursive.clone();
+        recursive_phi["$defs"]["node"]["properties"]["label"]["x-phi"] = json!(true);
+        assert!(phi_fields(&recursive_phi).is_err());
+    }
+}
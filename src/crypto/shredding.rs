@@ -0,0 +1,385 @@
+use crate::crypto::encryption::{self, hmac_sha256, FieldCipher, FieldOptions};
+use crate::crypto::keyring::{decode_key, write_key_file};
+use crate::error::PolyCryptError;
+use crate::logger::Secret;
+use crate::Logger;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+/// Prefix of the envelope key id of values encrypted under a subject key; the subject's
+/// reference (see [`SubjectKeyStore::reference`]) follows it.
+pub const SUBJECT_KEY_ID_PREFIX: &str = "subject:";
+
+/// Random per-subject keys for crypto-shredding.
+///
+/// Each subject's fields are encrypted under that subject's own key. Shredding a subject
+/// deletes the key and leaves a tombstone, which makes every copy of the subject's
+/// ciphertexts, including backups, permanently undecryptable and lets decryption report
+/// [`PolyCryptError::ShreddedSubjectError`] rather than a generic missing key.
+///
+/// Keys are generated at random rather than derived, since a derived key could always be
+/// derived again.
+///
+/// Subject ids are never stored: keys, tombstones and envelope key ids all use an opaque
+/// reference, a keyed hash of the subject id under a random per-store secret, so neither the
+/// store nor the ciphertexts reveal whom they belong to.
+#[derive(Clone)]
+pub struct SubjectKeyStore {
+    secret: Secret<[u8; 32]>,
+    // Both keyed by subject reference.
+    keys: HashMap<String, Secret<[u8; 32]>>,
+    shredded: HashSet<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubjectKeyStoreFile {
+    secret: String,
+    keys: BTreeMap<String, String>,
+    #[serde(default)]
+    shredded: BTreeSet<String>,
+}
+
+impl Default for SubjectKeyStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubjectKeyStore {
+    pub fn new() -> Self {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill(&mut secret);
+        Self {
+            secret: Secret::new(secret),
+            keys: HashMap::new(),
+            shredded: HashSet::new(),
+        }
+    }
+
+    /// Parses a store of the form
+    /// `{"secret": "<base64>", "keys": {"<reference>": "<base64 key>"}, "shredded": ["<reference>"]}`.
+    pub fn from_json(json: &str) -> Result<Self, PolyCryptError> {
+        let file: SubjectKeyStoreFile = serde_json::from_str(json).map_err(|e| {
+            PolyCryptError::InvalidKeyError(format!("Invalid subject key store: {}", e))
+        })?;
+
+        let mut store = Self::new();
+        store.secret = decode_key(&file.secret).map(Secret::new).map_err(|e| {
+            PolyCryptError::InvalidKeyError(format!("Subject key store secret: {}", e))
+        })?;
+        for (reference, encoded) in file.keys {
+            let key = decode_key(&encoded).map_err(|e| {
+                PolyCryptError::InvalidKeyError(format!("Subject '{}': {}", reference, e))
+            })?;
+            store.keys.insert(reference, Secret::new(key));
+        }
+        store.shredded = file.shredded.into_iter().collect();
+        if let Some(reference) = store.keys.keys().find(|r| store.shredded.contains(*r)) {
+            return Err(PolyCryptError::InvalidKeyError(format!(
+                "Subject '{}' is both shredded and has a key",
+                reference
+            )));
+        }
+        Ok(store)
+    }
+
+    pub fn to_json(&self) -> String {
+        let file = SubjectKeyStoreFile {
+            secret: base64::encode(self.secret.expose_secret()),
+            keys: self
+                .keys
+                .iter()
+                .map(|(reference, key)| (reference.clone(), base64::encode(key.expose_secret())))
+                .collect(),
+            shredded: self.shredded.iter().cloned().collect(),
+        };
+        serde_json::to_string(&file).unwrap()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PolyCryptError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Atomically replaces `path` with the store, readable by the owner only on Unix; see
+    /// [`write_key_file`]. Callers are responsible for not keeping older copies of the file
+    /// around, since those still hold the keys of subjects shredded since.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PolyCryptError> {
+        write_key_file(path.as_ref(), self.to_json().as_bytes())?;
+        Ok(())
+    }
+
+    /// The opaque reference under which `subject` is stored and recorded in envelopes: the
+    /// hex HMAC-SHA256 of the subject id under this store's secret.
+    pub fn reference(&self, subject: &str) -> String {
+        hex::encode(hmac_sha256(self.secret.expose_secret(), subject.as_bytes()))
+    }
+
+    /// The key of `subject`, generating one the first time the subject is seen.
+    pub fn get_or_create(&mut self, subject: &str) -> Result<&[u8; 32], PolyCryptError> {
+        let reference = self.reference(subject);
+        self.check_not_shredded(&reference)?;
+        let key = self.keys.entry(reference).or_insert_with(|| {
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill(&mut key);
+            Secret::new(key)
+        });
+        Ok(key.expose_secret())
+    }
+
+    pub fn get(&self, subject: &str) -> Result<&[u8; 32], PolyCryptError> {
+        self.get_by_reference(&self.reference(subject))
+    }
+
+    fn get_by_reference(&self, reference: &str) -> Result<&[u8; 32], PolyCryptError> {
+        self.check_not_shredded(reference)?;
+        self.keys
+            .get(reference)
+            .map(Secret::expose_secret)
+            .ok_or_else(|| {
+                PolyCryptError::InvalidKeyError(format!("No key for subject '{}'", reference))
+            })
+    }
+
+    /// Destroys the key of `subject`. Returns `false` if the subject was already shredded.
+    pub fn shred(&mut self, subject: &str) -> bool {
+        let reference = self.reference(subject);
+        self.keys.remove(&reference);
+        self.shredded.insert(reference)
+    }
+
+    pub fn is_shredded(&self, subject: &str) -> bool {
+        self.shredded.contains(&self.reference(subject))
+    }
+
+    /// Number of subjects with a key.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Errors carry the reference, never the subject id.
+    fn check_not_shredded(&self, reference: &str) -> Result<(), PolyCryptError> {
+        if self.shredded.contains(reference) {
+            return Err(PolyCryptError::ShreddedSubjectError(reference.to_string()));
+        }
+        Ok(())
+    }
+}
+
+// Key material is deliberately left out of debug output.
+impl fmt::Debug for SubjectKeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubjectKeyStore")
+            .field("subjects", &self.keys.len())
+            .field("shredded", &self.shredded.len())
+            .finish()
+    }
+}
+
+/// Like [`encrypt_fields`](encryption::encrypt_fields), under the key of the subject named by
+/// the record's top-level `subject_field`. The subject's key is created on first use.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(field_count = fields_to_encrypt.len()))
+)]
+pub fn encrypt_fields_for_subject(
+    record: &Value,
+    fields_to_encrypt: &[String],
+    subject_field: &str,
+    store: &mut SubjectKeyStore,
+) -> Result<Value, PolyCryptError> {
+    let logger = Logger::new(json!({"operation": "encrypt_fields_for_subject"}));
+    logger.info(
+        "Starting subject field encryption",
+        Some(json!({"fields": fields_to_encrypt})),
+    );
+
+    let subject = subject_id(record, subject_field)?;
+    let key_id = format!("{}{}", SUBJECT_KEY_ID_PREFIX, store.reference(&subject));
+    let cipher = FieldCipher {
+        key: store.get_or_create(&subject)?,
+        key_id: Some(&key_id),
+        deterministic: false,
+        strict: false,
+    };
+    let encrypted_record = encryption::encrypt_paths(record, fields_to_encrypt, &cipher)?;
+
+    logger.info("Subject field encryption completed", None);
+    Ok(encrypted_record)
+}
+
+/// Reverses [`encrypt_fields_for_subject`]. The subject is taken from each value's envelope,
+/// falling back to `subject_field` for values without one. Fails with
+/// [`PolyCryptError::ShreddedSubjectError`] once the subject has been shredded.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(field_count = fields_to_decrypt.len()))
+)]
+pub fn decrypt_fields_for_subject(
+    record: &Value,
+    fields_to_decrypt: &[String],
+    subject_field: &str,
+    store: &SubjectKeyStore,
+) -> Result<Value, PolyCryptError> {
+    let logger = Logger::new(json!({"operation": "decrypt_fields_for_subject"}));
+    logger.info(
+        "Starting subject field decryption",
+        Some(json!({"fields": fields_to_decrypt})),
+    );
+
+    let resolve_key = |key_id: Option<&str>| match key_id {
+        Some(key_id) => match key_id.strip_prefix(SUBJECT_KEY_ID_PREFIX) {
+            Some(reference) => store.get_by_reference(reference),
+            None => Err(PolyCryptError::InvalidKeyError(format!(
+                "Key id '{}' is not a subject key",
+                key_id
+            ))),
+        },
+        None => store.get(&subject_id(record, subject_field)?),
+    };
+    // Subject keys postdate the envelope format, so there are no legacy values to decrypt.
+    let options = FieldOptions {
+        legacy: false,
+        ..FieldOptions::default()
+    };
+    let decrypted_record =
+        encryption::decrypt_paths(record, fields_to_decrypt, &resolve_key, &options)?;
+
+    logger.info("Subject field decryption completed", None);
+    Ok(decrypted_record)
+}
+
+fn subject_id(record: &Value, subject_field: &str) -> Result<String, PolyCryptError> {
+    match record.get(subject_field) {
+        Some(Value::String(s)) if !s.is_empty() => Ok(s.clone()),
+        Some(Value::Number(n)) => Ok(n.to_string()),
+        _ => Err(PolyCryptError::InvalidKeyError(format!(
+            "Record has no usable subject field '{}'",
+            subject_field
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::envelope;
+
+    fn fields() -> Vec<String> {
+        vec!["name".to_string(), "dob".to_string()]
+    }
+
+    #[test]
+    fn test_subject_keys_encrypt_and_decrypt() {
+        let mut store = SubjectKeyStore::new();
+        let alice = json!({"patient_id": "p1", "name": "Alice", "dob": "1980-01-01"});
+        let bob = json!({"patient_id": 2, "name": "Bob", "dob": "1990-01-01"});
+
+        let alice_encrypted =
+            encrypt_fields_for_subject(&alice, &fields(), "patient_id", &mut store).unwrap();
+        let bob_encrypted =
+            encrypt_fields_for_subject(&bob, &fields(), "patient_id", &mut store).unwrap();
+        assert_ne!(store.get("p1").unwrap(), store.get("2").unwrap());
+
+        let bytes = base64::decode(alice_encrypted["name"].as_str().unwrap()).unwrap();
+        let (header, _) = envelope::open(&bytes).unwrap();
+        let key_id = header.key_id.unwrap();
+        assert_eq!(key_id, format!("subject:{}", store.reference("p1")));
+        assert!(!key_id.contains("p1"));
+
+        for (record, encrypted) in [(&alice, &alice_encrypted), (&bob, &bob_encrypted)] {
+            let decrypted =
+                decrypt_fields_for_subject(encrypted, &fields(), "patient_id", &store).unwrap();
+            assert_eq!(&decrypted, record);
+        }
+    }
+
+    #[test]
+    fn test_shredded_subject_cannot_be_decrypted() {
+        let mut store = SubjectKeyStore::new();
+        let record = json!({"patient_id": "p1", "name": "Alice"});
+        let encrypted =
+            encrypt_fields_for_subject(&record, &fields(), "patient_id", &mut store).unwrap();
+
+        // A restored copy of the store from before the deletion is what shredding defeats,
+        // so make sure the persisted form carries the tombstone and not the key.
+        assert!(store.shred("p1"));
+        assert!(!store.shred("p1"));
+        let json = store.to_json();
+        assert!(!json.contains("p1"));
+        let store = SubjectKeyStore::from_json(&json).unwrap();
+        assert!(store.is_shredded("p1"));
+        assert!(store.is_empty());
+
+        assert!(matches!(
+            decrypt_fields_for_subject(&encrypted, &fields(), "patient_id", &store),
+            Err(PolyCryptError::ShreddedSubjectError(reference))
+                if reference == store.reference("p1")
+        ));
+        let mut store = store;
+        assert!(matches!(
+            encrypt_fields_for_subject(&record, &fields(), "patient_id", &mut store),
+            Err(PolyCryptError::ShreddedSubjectError(_))
+        ));
+    }
+
+    fn restored_with(store: &SubjectKeyStore) -> SubjectKeyStore {
+        SubjectKeyStore::from_json(&store.to_json()).unwrap()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_save_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("subjects.json");
+        std::fs::write(&path, "{}").unwrap();
+        let mut store = SubjectKeyStore::new();
+        store.get_or_create("p1").unwrap();
+        store.save(&path).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // The store is replaced through a temporary file, which does not outlive the save.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert_eq!(restored_with(&store).len(), 1);
+        assert_eq!(SubjectKeyStore::from_file(&path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_store_roundtrip_and_errors() {
+        let mut store = SubjectKeyStore::new();
+        let key = *store.get_or_create("p1").unwrap();
+        let restored = SubjectKeyStore::from_json(&store.to_json()).unwrap();
+        assert_eq!(restored.get("p1").unwrap(), &key);
+        assert!(restored.get("p2").is_err());
+
+        // Long subject ids still fit the envelope's key id.
+        let long_subject = "x".repeat(1000);
+        let record = json!({"id": long_subject, "name": "Alice"});
+        let encrypted = encrypt_fields_for_subject(&record, &fields(), "id", &mut store).unwrap();
+        assert_eq!(
+            decrypt_fields_for_subject(&encrypted, &fields(), "id", &restored_with(&store))
+                .unwrap(),
+            record
+        );
+
+        let conflicting = format!(
+            r#"{{"secret": "{}", "keys": {{"p1": "{}"}}, "shredded": ["p1"]}}"#,
+            base64::encode([0u8; 32]),
+            base64::encode([1u8; 32])
+        );
+        assert!(SubjectKeyStore::from_json(&conflicting).is_err());
+        assert!(
+            encrypt_fields_for_subject(&json!({"name": "x"}), &fields(), "id", &mut store).is_err()
+        );
+    }
+}
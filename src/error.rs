@@ -18,6 +18,30 @@ pub enum PolyCryptError {
     #[error("Invalid key: {0}")]
     InvalidKeyError(String),
 
+    #[error("Invalid field path: {0}")]
+    InvalidPathError(String),
+
+    #[error("Schema error: {0}")]
+    SchemaError(String),
+
+    #[error("Policy error: {0}")]
+    PolicyError(String),
+
+    #[error("Subject has been shredded: {0}")]
+    ShreddedSubjectError(String),
+
+    #[error("De-identification error: {0}")]
+    DeidentifyError(String),
+
+    #[error("Audit error: {0}")]
+    AuditError(String),
+
+    #[error("Logger error: {0}")]
+    LoggerError(String),
+
+    #[error("Telemetry error: {0}")]
+    TelemetryError(String),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
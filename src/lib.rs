@@ -1,24 +1,129 @@
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod audit;
 pub mod bindings;
 pub mod crypto;
 pub mod error;
 pub mod logger;
+pub mod metrics;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "otlp")]
+pub mod telemetry;
+pub mod transform;
 
+pub use audit::{AuditContext, AuditLog};
 pub use bindings::ffi::{decrypt, encrypt, free_ffi_result, ByteArray, FFIResult};
+pub use crypto::encrypted::Encrypted;
+pub use crypto::keyring::Keyring;
+pub use crypto::policy::EncryptionPolicy;
+pub use crypto::shredding::SubjectKeyStore;
 pub use error::PolyCryptError;
-pub use logger::Logger;
+pub use logger::{Logger, Secret};
+#[cfg(feature = "derive")]
+pub use polycrypt_derive::polycrypt;
+
+use serde_json::Value;
+use std::sync::Mutex;
 
 pub struct PolyCrypt {
     logger: Logger,
+    policy: Option<EncryptionPolicy>,
+    keyring: Keyring,
+    audit_log: Option<Mutex<AuditLog>>,
 }
 
 impl PolyCrypt {
-    pub fn new(context: serde_json::Value) -> Self {
+    pub fn new(context: Value) -> Self {
         Self {
             logger: Logger::new(context),
+            policy: None,
+            keyring: Keyring::new(),
+            audit_log: None,
         }
     }
 
     pub fn log_info(&self, message: &str) {
         self.logger.info(message, None);
     }
+
+    /// Installs the field encryption policy and the keys it refers to. The policy is
+    /// validated against the keyring so a missing key is reported here rather than on the
+    /// first record.
+    pub fn load_policy(
+        &mut self,
+        policy: EncryptionPolicy,
+        keyring: Keyring,
+    ) -> Result<(), PolyCryptError> {
+        policy.validate_against(&keyring)?;
+        self.policy = Some(policy);
+        self.keyring = keyring;
+        Ok(())
+    }
+
+    pub fn encrypt_record(
+        &self,
+        record_type: &str,
+        record: &Value,
+    ) -> Result<Value, PolyCryptError> {
+        self.policy()?
+            .encrypt_record(record_type, record, &self.keyring)
+    }
+
+    /// Decrypts a record according to the loaded policy. Not audited; see
+    /// [`PolyCrypt::decrypt_record_audited`].
+    pub fn decrypt_record(
+        &self,
+        record_type: &str,
+        record: &Value,
+    ) -> Result<Value, PolyCryptError> {
+        self.policy()?
+            .decrypt_record(record_type, record, &self.keyring)
+    }
+
+    /// Installs the log that [`PolyCrypt::decrypt_record_audited`] writes to.
+    pub fn set_audit_log(&mut self, audit_log: AuditLog) {
+        self.audit_log = Some(Mutex::new(audit_log));
+    }
+
+    /// Like [`PolyCrypt::decrypt_record`], recording who decrypted which fields, with which
+    /// key and why. One entry is written per key, listing the policy paths that actually
+    /// held ciphertext in `record`; fields that are absent or already plaintext are not
+    /// recorded. The decrypted record is only returned once its entries are written, so a
+    /// failing audit log blocks access.
+    ///
+    /// This is the only audited decryption API. `decrypt_record`, the `decrypt_fields`
+    /// functions, `decrypt_and_mask_fields`, the language bindings and the sidecar server do
+    /// not write audit entries.
+    pub fn decrypt_record_audited(
+        &self,
+        record_type: &str,
+        record: &Value,
+        context: &AuditContext,
+    ) -> Result<Value, PolyCryptError> {
+        let policy = self.policy()?;
+        let audit_log = self
+            .audit_log
+            .as_ref()
+            .ok_or_else(|| PolyCryptError::AuditError("No audit log configured".to_string()))?;
+
+        let (decrypted_record, decrypted_paths) =
+            policy.decrypt_record_tracked(record_type, record, &self.keyring)?;
+        let operation = format!("decrypt_record:{}", record_type);
+        let mut audit_log = audit_log.lock().unwrap_or_else(|e| e.into_inner());
+        for (key_id, paths) in &decrypted_paths {
+            audit_log.record(&operation, paths, Some(key_id), context)?;
+        }
+        Ok(decrypted_record)
+    }
+
+    pub fn mask_record(&self, record_type: &str, record: &Value) -> Result<Value, PolyCryptError> {
+        self.policy()?.mask_record(record_type, record)
+    }
+
+    fn policy(&self) -> Result<&EncryptionPolicy, PolyCryptError> {
+        self.policy
+            .as_ref()
+            .ok_or_else(|| PolyCryptError::PolicyError("No encryption policy loaded".to_string()))
+    }
 }
@@ -1,6 +1,18 @@
+use crate::error::PolyCryptError;
+use crate::transform::masking::REDACTED;
 use chrono;
-use log::{error, info};
-use serde_json::{json, Value};
+use log::{log, log_enabled, Level, LevelFilter, Metadata, Record};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::mem::ManuallyDrop;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
 
 pub struct Logger {
     context: Value,
@@ -12,13 +24,25 @@ impl Logger {
     }
 
     pub fn info(&self, message: &str, additional_context: Option<Value>) {
-        let log_entry = self.create_log_entry("INFO", message, additional_context);
-        info!("{}", log_entry);
+        self.log(Level::Info, message, additional_context);
     }
 
     pub fn error(&self, message: &str, additional_context: Option<Value>) {
-        let log_entry = self.create_log_entry("ERROR", message, additional_context);
-        error!("{}", log_entry);
+        self.log(Level::Error, message, additional_context);
+    }
+
+    // Entries are only built, and redacted, when the `log` backend or the entry handler
+    // will receive them, since field functions log on every record.
+    fn log(&self, level: Level, message: &str, additional_context: Option<Value>) {
+        let to_backend = log_enabled!(level);
+        if !to_backend && ENTRY_HANDLER.read().unwrap().is_none() {
+            return;
+        }
+        let log_entry = self.create_log_entry(level.as_str(), message, additional_context);
+        dispatch(&log_entry);
+        if to_backend {
+            log!(level, "{}", log_entry);
+        }
     }
 
     fn create_log_entry(
@@ -26,7 +50,7 @@ impl Logger {
         level: &str,
         message: &str,
         additional_context: Option<Value>,
-    ) -> String {
+    ) -> Value {
         let mut entry = json!({
             "level": level,
             "message": message,
@@ -37,7 +61,563 @@ impl Logger {
         if let Some(additional) = additional_context {
             entry["additional_context"] = additional;
         }
+        redactor().redact_value(&mut entry);
+
+        if let Some(sink) = BACKEND.sink.read().unwrap().as_ref() {
+            merge_static_context(&mut entry, &sink.context);
+        }
+        entry
+    }
+}
+
+// Context keys whose values are always redacted, compared case-insensitively.
+const DEFAULT_REDACTED_KEYS: &[&str] = &[
+    "key",
+    "key_material",
+    "secret",
+    "password",
+    "token",
+    "plaintext",
+    "ssn",
+];
+
+// SSNs, email addresses, and 256-bit keys in base64 or hex.
+const DEFAULT_REDACTED_PATTERNS: &[&str] = &[
+    r"\b\d{3}-\d{2}-\d{4}\b",
+    r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+    r"\b[A-Za-z0-9+/]{43}=",
+    r"\b[0-9a-fA-F]{64}\b",
+];
+
+/// Sensitive context keys and text patterns scrubbed from log entries, in addition to the
+/// built-in ones (key material, passwords, SSNs, email addresses).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactionConfig {
+    /// Context keys whose values are replaced entirely, at any depth.
+    pub keys: Vec<String>,
+    /// Regular expressions whose matches are replaced in messages and string values.
+    pub patterns: Vec<String>,
+}
+
+struct Redactor {
+    keys: HashSet<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    fn new(config: &RedactionConfig) -> Result<Self, PolyCryptError> {
+        let keys = DEFAULT_REDACTED_KEYS
+            .iter()
+            .copied()
+            .chain(config.keys.iter().map(String::as_str))
+            .map(str::to_lowercase)
+            .collect();
+        let patterns = DEFAULT_REDACTED_PATTERNS
+            .iter()
+            .copied()
+            .chain(config.patterns.iter().map(String::as_str))
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    logger_error(format!("Invalid redaction pattern '{}': {}", pattern, e))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { keys, patterns })
+    }
+
+    fn redact_str(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for pattern in &self.patterns {
+            if pattern.is_match(&text) {
+                text = pattern.replace_all(&text, REDACTED).into_owned();
+            }
+        }
+        text
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.redact_str(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.keys.contains(&key.to_lowercase()) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_value(value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Global state is kept in `static` locks with const initializers. The redactor is built on
+// first use, since compiling its patterns cannot happen at compile time.
+static REDACTOR: RwLock<Option<Arc<Redactor>>> = RwLock::new(None);
+
+fn redactor() -> Arc<Redactor> {
+    if let Some(redactor) = REDACTOR.read().unwrap().as_ref() {
+        return redactor.clone();
+    }
+    REDACTOR
+        .write()
+        .unwrap()
+        .get_or_insert_with(|| {
+            Arc::new(
+                Redactor::new(&RedactionConfig::default()).expect("built-in patterns are valid"),
+            )
+        })
+        .clone()
+}
+
+/// Sets the keys and patterns redacted from log entries, on top of the built-in ones.
+/// [`init`] does this from [`LoggerConfig::redact`]; hosts with their own `log` backend can
+/// call it directly.
+pub fn set_redaction(config: &RedactionConfig) -> Result<(), PolyCryptError> {
+    *REDACTOR.write().unwrap() = Some(Arc::new(Redactor::new(config)?));
+    Ok(())
+}
+
+/// A value that must never reach a log entry, such as key material or plaintext.
+///
+/// `Secret` implements neither `Display` nor `Serialize`, so it can be formatted into
+/// neither a log message nor a context [`Value`], and its `Debug` output is redacted:
+///
+/// ```compile_fail
+/// use polycrypt_rs::{Logger, Secret};
+/// use serde_json::json;
+///
+/// let key = Secret::new([0u8; 32]);
+/// Logger::new(json!({})).info("Loaded key", Some(json!({ "key": key })));
+/// ```
+///
+/// When a `Secret` is dropped, the memory that held the value is overwritten with zeros.
+/// Only the value itself is wiped, not heap memory it owns, so keys are held as arrays.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T>(ManuallyDrop<T>);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(ManuallyDrop::new(value))
+    }
+
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+
+    /// Takes the value out without wiping it; the caller becomes responsible for it.
+    pub fn into_inner(self) -> T {
+        let mut secret = ManuallyDrop::new(self);
+        // SAFETY: `secret` is never dropped, so the value is taken exactly once.
+        unsafe { ManuallyDrop::take(&mut secret.0) }
+    }
+}
+
+impl<T> Drop for Secret<T> {
+    fn drop(&mut self) {
+        let bytes = (&mut self.0 as *mut ManuallyDrop<T>).cast::<u8>();
+        // SAFETY: the value is dropped exactly once, here, and its memory is only written,
+        // never read again as a `T`.
+        unsafe {
+            ManuallyDrop::drop(&mut self.0);
+            for offset in 0..std::mem::size_of::<T>() {
+                std::ptr::write_volatile(bytes.add(offset), 0);
+            }
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+/// Receives every entry built by [`Logger`] as `(level, message, context)`, where `context`
+/// is the entry's context with any additional context under `additional_context`.
+pub type EntryHandler = Box<dyn Fn(&str, &str, &Value) + Send + Sync>;
+
+static ENTRY_HANDLER: RwLock<Option<EntryHandler>> = RwLock::new(None);
+
+/// Sends every [`Logger`] entry to `handler`, or stops doing so when `None`.
+///
+/// Entries are passed to the handler whether or not a `log` backend is installed and
+/// regardless of its level, on whichever thread logged them. The handler must not call
+/// this function.
+pub fn set_entry_handler(handler: Option<EntryHandler>) {
+    *ENTRY_HANDLER.write().unwrap() = handler;
+}
+
+fn dispatch(entry: &Value) {
+    if let Some(handler) = ENTRY_HANDLER.read().unwrap().as_ref() {
+        let mut context = entry["context"].clone();
+        if let (Value::Object(context), Some(additional)) =
+            (&mut context, entry.get("additional_context"))
+        {
+            context.insert("additional_context".to_string(), additional.clone());
+        }
+        handler(
+            entry["level"].as_str().unwrap_or_default(),
+            entry["message"].as_str().unwrap_or_default(),
+            &context,
+        );
+    }
+}
+
+/// How the library's own log backend writes entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// One JSON object per line, as built by [`Logger`].
+    #[default]
+    Json,
+    /// `timestamp LEVEL message context` per line.
+    Text,
+}
+
+/// Where the library's own log backend writes entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogDestination {
+    #[default]
+    Stderr,
+    /// Appends to the file at this path, creating it if needed.
+    File(PathBuf),
+}
+
+/// Configuration for [`init`], e.g.
+/// `{"level": "debug", "format": "text", "destination": {"file": "/var/log/polycrypt.log"},
+/// "context": {"service": "billing"}, "redact": {"keys": ["mrn"]}}`. Every key is optional.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggerConfig {
+    /// `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+    pub format: LogFormat,
+    pub destination: LogDestination,
+    /// Merged into the `context` of every entry; keys set at the call site take precedence.
+    pub context: Map<String, Value>,
+    pub redact: RedactionConfig,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::default(),
+            destination: LogDestination::default(),
+            context: Map::new(),
+            redact: RedactionConfig::default(),
+        }
+    }
+}
+
+impl LoggerConfig {
+    pub fn from_json(json: &str) -> Result<Self, PolyCryptError> {
+        serde_json::from_str(json)
+            .map_err(|e| logger_error(format!("Invalid configuration: {}", e)))
+    }
+
+    fn level_filter(&self) -> Result<LevelFilter, PolyCryptError> {
+        LevelFilter::from_str(&self.level)
+            .map_err(|_| logger_error(format!("Unknown level '{}'", self.level)))
+    }
+}
+
+/// Installs the library's log backend as the `log` logger, or reconfigures it if it is
+/// already installed.
+///
+/// This is meant for hosts that cannot install a `log` backend themselves, such as the
+/// FFI bindings. Fails if a different backend was installed first.
+pub fn init(config: LoggerConfig) -> Result<(), PolyCryptError> {
+    let level = config.level_filter()?;
+    let redactor = Redactor::new(&config.redact)?;
+    let sink = Sink::open(&config)?;
+
+    let mut installed = BACKEND.installed.lock().unwrap();
+    if !*installed {
+        log::set_logger(&BACKEND)
+            .map_err(|_| logger_error("Another logger is already installed".to_string()))?;
+        *installed = true;
+    }
+
+    *REDACTOR.write().unwrap() = Some(Arc::new(redactor));
+    *BACKEND.sink.write().unwrap() = Some(sink);
+    log::set_max_level(level);
+    Ok(())
+}
+
+static BACKEND: Backend = Backend {
+    installed: Mutex::new(false),
+    sink: RwLock::new(None),
+};
+
+struct Backend {
+    // Whether `BACKEND` is the `log` logger.
+    installed: Mutex<bool>,
+    sink: RwLock<Option<Sink>>,
+}
+
+struct Sink {
+    format: LogFormat,
+    context: Map<String, Value>,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Sink {
+    fn open(config: &LoggerConfig) -> Result<Self, PolyCryptError> {
+        let writer: Box<dyn Write + Send> = match &config.destination {
+            LogDestination::Stderr => Box::new(std::io::stderr()),
+            LogDestination::File(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| {
+                        logger_error(format!("Cannot open '{}': {}", path.display(), e))
+                    })?;
+                Box::new(file)
+            }
+        };
+        Ok(Self {
+            format: config.format,
+            context: config.context.clone(),
+            writer: Mutex::new(writer),
+        })
+    }
+}
+
+impl log::Log for Backend {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let sink = self.sink.read().unwrap();
+        if let Some(sink) = sink.as_ref() {
+            let entry = record_entry(record, &sink.context);
+            let mut line = format_entry(&entry, sink.format);
+            line.push('\n');
+            let mut writer = sink.writer.lock().unwrap();
+            let _ = writer.write_all(line.as_bytes());
+            let _ = writer.flush();
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(sink) = self.sink.read().unwrap().as_ref() {
+            let _ = sink.writer.lock().unwrap().flush();
+        }
+    }
+}
+
+// `Logger` emits complete JSON entries from this module; everything else logged through
+// `log` gets wrapped into the same shape.
+const ENTRY_TARGET: &str = module_path!();
+
+fn record_entry(record: &Record, static_context: &Map<String, Value>) -> Value {
+    let message = record.args().to_string();
+    let mut entry = match serde_json::from_str(&message) {
+        Ok(entry @ Value::Object(_)) if record.target() == ENTRY_TARGET => entry,
+        _ => json!({
+            "level": record.level().to_string(),
+            "message": redactor().redact_str(&message),
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "context": {"target": record.target()},
+        }),
+    };
+
+    merge_static_context(&mut entry, static_context);
+    entry
+}
+
+fn merge_static_context(entry: &mut Value, static_context: &Map<String, Value>) {
+    if let Some(Value::Object(context)) = entry.get_mut("context") {
+        for (key, value) in static_context {
+            context.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+fn format_entry(entry: &Value, format: LogFormat) -> String {
+    match format {
+        LogFormat::Json => serde_json::to_string(entry).unwrap(),
+        LogFormat::Text => {
+            let text = |key: &str| entry[key].as_str().unwrap_or_default();
+            let mut line = format!(
+                "{} {} {} {}",
+                text("timestamp"),
+                text("level"),
+                text("message"),
+                entry["context"]
+            );
+            if let Some(additional) = entry.get("additional_context") {
+                line.push(' ');
+                line.push_str(&additional.to_string());
+            }
+            line
+        }
+    }
+}
+
+fn logger_error(message: String) -> PolyCryptError {
+    PolyCryptError::LoggerError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_parsing() {
+        let config = LoggerConfig::from_json(
+            r#"{"level": "debug", "format": "text", "destination": {"file": "/tmp/x.log"}, "context": {"service": "billing"}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.level_filter().unwrap(), LevelFilter::Debug);
+        assert_eq!(config.format, LogFormat::Text);
+        assert_eq!(
+            config.destination,
+            LogDestination::File(PathBuf::from("/tmp/x.log"))
+        );
+
+        assert_eq!(
+            LoggerConfig::from_json("{}").unwrap(),
+            LoggerConfig::default()
+        );
+        assert!(LoggerConfig::from_json(r#"{"destination": "stdout"}"#).is_err());
+        assert!(LoggerConfig::from_json(r#"{"level": "loud"}"#)
+            .unwrap()
+            .level_filter()
+            .is_err());
+    }
+
+    #[test]
+    fn test_redaction() {
+        let redactor = Redactor::new(&RedactionConfig {
+            keys: vec!["MRN".to_string()],
+            patterns: vec![r"\bP-\d{6}\b".to_string()],
+        })
+        .unwrap();
+        let key = base64::encode([7u8; 32]);
+        let mut entry = json!({
+            "message": "Failed for john@example.com with SSN 123-45-6789",
+            "context": {
+                "operation": "encrypt_fields",
+                "key_id": "phi-2024",
+                "Password": "hunter2",
+                "mrn": 12345,
+                "records": [{"patient": "P-123456", "fields": ["ssn", "name"]}],
+                "error": format!("bad key {}", key),
+            },
+        });
+        redactor.redact_value(&mut entry);
+
+        assert_eq!(
+            entry["message"],
+            "Failed for [REDACTED] with SSN [REDACTED]"
+        );
+        let context = &entry["context"];
+        assert_eq!(context["operation"], "encrypt_fields");
+        assert_eq!(context["key_id"], "phi-2024");
+        assert_eq!(context["Password"], REDACTED);
+        assert_eq!(context["mrn"], REDACTED);
+        assert_eq!(context["records"][0]["patient"], REDACTED);
+        assert_eq!(context["records"][0]["fields"], json!(["ssn", "name"]));
+        assert_eq!(context["error"], "bad key [REDACTED]");
+        assert_eq!(redactor.redact_str(&hex::encode([1u8; 32])), REDACTED);
+
+        assert!(Redactor::new(&RedactionConfig {
+            keys: vec![],
+            patterns: vec!["(".to_string()],
+        })
+        .is_err());
+        assert_eq!(
+            format!("{:?}", Secret::new("hunter2")),
+            "Secret([REDACTED])"
+        );
+        let secret = Secret::new("hunter2".to_string());
+        assert_eq!(secret.clone().into_inner(), "hunter2");
+        assert_eq!(secret.expose_secret(), "hunter2");
+    }
+
+    #[test]
+    fn test_entry_handler_receives_entries() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        set_entry_handler(Some(Box::new(move |level, message, context| {
+            if message == "Handler test" {
+                sink.lock()
+                    .unwrap()
+                    .push((level.to_string(), context.clone()));
+            }
+        })));
+
+        let logger = Logger::new(json!({"operation": "handler_test"}));
+        logger.info("Handler test", Some(json!({"fields": 2})));
+        logger.error("Handler test", None);
+        set_entry_handler(None);
+        logger.info("Handler test", None);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].0, "INFO");
+        assert_eq!(received[0].1["operation"], "handler_test");
+        assert_eq!(received[0].1["additional_context"], json!({"fields": 2}));
+        assert_eq!(received[1].0, "ERROR");
+        assert!(received[1].1.get("additional_context").is_none());
+    }
+
+    #[test]
+    fn test_entries_get_static_context() {
+        let static_context = json!({"service": "billing", "operation": "ignored"});
+        let static_context = static_context.as_object().unwrap();
+
+        let logged = Logger::new(json!({"operation": "encryption"})).create_log_entry(
+            "INFO",
+            "Encryption completed",
+            None,
+        );
+        let entry = record_entry(
+            &Record::builder()
+                .args(format_args!("{}", logged))
+                .level(log::Level::Info)
+                .target(ENTRY_TARGET)
+                .build(),
+            static_context,
+        );
+        assert_eq!(
+            entry["context"],
+            json!({"operation": "encryption", "service": "billing"})
+        );
 
-        serde_json::to_string(&entry).unwrap()
+        let entry = record_entry(
+            &Record::builder()
+                .args(format_args!("Decrypting field"))
+                .level(log::Level::Debug)
+                .target("polycrypt_rs::crypto::encryption")
+                .build(),
+            static_context,
+        );
+        assert_eq!(entry["level"], "DEBUG");
+        assert_eq!(
+            entry["context"]["target"],
+            "polycrypt_rs::crypto::encryption"
+        );
+        assert_eq!(entry["context"]["service"], "billing");
+        assert!(format_entry(&entry, LogFormat::Text).contains(" DEBUG Decrypting field {"));
     }
 }
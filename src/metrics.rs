@@ -0,0 +1,501 @@
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+// std's clock panics on wasm32-unknown-unknown; web-time reads `performance.now()` instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.00001, 0.000025, 0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05,
+    0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Every measured operation. Counters are allocated for each one up front, so recording is a
+/// handful of atomic increments with no lock or allocation. Names missing from this list
+/// are not recorded; a test checks that every name used in the crate is listed.
+pub const OPERATIONS: &[&str] = &[
+    "encrypt",
+    "encrypt_deterministic",
+    "encrypt_into",
+    "decrypt",
+    "decrypt_into",
+    "blind_index",
+    "encrypt_fields",
+    "encrypt_fields_in_batch",
+    "decrypt_fields",
+    "decrypt_fields_in_batch",
+    "encrypt_stream",
+    "decrypt_stream",
+    "ffi.encrypt",
+    "ffi.decrypt",
+    "ffi.encrypt_into",
+    "ffi.decrypt_into",
+    "ffi.encrypt_fields",
+    "ffi.encrypt_fields_bytes",
+    "ffi.decrypt_fields",
+    "ffi.decrypt_fields_bytes",
+    "ffi.encrypt_fields_in_batch",
+    "ffi.encrypt_fields_in_batch_bytes",
+    "ffi.decrypt_fields_in_batch",
+    "ffi.decrypt_fields_in_batch_bytes",
+    "ffi.mask_fields",
+    "ffi.mask_fields_bytes",
+    "ffi.decrypt_and_mask_fields",
+    "ffi.decrypt_and_mask_fields_bytes",
+    "ffi.pseudonymize_fields",
+    "ffi.pseudonymize_fields_bytes",
+    "ffi.inspect_ciphertext",
+    "ffi.phi_fields_from_schema",
+    "ffi.phi_fields_from_schema_bytes",
+    "ffi.metrics_snapshot",
+    "ffi.init_logger",
+    "ffi.init_logger_bytes",
+    "ffi.set_log_callback",
+];
+
+/// Operations on a single field, recorded per field path by [`observe_field`].
+pub const FIELD_OPERATIONS: &[&str] = &["encrypt_field", "decrypt_field"];
+
+/// Most field paths recorded separately per field operation. Further paths are counted
+/// together under [`OTHER_FIELDS`], so callers with many distinct or generated paths cannot
+/// grow the number of series without bound.
+pub const MAX_FIELDS: usize = 64;
+
+/// The `field` label of paths beyond [`MAX_FIELDS`].
+pub const OTHER_FIELDS: &str = "_other";
+
+struct Stats {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    duration_nanos: AtomicU64,
+    // Non-cumulative counts per bucket; the last one counts observations above every bound.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            duration_nanos: AtomicU64::new(0),
+            buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len() + 1],
+        }
+    }
+
+    fn record(&self, elapsed: Duration, ok: bool) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.duration_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+        self.duration_nanos.store(0, Ordering::Relaxed);
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self, operation: &str, field: Option<&str>) -> OperationMetrics {
+        let mut cumulative = 0;
+        let buckets = LATENCY_BUCKETS
+            .iter()
+            .zip(&self.buckets)
+            .map(|(le, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                Bucket {
+                    le: *le,
+                    count: cumulative,
+                }
+            })
+            .collect();
+        OperationMetrics {
+            operation: operation.to_string(),
+            field: field.map(str::to_string),
+            calls: self.calls.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            duration_seconds_sum: self.duration_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+            buckets,
+        }
+    }
+}
+
+// Indexed like `OPERATIONS`.
+static REGISTRY: [Stats; OPERATIONS.len()] = [const { Stats::new() }; OPERATIONS.len()];
+
+// Field paths are only known at runtime, so their counters are allocated on first use.
+// Recording a path seen before only takes the read lock.
+struct FieldRegistry {
+    fields: RwLock<Vec<FieldStats>>,
+}
+
+struct FieldStats {
+    operation: &'static str,
+    field: String,
+    stats: Stats,
+}
+
+impl FieldRegistry {
+    const fn new() -> Self {
+        Self {
+            fields: RwLock::new(Vec::new()),
+        }
+    }
+
+    fn record(&self, operation: &'static str, field: &str, elapsed: Duration, ok: bool) {
+        {
+            let fields = self.fields.read().unwrap();
+            if let Some(index) = Self::slot(&fields, operation, field) {
+                fields[index].stats.record(elapsed, ok);
+                return;
+            }
+        }
+        let mut fields = self.fields.write().unwrap();
+        let index = Self::slot(&fields, operation, field).unwrap_or_else(|| {
+            let full = fields
+                .iter()
+                .filter(|f| f.operation == operation && f.field != OTHER_FIELDS)
+                .count()
+                >= MAX_FIELDS;
+            fields.push(FieldStats {
+                operation,
+                field: if full { OTHER_FIELDS } else { field }.to_string(),
+                stats: Stats::new(),
+            });
+            fields.len() - 1
+        });
+        fields[index].stats.record(elapsed, ok);
+    }
+
+    // The entry `field` is recorded under, if it exists yet.
+    fn slot(fields: &[FieldStats], operation: &str, field: &str) -> Option<usize> {
+        let find = |field: &str| {
+            fields
+                .iter()
+                .position(|f| f.operation == operation && f.field == field)
+        };
+        find(field).or_else(|| {
+            let tracked = fields
+                .iter()
+                .filter(|f| f.operation == operation && f.field != OTHER_FIELDS)
+                .count();
+            if tracked >= MAX_FIELDS {
+                find(OTHER_FIELDS)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn reset(&self) {
+        self.fields.write().unwrap().clear();
+    }
+
+    fn snapshot(&self) -> Vec<OperationMetrics> {
+        self.fields
+            .read()
+            .unwrap()
+            .iter()
+            .map(|f| f.stats.snapshot(f.operation, Some(&f.field)))
+            .collect()
+    }
+}
+
+static FIELD_REGISTRY: FieldRegistry = FieldRegistry::new();
+
+/// Records one call of `operation`, which must be listed in [`OPERATIONS`].
+pub fn record(operation: &'static str, elapsed: Duration, ok: bool) {
+    match OPERATIONS.iter().position(|name| *name == operation) {
+        Some(index) => REGISTRY[index].record(elapsed, ok),
+        None => debug_assert!(false, "unregistered metrics operation {}", operation),
+    }
+}
+
+/// Records one call of `operation` on the field at `field`. `operation` must be listed in
+/// [`FIELD_OPERATIONS`].
+pub fn record_field(operation: &'static str, field: &str, elapsed: Duration, ok: bool) {
+    if FIELD_OPERATIONS.contains(&operation) {
+        FIELD_REGISTRY.record(operation, field, elapsed, ok);
+    } else {
+        debug_assert!(false, "unregistered metrics field operation {}", operation);
+    }
+}
+
+/// Runs `f` and records its latency and whether it failed under `operation`.
+pub fn observe<T, E>(operation: &'static str, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    let start = Instant::now();
+    let result = f();
+    record(operation, start.elapsed(), result.is_ok());
+    result
+}
+
+/// Like [`observe`], for an operation on the field at `field`.
+pub fn observe_field<T, E>(
+    operation: &'static str,
+    field: &str,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let start = Instant::now();
+    let result = f();
+    record_field(operation, field, start.elapsed(), result.is_ok());
+    result
+}
+
+/// Clears every recorded metric.
+pub fn reset() {
+    REGISTRY.iter().for_each(Stats::reset);
+    FIELD_REGISTRY.reset();
+}
+
+/// Metrics of one operation, or of one field for [`FIELD_OPERATIONS`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperationMetrics {
+    pub operation: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub calls: u64,
+    pub errors: u64,
+    pub duration_seconds_sum: f64,
+    /// Cumulative counts of calls that took at most `le` seconds.
+    pub buckets: Vec<Bucket>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bucket {
+    pub le: f64,
+    pub count: u64,
+}
+
+/// Returns the current value of every operation called so far, in [`OPERATIONS`] order,
+/// followed by every field in the order they were first seen.
+pub fn snapshot() -> Vec<OperationMetrics> {
+    OPERATIONS
+        .iter()
+        .zip(&REGISTRY)
+        .map(|(operation, stats)| stats.snapshot(operation, None))
+        .chain(FIELD_REGISTRY.snapshot())
+        .filter(|metrics| metrics.calls > 0)
+        .collect()
+}
+
+/// Renders every metric in the Prometheus text exposition format.
+pub fn render_prometheus() -> String {
+    render(&snapshot())
+}
+
+fn render(metrics: &[OperationMetrics]) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP polycrypt_operations_total Calls of each operation.\n");
+    out.push_str("# TYPE polycrypt_operations_total counter\n");
+    for m in metrics {
+        writeln!(
+            out,
+            "polycrypt_operations_total{{{}}} {}",
+            labels(m),
+            m.calls
+        )
+        .unwrap();
+    }
+
+    out.push_str("# HELP polycrypt_operation_errors_total Calls of each operation that failed.\n");
+    out.push_str("# TYPE polycrypt_operation_errors_total counter\n");
+    for m in metrics {
+        writeln!(
+            out,
+            "polycrypt_operation_errors_total{{{}}} {}",
+            labels(m),
+            m.errors
+        )
+        .unwrap();
+    }
+
+    out.push_str("# HELP polycrypt_operation_duration_seconds Latency of each operation.\n");
+    out.push_str("# TYPE polycrypt_operation_duration_seconds histogram\n");
+    for m in metrics {
+        let labels = labels(m);
+        for bucket in &m.buckets {
+            writeln!(
+                out,
+                "polycrypt_operation_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bucket.le, bucket.count
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "polycrypt_operation_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, m.calls
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "polycrypt_operation_duration_seconds_sum{{{}}} {}",
+            labels, m.duration_seconds_sum
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "polycrypt_operation_duration_seconds_count{{{}}} {}",
+            labels, m.calls
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn labels(metrics: &OperationMetrics) -> String {
+    let mut labels = format!("operation=\"{}\"", escape_label(&metrics.operation));
+    if let Some(field) = &metrics.field {
+        write!(labels, ",field=\"{}\"", escape_label(field)).unwrap();
+    }
+    labels
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_histogram() {
+        let stats = Stats::new();
+        stats.record(Duration::from_micros(20), true);
+        stats.record(Duration::from_millis(2), false);
+        stats.record(Duration::from_secs(10), true);
+
+        let m = stats.snapshot("test_op", None);
+        assert_eq!((m.calls, m.errors), (3, 1));
+        assert!((m.duration_seconds_sum - 10.00202).abs() < 1e-9);
+        let count_at = |le: f64| m.buckets.iter().find(|b| b.le == le).unwrap().count;
+        assert_eq!(count_at(0.00001), 0);
+        assert_eq!(count_at(0.000025), 1);
+        assert_eq!(count_at(0.0025), 2);
+        assert_eq!(count_at(2.5), 2);
+
+        stats.reset();
+        assert_eq!(stats.snapshot("test_op", None).calls, 0);
+    }
+
+    #[test]
+    fn test_observe_registered_operation() {
+        // The registry is shared with every other test, so only check that counts grow.
+        let errors = |snapshot: Vec<OperationMetrics>| {
+            snapshot
+                .into_iter()
+                .find(|m| m.operation == "blind_index")
+                .map_or(0, |m| m.errors)
+        };
+        let before = errors(snapshot());
+        let result: Result<(), &str> = observe("blind_index", || Err("failed"));
+        assert!(result.is_err());
+        assert!(errors(snapshot()) > before);
+    }
+
+    #[test]
+    fn test_field_series_are_bounded() {
+        let registry = FieldRegistry::new();
+        for i in 0..MAX_FIELDS + 10 {
+            registry.record("encrypt_field", &format!("f{}", i), Duration::ZERO, true);
+        }
+        registry.record("encrypt_field", "f0", Duration::ZERO, false);
+        registry.record("decrypt_field", "f0", Duration::ZERO, true);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), MAX_FIELDS + 2);
+        let find = |operation: &str, field: &str| {
+            snapshot
+                .iter()
+                .find(|m| m.operation == operation && m.field.as_deref() == Some(field))
+                .unwrap()
+        };
+        assert_eq!(find("encrypt_field", "f0").errors, 1);
+        assert_eq!(find("encrypt_field", OTHER_FIELDS).calls, 10);
+        assert_eq!(find("decrypt_field", "f0").calls, 1);
+
+        registry.reset();
+        assert!(registry.snapshot().is_empty());
+    }
+
+    // `record` cannot reject unknown names at compile time, so check every name the crate
+    // records with.
+    #[test]
+    fn test_recorded_operations_are_registered() {
+        let call = regex::Regex::new(
+            r#"(?:^|[^.\w])(observe|observe_field|record|record_field|instrumented|instrumented_code)\(\s*"([^"]*)""#,
+        )
+        .unwrap();
+        let mut dirs = vec![std::path::PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src"
+        ))];
+        let mut checked = 0;
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let source = std::fs::read_to_string(&path).unwrap();
+                for captures in call.captures_iter(&source) {
+                    let registered = match &captures[1] {
+                        "observe_field" | "record_field" => FIELD_OPERATIONS,
+                        _ => OPERATIONS,
+                    };
+                    assert!(
+                        registered.contains(&&captures[2]),
+                        "{} in {} is not registered",
+                        &captures[2],
+                        path.display()
+                    );
+                    checked += 1;
+                }
+            }
+        }
+        assert!(checked > OPERATIONS.len());
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let stats = Stats::new();
+        stats.record(Duration::from_micros(1), false);
+        let text = render(&[stats.snapshot("test\"render", None)]);
+        assert!(text.contains("# TYPE polycrypt_operation_duration_seconds histogram\n"));
+        assert!(text.contains("polycrypt_operation_errors_total{operation=\"test\\\"render\"} 1\n"));
+        assert!(text.contains(
+            "polycrypt_operation_duration_seconds_bucket{operation=\"test\\\"render\",le=\"0.00001\"} 1\n"
+        ));
+        assert!(text.contains(
+            "polycrypt_operation_duration_seconds_count{operation=\"test\\\"render\"} 1\n"
+        ));
+
+        let text = render(&[stats.snapshot("encrypt_field", Some("contacts[].phone"))]);
+        assert!(text.contains(
+            "polycrypt_operations_total{operation=\"encrypt_field\",field=\"contacts[].phone\"} 1\n"
+        ));
+    }
+}
@@ -0,0 +1,551 @@
+//! Local HTTP/JSON sidecar for services that cannot load the shared library.
+//!
+//! Every endpoint takes a JSON `POST` body and answers with JSON. Binary data travels as
+//! base64. Keys never leave the process: requests name a key id from the keyring loaded at
+//! startup, or fall back to its primary key.
+//!
+//! | Endpoint                     | Request                                  | Response                  |
+//! |------------------------------|------------------------------------------|---------------------------|
+//! | `POST /v1/encrypt`           | `{"plaintext", "key_id"?}`               | `{"ciphertext"}`          |
+//! | `POST /v1/decrypt`           | `{"ciphertext", "key_id"?}`              | `{"plaintext"}`           |
+//! | `POST /v1/encrypt_fields`    | `{"record", "fields", "key_id"?}`        | `{"record"}`              |
+//! | `POST /v1/decrypt_fields`    | `{"record", "fields", "key_id"?}`        | `{"record"}`              |
+//! | `POST /v1/encrypt_fields_batch` | `{"records", "fields", "key_id"?}`    | `{"records"}`             |
+//! | `POST /v1/decrypt_fields_batch` | `{"records", "fields", "key_id"?}`    | `{"records"}`             |
+//! | `GET /health`                | -                                        | `{"status": "ok"}`        |
+//!
+//! Errors are returned as `{"error": "..."}` with a 4xx status.
+//!
+//! Every `/v1` request must carry `Authorization: Bearer <token>` with the token given at
+//! startup. Over TCP, the `Host` header must also name the address the server is bound to,
+//! which stops web pages from reaching it through DNS rebinding. [`Server::bind`] only accepts
+//! loopback addresses; [`Server::bind_remote`] is the explicit opt-in for anything else. Unix
+//! sockets are created readable and writable by the owner only.
+//!
+//! Decryptions through the server are not written to the [audit log](crate::audit).
+
+use crate::crypto::encryption::{self, FieldCipher, FieldOptions};
+use crate::crypto::envelope::{self, Algorithm, Header};
+use crate::crypto::keyring::Keyring;
+use crate::error::PolyCryptError;
+use crate::Logger;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Largest request body accepted, in bytes.
+pub const MAX_BODY_LEN: u64 = 64 * 1024 * 1024;
+
+pub struct Server {
+    http: tiny_http::Server,
+    keyring: Keyring,
+    token: String,
+    // Host names accepted in the `Host` header, and the port they must be paired with.
+    // `None` for Unix sockets, which browsers cannot reach.
+    allowed_hosts: Option<(Vec<String>, u16)>,
+    stopping: AtomicBool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EncryptRequest {
+    plaintext: String,
+    #[serde(default)]
+    key_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DecryptRequest {
+    ciphertext: String,
+    #[serde(default)]
+    key_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldsRequest {
+    record: Value,
+    fields: Vec<String>,
+    #[serde(default)]
+    key_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchRequest {
+    records: Vec<Value>,
+    fields: Vec<String>,
+    #[serde(default)]
+    key_id: Option<String>,
+}
+
+impl Server {
+    /// Listens on a loopback TCP address such as `127.0.0.1:8700`, requiring `token` as the
+    /// bearer token of every `/v1` request. Fails for addresses that are not loopback.
+    pub fn bind(addr: &str, keyring: Keyring, token: String) -> Result<Self, PolyCryptError> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() || addrs.iter().any(|addr| !addr.ip().is_loopback()) {
+            return Err(PolyCryptError::IoError(std::io::Error::other(format!(
+                "{} is not a loopback address",
+                addr
+            ))));
+        }
+        Self::bind_remote(addr, keyring, token)
+    }
+
+    /// Like [`Server::bind`], but also accepts addresses reachable from other hosts. Requests
+    /// must then name the bound IP address, or a host added with [`Server::allow_host`].
+    pub fn bind_remote(
+        addr: &str,
+        keyring: Keyring,
+        token: String,
+    ) -> Result<Self, PolyCryptError> {
+        check_token(&token)?;
+        let http = tiny_http::Server::http(addr).map_err(bind_error)?;
+        let local_addr = http
+            .server_addr()
+            .to_ip()
+            .expect("a TCP server has an IP address");
+        let mut names = vec![host_name(local_addr.ip())];
+        if local_addr.ip().is_loopback() {
+            names.extend(["localhost", "127.0.0.1", "[::1]"].map(String::from));
+        }
+        Ok(Self {
+            http,
+            keyring,
+            token,
+            allowed_hosts: Some((names, local_addr.port())),
+            stopping: AtomicBool::new(false),
+        })
+    }
+
+    /// Listens on a Unix domain socket, which is made accessible to the owner only. Fails if
+    /// `path` already exists.
+    #[cfg(unix)]
+    pub fn bind_unix(
+        path: &std::path::Path,
+        keyring: Keyring,
+        token: String,
+    ) -> Result<Self, PolyCryptError> {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+        check_token(&token)?;
+        if path.symlink_metadata().is_ok() {
+            return Err(PolyCryptError::IoError(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            )));
+        }
+        // Bind inside a directory only the owner can enter and tighten the socket's mode
+        // before moving it into place, so nobody else can connect in between.
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => std::path::Path::new("."),
+        };
+        let private_dir = parent.join(format!(
+            ".polycrypt-server-{}-{:08x}",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&private_dir)?;
+        let staged = private_dir.join("socket");
+        let http = tiny_http::Server::http_unix(&staged)
+            .map_err(bind_error)
+            .and_then(|http| {
+                std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+                std::fs::rename(&staged, path)?;
+                Ok(http)
+            });
+        // Best effort: the staged socket is only left over if moving it failed.
+        let _ = std::fs::remove_file(&staged);
+        let _ = std::fs::remove_dir(&private_dir);
+        let http = http?;
+        Ok(Self {
+            http,
+            keyring,
+            token,
+            allowed_hosts: None,
+            stopping: AtomicBool::new(false),
+        })
+    }
+
+    /// Also accepts requests whose `Host` header names `host`, e.g. a DNS name of a server
+    /// bound with [`Server::bind_remote`]. Has no effect on Unix sockets.
+    pub fn allow_host(&mut self, host: &str) {
+        if let Some((names, _)) = &mut self.allowed_hosts {
+            names.push(host.to_ascii_lowercase());
+        }
+    }
+
+    /// The TCP address the server listens on, or `None` for a Unix socket.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Serves requests on `workers` threads until [`Server::shutdown`] is called.
+    pub fn run(&self, workers: usize) {
+        let logger = Logger::new(json!({"operation": "server"}));
+        logger.info(
+            "Server started",
+            Some(json!({"workers": workers, "key_ids": self.keyring.key_ids().count()})),
+        );
+
+        std::thread::scope(|scope| {
+            for _ in 0..workers.max(1) {
+                scope.spawn(|| loop {
+                    match self.http.recv() {
+                        Ok(request) => self.respond(request),
+                        Err(_) if self.stopping.load(Ordering::SeqCst) => {
+                            // Each unblock wakes a single worker, so pass it on.
+                            self.http.unblock();
+                            break;
+                        }
+                        Err(e) => log::warn!("Failed to accept a request: {}", e),
+                    }
+                });
+            }
+        });
+
+        logger.info("Server stopped", None);
+    }
+
+    /// Makes [`Server::run`] return once in-flight requests are answered.
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.http.unblock();
+    }
+
+    fn respond(&self, mut request: tiny_http::Request) {
+        // Rejected requests are answered before their body is read, so unauthenticated
+        // clients cannot make the server buffer it.
+        let (status, response) = if !self.host_allowed(&request) {
+            (403, json!({"error": "Host not allowed"}))
+        } else if !request.url().starts_with("/health") && !self.authorized(&request) {
+            (401, json!({"error": "Missing or invalid bearer token"}))
+        } else {
+            let mut body = Vec::new();
+            let read = request
+                .as_reader()
+                .take(MAX_BODY_LEN + 1)
+                .read_to_end(&mut body);
+            match read {
+                Err(e) => (400, json!({"error": format!("Cannot read request: {}", e)})),
+                Ok(_) if body.len() as u64 > MAX_BODY_LEN => {
+                    (413, json!({"error": "Request body is too large"}))
+                }
+                Ok(_) => handle(
+                    &self.keyring,
+                    request.method().as_str(),
+                    request.url(),
+                    &body,
+                ),
+            }
+        };
+
+        let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json")
+            .expect("static header is valid");
+        let response = tiny_http::Response::from_string(response.to_string())
+            .with_status_code(status)
+            .with_header(content_type);
+        // The client may have gone away; there is nobody left to tell.
+        let _ = request.respond(response);
+    }
+
+    fn host_allowed(&self, request: &tiny_http::Request) -> bool {
+        let Some((names, port)) = &self.allowed_hosts else {
+            return true;
+        };
+        header(request, "Host")
+            .and_then(parse_host)
+            .is_some_and(|(name, host_port)| host_port == *port && names.contains(&name))
+    }
+
+    fn authorized(&self, request: &tiny_http::Request) -> bool {
+        header(request, "Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), self.token.as_bytes()))
+    }
+}
+
+fn header<'a>(request: &'a tiny_http::Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+// Splits a `Host` header into its lowercase name and port, which defaults to 80.
+fn parse_host(host: &str) -> Option<(String, u16)> {
+    let host = host.trim().to_ascii_lowercase();
+    let (name, port) = match host.rfind(']') {
+        // A bracketed IPv6 address, optionally followed by a port.
+        Some(end) if host.starts_with('[') => (&host[..=end], host[end + 1..].strip_prefix(':')),
+        _ => match host.rsplit_once(':') {
+            Some((name, port)) => (name, Some(port)),
+            None => (host.as_str(), None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => 80,
+    };
+    Some((name.trim_end_matches('.').to_string(), port))
+}
+
+fn host_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    }
+}
+
+fn check_token(token: &str) -> Result<(), PolyCryptError> {
+    if token.trim().is_empty() {
+        return Err(PolyCryptError::InvalidKeyError(
+            "The server token must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Routes one request and returns the HTTP status and JSON body to answer with.
+pub fn handle(keyring: &Keyring, method: &str, path: &str, body: &[u8]) -> (u16, Value) {
+    let path = path.split('?').next().unwrap_or(path);
+    let result = match (method, path) {
+        ("GET", "/health") => return (200, json!({"status": "ok"})),
+        ("POST", "/v1/encrypt") => parse(body).map(|r| encrypt(keyring, r)),
+        ("POST", "/v1/decrypt") => parse(body).map(|r| decrypt(keyring, r)),
+        ("POST", "/v1/encrypt_fields") => parse(body).map(|r: FieldsRequest| {
+            encrypt_record(keyring, &r.record, &r.fields, r.key_id.as_deref())
+                .map(|record| json!({"record": record}))
+        }),
+        ("POST", "/v1/decrypt_fields") => parse(body).map(|r: FieldsRequest| {
+            decrypt_record(keyring, &r.record, &r.fields, r.key_id.as_deref())
+                .map(|record| json!({"record": record}))
+        }),
+        ("POST", "/v1/encrypt_fields_batch") => parse(body).map(|r: BatchRequest| {
+            r.records
+                .iter()
+                .map(|record| encrypt_record(keyring, record, &r.fields, r.key_id.as_deref()))
+                .collect::<Result<Vec<Value>, PolyCryptError>>()
+                .map(|records| json!({"records": records}))
+        }),
+        ("POST", "/v1/decrypt_fields_batch") => parse(body).map(|r: BatchRequest| {
+            r.records
+                .iter()
+                .map(|record| decrypt_record(keyring, record, &r.fields, r.key_id.as_deref()))
+                .collect::<Result<Vec<Value>, PolyCryptError>>()
+                .map(|records| json!({"records": records}))
+        }),
+        (
+            _,
+            "/health"
+            | "/v1/encrypt"
+            | "/v1/decrypt"
+            | "/v1/encrypt_fields"
+            | "/v1/decrypt_fields"
+            | "/v1/encrypt_fields_batch"
+            | "/v1/decrypt_fields_batch",
+        ) => return (405, json!({"error": "Method not allowed"})),
+        _ => return (404, json!({"error": "Not found"})),
+    };
+
+    match result {
+        Err(message) => (400, json!({ "error": message })),
+        Ok(Err(e)) => (422, json!({"error": e.to_string()})),
+        Ok(Ok(response)) => (200, response),
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, String> {
+    serde_json::from_slice(body).map_err(|e| format!("Invalid request: {}", e))
+}
+
+fn encrypt(keyring: &Keyring, request: EncryptRequest) -> Result<Value, PolyCryptError> {
+    let key_id = resolve_key_id(keyring, request.key_id.as_deref())?;
+    let plaintext = base64::decode(&request.plaintext)?;
+    let payload = encryption::encrypt(&plaintext, keyring.get(key_id)?)?;
+    let header = Header::new(Algorithm::Aes256Cbc, Some(key_id), false);
+    Ok(json!({"ciphertext": base64::encode(envelope::seal(&header, &payload)?)}))
+}
+
+fn decrypt(keyring: &Keyring, request: DecryptRequest) -> Result<Value, PolyCryptError> {
+    let ciphertext = base64::decode(&request.ciphertext)?;
+    let plaintext = if envelope::is_envelope(&ciphertext) {
+        let (header, payload) = envelope::open(&ciphertext)?;
+        let key_id = match header.key_id.as_deref() {
+            Some(key_id) => key_id,
+            None => resolve_key_id(keyring, request.key_id.as_deref())?,
+        };
+        encryption::decrypt(payload, keyring.get(key_id)?)?
+    } else {
+        let key_id = resolve_key_id(keyring, request.key_id.as_deref())?;
+        encryption::decrypt(&ciphertext, keyring.get(key_id)?)?
+    };
+    Ok(json!({"plaintext": base64::encode(plaintext)}))
+}
+
+fn encrypt_record(
+    keyring: &Keyring,
+    record: &Value,
+    fields: &[String],
+    key_id: Option<&str>,
+) -> Result<Value, PolyCryptError> {
+    let key_id = resolve_key_id(keyring, key_id)?;
+    let cipher = FieldCipher {
+        key: keyring.get(key_id)?,
+        key_id: Some(key_id),
+        deterministic: false,
+        strict: false,
+    };
+    encryption::encrypt_paths(record, fields, &cipher)
+}
+
+fn decrypt_record(
+    keyring: &Keyring,
+    record: &Value,
+    fields: &[String],
+    key_id: Option<&str>,
+) -> Result<Value, PolyCryptError> {
+    // Envelopes name their key; the requested or primary key covers legacy values, which
+    // the default options decrypt just as `encryption::decrypt_fields` does.
+    let resolve_key = |envelope_key_id: Option<&str>| match envelope_key_id {
+        Some(envelope_key_id) => keyring.get(envelope_key_id),
+        None => keyring.get(resolve_key_id(keyring, key_id)?),
+    };
+    encryption::decrypt_paths(record, fields, &resolve_key, &FieldOptions::default())
+}
+
+fn resolve_key_id<'a>(
+    keyring: &'a Keyring,
+    key_id: Option<&'a str>,
+) -> Result<&'a str, PolyCryptError> {
+    key_id.or_else(|| keyring.primary_key_id()).ok_or_else(|| {
+        PolyCryptError::InvalidKeyError(
+            "No key id given and the keyring has no primary key".to_string(),
+        )
+    })
+}
+
+fn bind_error(e: Box<dyn std::error::Error + Send + Sync>) -> PolyCryptError {
+    PolyCryptError::IoError(std::io::Error::other(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring() -> Keyring {
+        let mut keyring = Keyring::new();
+        keyring.insert("k1", [1u8; 32]);
+        keyring.insert("k2", [2u8; 32]);
+        keyring.set_primary("k2").unwrap();
+        keyring
+    }
+
+    fn post(keyring: &Keyring, path: &str, body: Value) -> (u16, Value) {
+        handle(keyring, "POST", path, body.to_string().as_bytes())
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let keyring = keyring();
+        let plaintext = base64::encode("Hello, world!");
+
+        let (status, encrypted) = post(
+            &keyring,
+            "/v1/encrypt",
+            json!({"plaintext": plaintext, "key_id": "k1"}),
+        );
+        assert_eq!(status, 200);
+
+        // The envelope names k1, so decryption does not depend on the primary key.
+        let (status, decrypted) = post(
+            &keyring,
+            "/v1/decrypt",
+            json!({"ciphertext": encrypted["ciphertext"]}),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(decrypted["plaintext"], plaintext);
+    }
+
+    #[test]
+    fn test_field_and_batch_endpoints() {
+        let keyring = keyring();
+        let records = json!([{"name": "John Doe", "id": "1"}, {"name": "Jane Doe", "id": "2"}]);
+
+        let (status, encrypted) = post(
+            &keyring,
+            "/v1/encrypt_fields_batch",
+            json!({"records": records, "fields": ["name"]}),
+        );
+        assert_eq!(status, 200);
+        assert_ne!(encrypted["records"][0]["name"], "John Doe");
+
+        let (status, decrypted) = post(
+            &keyring,
+            "/v1/decrypt_fields",
+            json!({"record": encrypted["records"][1], "fields": ["name"]}),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(decrypted["record"], records[1]);
+    }
+
+    #[test]
+    fn test_decrypt_fields_accepts_legacy_values() {
+        let keyring = keyring();
+        let legacy = base64::encode(encryption::encrypt(b"John Doe", &[1u8; 32]).unwrap());
+
+        let (status, decrypted) = post(
+            &keyring,
+            "/v1/decrypt_fields",
+            json!({"record": {"name": legacy}, "fields": ["name"], "key_id": "k1"}),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(decrypted["record"], json!({"name": "John Doe"}));
+    }
+
+    #[test]
+    fn test_parse_host() {
+        assert_eq!(
+            parse_host("LocalHost:8700"),
+            Some(("localhost".to_string(), 8700))
+        );
+        assert_eq!(parse_host("127.0.0.1"), Some(("127.0.0.1".to_string(), 80)));
+        assert_eq!(parse_host("[::1]:8700"), Some(("[::1]".to_string(), 8700)));
+        assert_eq!(
+            parse_host("evil.example.:8700"),
+            Some(("evil.example".to_string(), 8700))
+        );
+        assert_eq!(parse_host("localhost:x"), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let keyring = keyring();
+        assert_eq!(handle(&keyring, "GET", "/health", b"").0, 200);
+        assert_eq!(handle(&keyring, "GET", "/v1/encrypt", b"").0, 405);
+        assert_eq!(handle(&keyring, "POST", "/v1/nope", b"{}").0, 404);
+        assert_eq!(post(&keyring, "/v1/encrypt", json!({"text": "x"})).0, 400);
+        assert_eq!(
+            post(
+                &keyring,
+                "/v1/encrypt",
+                json!({"plaintext": "eA==", "key_id": "missing"})
+            )
+            .0,
+            422
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Export of the library's `tracing` spans to an OpenTelemetry collector.
+//!
+//! Applications that already export their own traces with `tracing-opentelemetry` need
+//! nothing from this module: polycrypt-rs spans are ordinary `tracing` spans, so their layer
+//! exports them as children of the caller's spans. [`otlp_layer`] is for applications without
+//! an OpenTelemetry pipeline. It builds the same kind of layer on top of an OTLP/HTTP
+//! exporter that batches spans on a background thread:
+//!
+//! ```no_run
+//! use polycrypt_rs::telemetry::{self, OtlpConfig};
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let (layer, handle) = telemetry::otlp_layer(OtlpConfig::default()).unwrap();
+//! tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)).unwrap();
+//! // ...
+//! handle.flush().unwrap();
+//! ```
+//!
+//! Spans carry operation names, counts, algorithms and key ids; values are never recorded.
+
+use crate::error::PolyCryptError;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::InstrumentationScope;
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{BatchConfigBuilder, BatchSpanProcessor, SdkTracerProvider, Tracer};
+use opentelemetry_sdk::Resource;
+use std::time::Duration;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    /// Collector traces endpoint, used as is.
+    pub endpoint: String,
+    /// Reported as the `service.name` resource attribute.
+    pub service_name: String,
+    /// Spans are exported once this many are pending...
+    pub batch_size: usize,
+    /// ...or when the oldest pending span is this old.
+    pub flush_interval: Duration,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://127.0.0.1:4318/v1/traces".to_string(),
+            service_name: "polycrypt-rs".to_string(),
+            batch_size: 512,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A `tracing-subscriber` layer exporting spans to an OTLP collector.
+pub type OtlpLayer<S> = OpenTelemetryLayer<S, Tracer>;
+
+/// Controls the exporter behind an [`OtlpLayer`].
+pub struct OtlpHandle {
+    provider: SdkTracerProvider,
+}
+
+/// Builds a layer exporting spans as OTLP/HTTP JSON. The exporter stops, after exporting
+/// pending spans, once the layer and the handle have both been dropped.
+pub fn otlp_layer<S>(config: OtlpConfig) -> Result<(OtlpLayer<S>, OtlpHandle), PolyCryptError>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpJson)
+        .with_endpoint(&config.endpoint)
+        .with_timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| telemetry_error(e.to_string()))?;
+    let processor = BatchSpanProcessor::builder(exporter)
+        .with_batch_config(
+            BatchConfigBuilder::default()
+                .with_max_export_batch_size(config.batch_size)
+                .with_scheduled_delay(config.flush_interval)
+                .build(),
+        )
+        .build();
+    let provider = SdkTracerProvider::builder()
+        .with_span_processor(processor)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name)
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer_with_scope(
+        InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
+            .with_version(env!("CARGO_PKG_VERSION"))
+            .build(),
+    );
+
+    Ok((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        OtlpHandle { provider },
+    ))
+}
+
+impl OtlpHandle {
+    /// Exports every span closed so far and waits for the collector to accept them.
+    pub fn flush(&self) -> Result<(), PolyCryptError> {
+        self.provider
+            .force_flush()
+            .map_err(|e| telemetry_error(e.to_string()))
+    }
+}
+
+fn telemetry_error(message: String) -> PolyCryptError {
+    PolyCryptError::TelemetryError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::encryption;
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // Accepts one request like an OTLP collector would and hands over its body.
+    fn collector() -> (String, mpsc::Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}")
+                .unwrap();
+            sender.send(serde_json::from_slice(&body).unwrap()).unwrap();
+        });
+        (endpoint, receiver)
+    }
+
+    fn attribute<'a>(span: &'a Value, key: &str) -> Option<&'a Value> {
+        span["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|attribute| attribute["key"] == key)
+            .map(|attribute| &attribute["value"])
+    }
+
+    #[test]
+    fn test_spans_exported_to_collector() {
+        let (endpoint, requests) = collector();
+        let (layer, handle) = otlp_layer(OtlpConfig {
+            endpoint,
+            service_name: "billing".to_string(),
+            ..OtlpConfig::default()
+        })
+        .unwrap();
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let record = json!({"name": "John Doe", "ssn": "123-45-6789"});
+            let fields = ["name".to_string(), "ssn".to_string()];
+            tracing::info_span!("handle_request").in_scope(|| {
+                encryption::encrypt_fields(&record, &fields, &[0u8; 32]).unwrap();
+            });
+        });
+        handle.flush().unwrap();
+
+        let request = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        let resource = &request["resourceSpans"][0];
+        assert_eq!(
+            attribute(&resource["resource"], "service.name"),
+            Some(&json!({"stringValue": "billing"}))
+        );
+        let spans = resource["scopeSpans"][0]["spans"].as_array().unwrap();
+        let find = |name: &str| spans.iter().find(|s| s["name"] == name).unwrap();
+        let parent = find("encrypt_fields");
+        // Library spans join the caller's trace.
+        let request_span = find("handle_request");
+        assert_eq!(parent["traceId"], request_span["traceId"]);
+        assert_eq!(parent["parentSpanId"], request_span["spanId"]);
+        // Unsigned fields may be exported as strings; either way the value is 2.
+        let field_count = attribute(parent, "field_count").unwrap();
+        assert!(field_count["intValue"] == "2" || field_count["stringValue"] == "2");
+
+        let children: Vec<&Value> = spans.iter().filter(|s| s["name"] == "encrypt").collect();
+        assert_eq!(children.len(), 2);
+        for child in children {
+            assert_eq!(child["traceId"], parent["traceId"]);
+            assert_eq!(child["parentSpanId"], parent["spanId"]);
+            assert_eq!(
+                attribute(child, "algorithm"),
+                Some(&json!({"stringValue": "aes-256-cbc"}))
+            );
+        }
+
+        let exported = request.to_string();
+        assert!(!exported.contains("John Doe"));
+        assert!(!exported.contains("123-45-6789"));
+    }
+}
@@ -0,0 +1,365 @@
+use crate::crypto::encryption::{derive_subkey, hmac_sha256};
+use crate::crypto::path::FieldPath;
+use crate::error::PolyCryptError;
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const DATE_SHIFT_KEY_LABEL: &[u8] = b"polycrypt-rs/date-shift";
+
+/// Three-digit ZIP prefixes covering 20,000 people or fewer, which Safe Harbor requires to be
+/// replaced with `000`.
+pub const RESTRICTED_ZIP3: [&str; 17] = [
+    "036", "059", "063", "102", "203", "556", "692", "790", "821", "823", "830", "831", "878",
+    "879", "884", "890", "893",
+];
+
+/// Safe Harbor-style transform applied to a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Deidentification {
+    /// Removes the field, for direct identifiers such as names and phone numbers.
+    Drop,
+    /// Generalizes a date to its year (`1980-05-12` becomes `1980`).
+    Year,
+    /// Moves a date by a per-subject offset, so intervals within one subject's records are
+    /// preserved while the real dates are not.
+    ShiftDate,
+    /// Truncates a ZIP code to its first three digits, or `000` for restricted prefixes.
+    /// Numeric ZIP codes are read as five digits, restoring leading zeros.
+    Zip3,
+}
+
+/// Fields to de-identify and how, e.g.
+/// `{"subject_field": "id", "fields": {"name": "drop", "dob": "year", "last_visit": "shift_date", "zip": "zip3"}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeidentifyConfig {
+    pub fields: BTreeMap<String, Deidentification>,
+    /// Top-level field identifying the subject of a record; required by `shift_date`.
+    #[serde(default)]
+    pub subject_field: Option<String>,
+    /// Largest date shift, in days, in either direction.
+    #[serde(default = "default_max_shift_days")]
+    pub max_shift_days: u32,
+}
+
+fn default_max_shift_days() -> u32 {
+    365
+}
+
+impl DeidentifyConfig {
+    pub fn from_json(json: &str) -> Result<Self, PolyCryptError> {
+        let config: Self = serde_json::from_str(json)
+            .map_err(|e| deidentify_error(format!("Invalid configuration: {}", e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), PolyCryptError> {
+        for (field, transform) in &self.fields {
+            let path = FieldPath::parse(field)?;
+            if *transform == Deidentification::Drop && path.leaf_key().is_none() {
+                return Err(deidentify_error(format!(
+                    "Cannot drop '{}': the path must end in a field name",
+                    field
+                )));
+            }
+            if *transform == Deidentification::ShiftDate && self.subject_field.is_none() {
+                return Err(deidentify_error(format!(
+                    "Shifting '{}' requires a subject_field",
+                    field
+                )));
+            }
+        }
+        if self.max_shift_days == 0 {
+            return Err(deidentify_error(
+                "max_shift_days must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// De-identifies a record. `key` seeds the per-subject date shifts; the same key and subject
+/// always produce the same shift, across records and datasets.
+///
+/// Values that cannot be transformed, such as a malformed date, are reported as errors
+/// rather than passed through.
+pub fn deidentify_record(
+    record: &Value,
+    config: &DeidentifyConfig,
+    key: &[u8; 32],
+) -> Result<Value, PolyCryptError> {
+    config.validate()?;
+    let shift = match &config.subject_field {
+        Some(subject_field)
+            if config
+                .fields
+                .values()
+                .any(|t| *t == Deidentification::ShiftDate) =>
+        {
+            let subject = subject_id(record, subject_field)?;
+            date_shift_days(&subject, key, config.max_shift_days)
+        }
+        _ => 0,
+    };
+
+    let mut deidentified = record.clone();
+    for (field, transform) in &config.fields {
+        let path = FieldPath::parse(field)?;
+        if *transform == Deidentification::Drop {
+            path.visit_parents_mut(&mut deidentified, &mut |parent, leaf| {
+                parent.remove(leaf);
+                Ok(())
+            })?;
+            continue;
+        }
+        path.visit_mut(&mut deidentified, &mut |value: &mut Value| {
+            *value = transform_value(value, *transform, shift)
+                .map_err(|e| deidentify_error(format!("Field '{}': {}", field, e)))?;
+            Ok(())
+        })?;
+    }
+    Ok(deidentified)
+}
+
+pub fn deidentify_batch(
+    records: &[Value],
+    config: &DeidentifyConfig,
+    key: &[u8; 32],
+) -> Result<Vec<Value>, PolyCryptError> {
+    records
+        .iter()
+        .map(|record| deidentify_record(record, config, key))
+        .collect()
+}
+
+/// The date shift for `subject`: a non-zero number of days between `-max_days` and
+/// `max_days`, derived from `key` with HMAC.
+pub fn date_shift_days(subject: &str, key: &[u8; 32], max_days: u32) -> i64 {
+    let digest = hmac_sha256(
+        &derive_subkey(key, DATE_SHIFT_KEY_LABEL),
+        subject.as_bytes(),
+    );
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&digest[..8]);
+
+    let max_days = i64::from(max_days.max(1));
+    let n = (u64::from_be_bytes(prefix) % (2 * max_days as u64)) as i64;
+    if n < max_days {
+        -(n + 1)
+    } else {
+        n - max_days + 1
+    }
+}
+
+fn transform_value(
+    value: &Value,
+    transform: Deidentification,
+    shift: i64,
+) -> Result<Value, String> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| transform_value(item, transform, shift))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Value::String(s) => {
+            let transformed = match transform {
+                Deidentification::Year => {
+                    parse_date(s).map(|(date, _)| format!("{:04}", date.year()))
+                }
+                Deidentification::ShiftDate => shift_date(s, shift),
+                Deidentification::Zip3 => zip3(s),
+                Deidentification::Drop => None,
+            };
+            transformed
+                .map(Value::String)
+                .ok_or_else(|| format!("cannot apply {:?} to this value", transform))
+        }
+        // ZIP codes stored as numbers have lost their leading zeros.
+        Value::Number(n) if transform == Deidentification::Zip3 => n
+            .as_u64()
+            .filter(|zip| *zip <= 99_999)
+            .and_then(|zip| zip3(&format!("{:05}", zip)))
+            .map(Value::String)
+            .ok_or_else(|| format!("cannot apply {:?} to this value", transform)),
+        _ => Err(format!(
+            "cannot apply {:?} to a non-string value",
+            transform
+        )),
+    }
+}
+
+fn subject_id(record: &Value, subject_field: &str) -> Result<String, PolyCryptError> {
+    match record.get(subject_field) {
+        Some(Value::String(s)) if !s.is_empty() => Ok(s.clone()),
+        Some(Value::Number(n)) => Ok(n.to_string()),
+        _ => Err(deidentify_error(format!(
+            "Record has no usable subject field '{}'",
+            subject_field
+        ))),
+    }
+}
+
+fn zip3(value: &str) -> Option<String> {
+    let digits = match value.split_once('-') {
+        Some((zip5, plus4)) if plus4.len() == 4 && plus4.chars().all(|c| c.is_ascii_digit()) => {
+            zip5
+        }
+        Some(_) => return None,
+        None => value,
+    };
+    if digits.len() != 5 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let prefix = &digits[..3];
+    Some(if RESTRICTED_ZIP3.contains(&prefix) {
+        "000".to_string()
+    } else {
+        prefix.to_string()
+    })
+}
+
+/// Shifts the `YYYY-MM-DD` date at the start of `value`, keeping any time part as is.
+fn shift_date(value: &str, days: i64) -> Option<String> {
+    let (date, rest) = parse_date(value)?;
+    let shifted = date.checked_add_signed(Duration::days(days))?;
+    Some(format!("{}{}", shifted.format(DATE_FORMAT), rest))
+}
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Parses the `YYYY-MM-DD` date at the start of `value`. The remainder must be empty or a
+/// time part starting with `T` or a space.
+fn parse_date(value: &str) -> Option<(NaiveDate, &str)> {
+    let date = value.get(..10)?;
+    let rest = &value[10..];
+    if !(rest.is_empty() || rest.starts_with(['T', ' '])) {
+        return None;
+    }
+    let parsed = NaiveDate::parse_from_str(date, DATE_FORMAT).ok()?;
+    // chrono also accepts unpadded fields such as `2024-3-01`; require the canonical form.
+    (parsed.format(DATE_FORMAT).to_string() == date).then_some((parsed, rest))
+}
+
+fn deidentify_error(message: String) -> PolyCryptError {
+    PolyCryptError::DeidentifyError(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> DeidentifyConfig {
+        DeidentifyConfig::from_json(
+            r#"{
+                "subject_field": "id",
+                "fields": {
+                    "name": "drop",
+                    "contact.phone": "drop",
+                    "dob": "year",
+                    "last_visit": "shift_date",
+                    "visits[].date": "shift_date",
+                    "zip": "zip3"
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_deidentify_record() {
+        let key = [0u8; 32];
+        let record = json!({
+            "id": "1234",
+            "name": "John Doe",
+            "contact": {"phone": "555-0100", "state": "NY"},
+            "dob": "1980-05-12",
+            "last_visit": "2024-03-01",
+            "visits": [{"date": "2024-02-28T10:30:00Z"}, {"date": "2024-03-01"}],
+            "zip": "12345-6789"
+        });
+
+        let deidentified = deidentify_record(&record, &config(), &key).unwrap();
+        let shift = date_shift_days("1234", &key, 365);
+        let shifted = |y, m, d| {
+            (NaiveDate::from_ymd_opt(y, m, d).unwrap() + Duration::days(shift))
+                .format(DATE_FORMAT)
+                .to_string()
+        };
+
+        assert_eq!(deidentified.get("name"), None);
+        assert_eq!(deidentified["contact"], json!({"state": "NY"}));
+        assert_eq!(deidentified["dob"], "1980");
+        assert_eq!(deidentified["last_visit"], shifted(2024, 3, 1));
+        assert_eq!(deidentified["visits"][1]["date"], shifted(2024, 3, 1));
+        assert_eq!(
+            deidentified["visits"][0]["date"],
+            format!("{}T10:30:00Z", shifted(2024, 2, 28))
+        );
+        assert_eq!(deidentified["zip"], "123");
+        assert_eq!(deidentified["id"], "1234");
+    }
+
+    #[test]
+    fn test_date_shift_is_consistent_per_subject() {
+        let key = [7u8; 32];
+        let shift = date_shift_days("patient-1", &key, 30);
+        assert_eq!(shift, date_shift_days("patient-1", &key, 30));
+        assert_ne!(shift, 0);
+        assert!((-30..=30).contains(&shift));
+
+        let records = vec![
+            json!({"id": "patient-1", "last_visit": "2024-01-01"}),
+            json!({"id": "patient-1", "last_visit": "2024-01-11"}),
+        ];
+        let batch = deidentify_batch(&records, &config(), &key).unwrap();
+        let date = |v: &Value| parse_date(v["last_visit"].as_str().unwrap()).unwrap().0;
+        assert_eq!((date(&batch[1]) - date(&batch[0])).num_days(), 10);
+    }
+
+    #[test]
+    fn test_shift_date_and_parse_date() {
+        assert_eq!(shift_date("2024-02-29", 1).as_deref(), Some("2024-03-01"));
+        assert_eq!(
+            shift_date("2000-01-01T00:00:00Z", -1).as_deref(),
+            Some("1999-12-31T00:00:00Z")
+        );
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("2024-3-01 "), None);
+        assert_eq!(parse_date("2024-03-01x"), None);
+    }
+
+    #[test]
+    fn test_zip3_and_errors() {
+        assert_eq!(zip3("03601").as_deref(), Some("000"));
+        assert_eq!(zip3("10001").as_deref(), Some("100"));
+        assert_eq!(zip3("1234"), None);
+        // Numeric ZIPs get their leading zeros back before truncation.
+        assert_eq!(
+            transform_value(&json!(2134), Deidentification::Zip3, 0).unwrap(),
+            "021"
+        );
+        assert_eq!(
+            transform_value(&json!(3601), Deidentification::Zip3, 0).unwrap(),
+            "000"
+        );
+        assert!(transform_value(&json!(123456), Deidentification::Zip3, 0).is_err());
+        assert!(transform_value(&json!(12), Deidentification::Year, 0).is_err());
+
+        let key = [0u8; 32];
+        assert!(
+            deidentify_record(&json!({"id": "1", "dob": "05/12/1980"}), &config(), &key).is_err()
+        );
+        assert!(deidentify_record(&json!({"last_visit": "2024-03-01"}), &config(), &key).is_err());
+        assert!(
+            DeidentifyConfig::from_json(r#"{"fields": {"last_visit": "shift_date"}}"#).is_err()
+        );
+        assert!(DeidentifyConfig::from_json(r#"{"fields": {"names[]": "drop"}}"#).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::crypto::encryption;
+use crate::crypto::path::FieldPath;
+use crate::error::PolyCryptError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Masking to apply per field path, e.g. `{"ssn": {"keep_last": 4}, "dob": "year"}`.
+pub type FieldMasks = BTreeMap<String, MaskingMode>;
+
+/// How a plaintext value is disclosed to consumers that must not see it in full.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskingMode {
+    /// Replaces the whole value with [`REDACTED`].
+    Redact,
+    /// Keeps the last N letters or digits and replaces the others with `*`, leaving
+    /// separators in place (`123-45-6789` becomes `***-**-6789`).
+    KeepLast(usize),
+    /// Keeps the first character of the local part and the domain of an email address
+    /// (`john.doe@example.com` becomes `j*******@example.com`).
+    Email,
+    /// Truncates a date to its year (`1980-05-12` becomes `1980`).
+    Year,
+}
+
+impl MaskingMode {
+    /// Masks `value`. Values that do not fit the mode, such as an email without `@`, are
+    /// redacted rather than passed through.
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            MaskingMode::Redact => REDACTED.to_string(),
+            MaskingMode::KeepLast(n) => keep_last(value, *n),
+            MaskingMode::Email => mask_email(value).unwrap_or_else(|| REDACTED.to_string()),
+            MaskingMode::Year => year_of(value).unwrap_or_else(|| REDACTED.to_string()),
+        }
+    }
+}
+
+/// Masks the fields of a plaintext record according to `masks`.
+pub fn mask_fields(record: &Value, masks: &FieldMasks) -> Result<Value, PolyCryptError> {
+    let mut masked_record = record.clone();
+    for (field, mode) in masks {
+        FieldPath::parse(field)?.visit_mut(&mut masked_record, &mut |value: &mut Value| {
+            *value = mask_value(value, mode);
+            Ok(())
+        })?;
+    }
+    Ok(masked_record)
+}
+
+/// Decrypts the masked fields of an encrypted record and masks them, so the caller never
+/// holds the full plaintext. Use in place of
+/// [`decrypt_fields`](encryption::decrypt_fields) for consumers that only need masked PHI.
+/// Like `decrypt_fields`, it is not audited; see [`crate::audit`].
+pub fn decrypt_and_mask_fields(
+    record: &Value,
+    masks: &FieldMasks,
+    key: &[u8; 32],
+) -> Result<Value, PolyCryptError> {
+    let fields: Vec<String> = masks.keys().cloned().collect();
+    let decrypted_record = encryption::decrypt_fields(record, &fields, key)?;
+    mask_fields(&decrypted_record, masks)
+}
+
+/// Masks a string, or each string of an array. Other JSON values are masked through their
+/// textual representation.
+pub fn mask_value(value: &Value, mode: &MaskingMode) -> Value {
+    match value {
+        Value::Null => Value::Null,
+        Value::String(s) => Value::String(mode.apply(s)),
+        Value::Array(items) => Value::Array(items.iter().map(|v| mask_value(v, mode)).collect()),
+        other => Value::String(mode.apply(&other.to_string())),
+    }
+}
+
+fn mask_email(value: &str) -> Option<String> {
+    let (local, domain) = value.rsplit_once('@')?;
+    let mut chars = local.chars();
+    let first = chars.next()?;
+    if domain.is_empty() {
+        return None;
+    }
+    let hidden = "*".repeat(chars.count().max(1));
+    Some(format!("{}{}@{}", first, hidden, domain))
+}
+
+/// Accepts dates starting with a four-digit year, such as `1980-05-12`, `1980/05/12` and
+/// RFC 3339 timestamps.
+fn year_of(value: &str) -> Option<String> {
+    let year = value.get(..4)?;
+    let rest = &value[4..];
+    let is_year = year.chars().all(|c| c.is_ascii_digit());
+    let is_date = rest.is_empty() || rest.starts_with(['-', '/']);
+    (is_year && is_date).then(|| year.to_string())
+}
+
+fn keep_last(value: &str, n: usize) -> String {
+    let total = value.chars().filter(|c| c.is_alphanumeric()).count();
+    let mut seen = 0;
+    value
+        .chars()
+        .map(|c| {
+            if !c.is_alphanumeric() {
+                return c;
+            }
+            seen += 1;
+            if seen + n > total {
+                c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_keep_last_preserves_separators() {
+        let mode = MaskingMode::KeepLast(4);
+        assert_eq!(mode.apply("123-45-6789"), "***-**-6789");
+        assert_eq!(mode.apply("12"), "12");
+    }
+
+    #[test]
+    fn test_email_and_year() {
+        assert_eq!(
+            MaskingMode::Email.apply("john.doe@example.com"),
+            "j*******@example.com"
+        );
+        assert_eq!(MaskingMode::Email.apply("j@example.com"), "j*@example.com");
+        assert_eq!(MaskingMode::Email.apply("not an email"), REDACTED);
+
+        assert_eq!(MaskingMode::Year.apply("1980-05-12"), "1980");
+        assert_eq!(MaskingMode::Year.apply("2024-01-02T03:04:05Z"), "2024");
+        assert_eq!(MaskingMode::Year.apply("05/12/1980"), REDACTED);
+    }
+
+    #[test]
+    fn test_decrypt_and_mask_fields() {
+        let key = [0u8; 32];
+        let record = json!({
+            "ssn": "123-45-6789",
+            "email": "john.doe@example.com",
+            "visit": {"date": "2024-03-01"},
+            "notes": "private"
+        });
+        let masks: FieldMasks = serde_json::from_value(json!({
+            "ssn": {"keep_last": 4},
+            "email": "email",
+            "visit.date": "year",
+            "notes": "redact"
+        }))
+        .unwrap();
+        let fields: Vec<String> = masks.keys().cloned().collect();
+        let encrypted = encryption::encrypt_fields(&record, &fields, &key).unwrap();
+
+        let expected = json!({
+            "ssn": "***-**-6789",
+            "email": "j*******@example.com",
+            "visit": {"date": "2024"},
+            "notes": REDACTED
+        });
+        assert_eq!(
+            decrypt_and_mask_fields(&encrypted, &masks, &key).unwrap(),
+            expected
+        );
+        assert_eq!(mask_fields(&record, &masks).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_mask_value() {
+        assert_eq!(
+            mask_value(&json!(["a", "b"]), &MaskingMode::Redact),
+            json!([REDACTED, REDACTED])
+        );
+        assert_eq!(mask_value(&Value::Null, &MaskingMode::Redact), Value::Null);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod deidentify;
+pub mod masking;
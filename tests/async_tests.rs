@@ -0,0 +1,65 @@
+#![cfg(feature = "async")]
+
+use polycrypt_rs::asynchronous;
+use polycrypt_rs::crypto::encryption;
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_async_encrypt_decrypt_fields_in_batch() {
+    let key = [0u8; 32];
+    let records = vec![
+        json!({"id": "1234", "name": "John Doe"}),
+        json!({"id": "5678", "name": "Jane Smith"}),
+    ];
+    let fields = vec!["name".to_string()];
+
+    let encrypted = asynchronous::encrypt_fields_in_batch(records.clone(), fields.clone(), &key)
+        .await
+        .unwrap();
+    assert_ne!(encrypted[0]["name"], records[0]["name"]);
+
+    let decrypted = asynchronous::decrypt_fields_in_batch(encrypted, fields, &key)
+        .await
+        .unwrap();
+    assert_eq!(decrypted, records);
+
+    let ciphertext = asynchronous::encrypt(b"Hello".to_vec(), &key)
+        .await
+        .unwrap();
+    assert_eq!(
+        asynchronous::decrypt(ciphertext, &key).await.unwrap(),
+        b"Hello"
+    );
+    assert!(asynchronous::decrypt(vec![0u8; 8], &key).await.is_err());
+}
+
+#[tokio::test]
+async fn test_encrypt_decrypt_stream() {
+    let key = [7u8; 32];
+    // Lengths around the chunk and block boundaries.
+    for len in [0, 1, 16, 65_535, 65_536, 65_552, 200_000] {
+        let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+
+        let mut ciphertext = Vec::new();
+        let written =
+            asynchronous::encrypt_stream(&mut plaintext.as_slice(), &mut ciphertext, &key)
+                .await
+                .unwrap();
+        assert_eq!(written, ciphertext.len() as u64);
+        assert_eq!(ciphertext.len(), encryption::ciphertext_len(len));
+        // Streams use the same format as the one-shot functions.
+        assert_eq!(encryption::decrypt(&ciphertext, &key).unwrap(), plaintext);
+
+        let one_shot = encryption::encrypt(&plaintext, &key).unwrap();
+        let mut decrypted = Vec::new();
+        let written = asynchronous::decrypt_stream(&mut one_shot.as_slice(), &mut decrypted, &key)
+            .await
+            .unwrap();
+        assert_eq!(written, len as u64);
+        assert_eq!(decrypted, plaintext);
+    }
+
+    let mut truncated = &encryption::encrypt(b"Hello", &key).unwrap()[..20];
+    let result = asynchronous::decrypt_stream(&mut truncated, &mut Vec::new(), &key).await;
+    assert!(result.is_err());
+}
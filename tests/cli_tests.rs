@@ -0,0 +1,174 @@
+#![cfg(feature = "cli")]
+
+use polycrypt_rs::crypto::encryption;
+use polycrypt_rs::crypto::keyring::decode_key;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use tempfile::tempdir;
+
+fn polycrypt(args: &[&str], key_file: Option<&Path>, stdin: &[u8]) -> Output {
+    let output = run_polycrypt(args, key_file, stdin);
+    assert!(
+        output.status.success(),
+        "polycrypt {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn run_polycrypt(args: &[&str], key_file: Option<&Path>, stdin: &[u8]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_polycrypt"));
+    command
+        .args(args)
+        .env_remove("POLYCRYPT_KEY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(key_file) = key_file {
+        command.arg("--key-file").arg(key_file);
+    }
+    let mut child = command.spawn().unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn ndjson(bytes: &[u8]) -> Vec<Value> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_cli_encrypt_decrypt_and_inspect() {
+    let dir = tempdir().unwrap();
+    let key_file = dir.path().join("key");
+    polycrypt(&["keygen", "--out", key_file.to_str().unwrap()], None, b"");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&key_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let encrypted = polycrypt(
+        &["encrypt", "--key-id", "ops-1"],
+        Some(&key_file),
+        b"Hello, world!",
+    )
+    .stdout;
+    let decrypted = polycrypt(&["decrypt"], Some(&key_file), &encrypted).stdout;
+    assert_eq!(decrypted, b"Hello, world!");
+
+    let inspected: Value =
+        serde_json::from_slice(&polycrypt(&["inspect"], None, &encrypted).stdout).unwrap();
+    assert_eq!(inspected["format"], "envelope");
+    assert_eq!(inspected["key_id"], "ops-1");
+}
+
+#[test]
+fn test_cli_fields_ndjson_and_rotate() {
+    let dir = tempdir().unwrap();
+    let old_key = dir.path().join("old");
+    let new_key = dir.path().join("new");
+    polycrypt(&["keygen", "--out", old_key.to_str().unwrap()], None, b"");
+    polycrypt(&["keygen", "--out", new_key.to_str().unwrap()], None, b"");
+
+    let records = "{\"id\":\"1\",\"name\":\"John Doe\",\"address\":{\"zip\":\"12345\"}}\n\n{\"id\":\"2\",\"name\":\"Jane Doe\"}\n";
+    let fields = ["--fields", "name,address.zip", "--ndjson"];
+
+    let encrypted = polycrypt(
+        &[&["encrypt-fields"][..], &fields].concat(),
+        Some(&old_key),
+        records.as_bytes(),
+    )
+    .stdout;
+    let encrypted_lines = ndjson(&encrypted);
+    assert_eq!(encrypted_lines.len(), 2);
+    assert_ne!(encrypted_lines[0]["name"], "John Doe");
+
+    let name = encrypted_lines[0]["name"].as_str().unwrap();
+    let inspected: Value =
+        serde_json::from_slice(&polycrypt(&["inspect", name], None, b"").stdout).unwrap();
+    assert_eq!(inspected["format"], "envelope");
+
+    let rotate = [
+        "rotate",
+        "--old-key-file",
+        old_key.to_str().unwrap(),
+        "--new-key-file",
+        new_key.to_str().unwrap(),
+    ];
+    let rotated = polycrypt(&[&rotate[..], &fields].concat(), None, &encrypted).stdout;
+    let decrypted = polycrypt(
+        &[&["decrypt-fields"][..], &fields].concat(),
+        Some(&new_key),
+        &rotated,
+    )
+    .stdout;
+    assert_eq!(ndjson(&decrypted), ndjson(records.as_bytes()));
+
+    // A single JSON document may hold an array of records.
+    let array = serde_json::to_vec(&json!([{"name": "John Doe"}])).unwrap();
+    let encrypted = polycrypt(
+        &["encrypt-fields", "--fields", "name"],
+        Some(&old_key),
+        &array,
+    )
+    .stdout;
+    let decrypted: Value = serde_json::from_slice(
+        &polycrypt(
+            &["decrypt-fields", "--fields", "name"],
+            Some(&old_key),
+            &encrypted,
+        )
+        .stdout,
+    )
+    .unwrap();
+    assert_eq!(decrypted, json!([{"name": "John Doe"}]));
+}
+
+#[test]
+fn test_cli_rotate_legacy_values() {
+    let dir = tempdir().unwrap();
+    let old_key = dir.path().join("old");
+    let new_key = dir.path().join("new");
+    polycrypt(&["keygen", "--out", old_key.to_str().unwrap()], None, b"");
+    polycrypt(&["keygen", "--out", new_key.to_str().unwrap()], None, b"");
+
+    // A value written before field values carried an envelope.
+    let key = decode_key(&std::fs::read_to_string(&old_key).unwrap()).unwrap();
+    let legacy = base64::encode(encryption::encrypt(b"John Doe", &key).unwrap());
+    let record = serde_json::to_vec(&json!({"name": legacy})).unwrap();
+
+    let rotate = [
+        "rotate",
+        "--old-key-file",
+        old_key.to_str().unwrap(),
+        "--new-key-file",
+        new_key.to_str().unwrap(),
+        "--fields",
+        "name",
+    ];
+    let rotated = polycrypt(&rotate, None, &record).stdout;
+    let decrypted: Value = serde_json::from_slice(
+        &polycrypt(
+            &["decrypt-fields", "--fields", "name"],
+            Some(&new_key),
+            &rotated,
+        )
+        .stdout,
+    )
+    .unwrap();
+    assert_eq!(decrypted, json!({"name": "John Doe"}));
+
+    // Values the old key does not decrypt fail the rotation instead of being sealed as is.
+    let plaintext = serde_json::to_vec(&json!({"name": "John Doe"})).unwrap();
+    assert!(!run_polycrypt(&rotate, None, &plaintext).status.success());
+    let other = polycrypt(&rotate, None, &record).stdout;
+    assert!(!run_polycrypt(&rotate, None, &other).status.success());
+}
@@ -0,0 +1,122 @@
+use polycrypt_rs::crypto::encrypted::{self, Encrypted};
+use polycrypt_rs::crypto::encryption;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Patient {
+    id: String,
+    name: Encrypted<String>,
+    #[serde(with = "polycrypt_rs::crypto::encrypted")]
+    phones: Vec<String>,
+}
+
+fn fields() -> Vec<String> {
+    vec!["name".to_string(), "phones".to_string()]
+}
+
+#[test]
+fn test_encrypted_matches_encrypt_fields_format() {
+    let key = [0u8; 32];
+    let patient = Patient {
+        id: "1234".to_string(),
+        name: Encrypted::new("John Doe".to_string()),
+        phones: vec!["555-0100".to_string(), "555-0101".to_string()],
+    };
+
+    let encrypted = encrypted::with_key(&key, || serde_json::to_value(&patient)).unwrap();
+    assert_eq!(encrypted["id"], "1234");
+    assert_ne!(encrypted["name"], "John Doe");
+    assert_eq!(encrypted["phones"].as_array().unwrap().len(), 2);
+
+    // Typed output decrypts with decrypt_fields, and encrypt_fields output deserializes.
+    let decrypted = encryption::decrypt_fields(&encrypted, &fields(), &key).unwrap();
+    assert_eq!(
+        decrypted,
+        json!({"id": "1234", "name": "John Doe", "phones": ["555-0100", "555-0101"]})
+    );
+    let from_fields = encryption::encrypt_fields(&decrypted, &fields(), &key).unwrap();
+    let roundtrip: Patient =
+        encrypted::with_key(&key, || serde_json::from_value(from_fields)).unwrap();
+    assert_eq!(roundtrip, patient);
+
+    // Unlike decrypt_fields, plaintext values are rejected.
+    let plain = encrypted::with_key(&key, || serde_json::from_value::<Patient>(decrypted));
+    assert!(plain.is_err());
+}
+
+#[test]
+fn test_encrypted_option() {
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Contact {
+        #[serde(with = "polycrypt_rs::crypto::encrypted")]
+        email: Option<String>,
+    }
+
+    let key = [0u8; 32];
+    for contact in [
+        Contact { email: None },
+        Contact {
+            email: Some("john@example.com".to_string()),
+        },
+    ] {
+        let encrypted = encrypted::with_key(&key, || serde_json::to_value(&contact)).unwrap();
+        assert_ne!(encrypted["email"], "john@example.com");
+        assert_eq!(encrypted["email"].is_null(), contact.email.is_none());
+        let roundtrip: Contact =
+            encrypted::with_key(&key, || serde_json::from_value(encrypted)).unwrap();
+        assert_eq!(roundtrip, contact);
+    }
+}
+
+#[test]
+fn test_encrypted_requires_key() {
+    let value = Encrypted::new("John Doe".to_string());
+    let error = serde_json::to_string(&value).unwrap_err();
+    assert!(error.to_string().contains("No encryption key in scope"));
+
+    let key = [0u8; 32];
+    let ciphertext = encrypted::with_key(&key, || serde_json::to_string(&value)).unwrap();
+    assert!(serde_json::from_str::<Encrypted<String>>(&ciphertext).is_err());
+    assert!(
+        encrypted::with_key(&[1u8; 32], || serde_json::from_str::<Encrypted<String>>(
+            &ciphertext
+        ))
+        .is_err()
+    );
+
+    let number = Encrypted::new(42);
+    assert!(encrypted::with_key(&key, || serde_json::to_string(&number)).is_err());
+    assert_eq!(format!("{:?}", value), "Encrypted([REDACTED])");
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_polycrypt_attribute() {
+    #[polycrypt_rs::polycrypt]
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Visit {
+        id: String,
+        #[polycrypt(encrypt)]
+        #[serde(rename = "diagnosis_code")]
+        diagnosis: String,
+        #[polycrypt(encrypt)]
+        notes: Vec<String>,
+    }
+
+    let key = [0u8; 32];
+    let visit = Visit {
+        id: "v1".to_string(),
+        diagnosis: "E11.9".to_string(),
+        notes: vec!["Follow up in 3 months".to_string()],
+    };
+    let encrypted = encrypted::with_key(&key, || serde_json::to_value(&visit)).unwrap();
+    assert_ne!(encrypted["diagnosis_code"], "E11.9");
+
+    let fields = vec!["diagnosis_code".to_string(), "notes".to_string()];
+    let decrypted = encryption::decrypt_fields(&encrypted, &fields, &key).unwrap();
+    assert_eq!(decrypted["diagnosis_code"], "E11.9");
+
+    let roundtrip: Visit = encrypted::with_key(&key, || serde_json::from_value(encrypted)).unwrap();
+    assert_eq!(roundtrip, visit);
+}
@@ -8,12 +8,17 @@ fn test_ffi_encrypt_decrypt() {
     let plaintext = b"Hello, world!";
     let key = [0u8; 32];
 
-    let encrypted = ffi::encrypt(plaintext.as_ptr(), plaintext.len(), key.as_ptr());
+    let encrypted = ffi::encrypt(plaintext.as_ptr(), plaintext.len(), key.as_ptr(), key.len());
     assert_eq!(encrypted.error_code, 0);
     assert_ne!(encrypted.data.len, 0);
     assert_ne!(encrypted.data.data, std::ptr::null_mut());
 
-    let decrypted = ffi::decrypt(encrypted.data.data, encrypted.data.len, key.as_ptr());
+    let decrypted = ffi::decrypt(
+        encrypted.data.data,
+        encrypted.data.len,
+        key.as_ptr(),
+        key.len(),
+    );
     assert_eq!(decrypted.error_code, 0);
     assert_ne!(decrypted.data.len, 0);
     assert_ne!(decrypted.data.data, std::ptr::null_mut());
@@ -26,6 +31,51 @@ fn test_ffi_encrypt_decrypt() {
     ffi::free_ffi_result(decrypted);
 }
 
+#[test]
+fn test_ffi_encrypt_decrypt_into() {
+    let plaintext = b"Hello, world!";
+    let key = [0u8; 32];
+
+    let mut ciphertext = vec![0u8; ffi::ciphertext_len(plaintext.len())];
+    let mut written = 0;
+    let code = ffi::encrypt_into(
+        plaintext.as_ptr(),
+        plaintext.len(),
+        key.as_ptr(),
+        key.len(),
+        ciphertext.as_mut_ptr(),
+        ciphertext.len(),
+        &mut written,
+    );
+    assert_eq!(code, 0);
+    assert_eq!(written, ciphertext.len());
+
+    let mut decrypted = vec![0u8; ciphertext.len()];
+    let code = ffi::decrypt_into(
+        ciphertext.as_ptr(),
+        ciphertext.len(),
+        key.as_ptr(),
+        key.len(),
+        decrypted.as_mut_ptr(),
+        decrypted.len(),
+        &mut written,
+    );
+    assert_eq!(code, 0);
+    assert_eq!(&decrypted[..written], plaintext);
+
+    let mut small = [0u8; 16];
+    let code = ffi::encrypt_into(
+        plaintext.as_ptr(),
+        plaintext.len(),
+        key.as_ptr(),
+        key.len(),
+        small.as_mut_ptr(),
+        small.len(),
+        &mut written,
+    );
+    assert_eq!(code, -1);
+}
+
 #[test]
 fn test_ffi_encrypt_decrypt_fields() {
     let key = [0u8; 32];
@@ -39,6 +89,7 @@ fn test_ffi_encrypt_decrypt_fields() {
         record_cstring.as_ptr(),
         fields_cstring.as_ptr(),
         key.as_ptr(),
+        key.len(),
     );
     assert_eq!(encrypted.error_code, 0);
     assert_ne!(encrypted.data.len, 0);
@@ -49,6 +100,7 @@ fn test_ffi_encrypt_decrypt_fields() {
         encrypted.data.len,
         fields_cstring.as_ptr(),
         key.as_ptr(),
+        key.len(),
     );
     assert_eq!(decrypted.error_code, 0);
     assert_ne!(decrypted.data.len, 0);
@@ -70,6 +122,157 @@ fn test_ffi_encrypt_decrypt_fields() {
     ffi::free_ffi_result(decrypted);
 }
 
+#[test]
+fn test_ffi_encrypt_decrypt_fields_bytes() {
+    let key = [0u8; 32];
+    // Neither buffer is NUL-terminated, and the record holds an escaped NUL.
+    let record = br#"{"id":"1234","name":"John\u0000Doe"}trailing"#;
+    let record = &record[..record.len() - "trailing".len()];
+    let fields = br#"["name"]"#;
+
+    let encrypted = ffi::encrypt_fields_bytes(
+        record.as_ptr(),
+        record.len(),
+        fields.as_ptr(),
+        fields.len(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(encrypted.error_code, ffi::ERROR_OK);
+
+    let decrypted = ffi::decrypt_fields_bytes(
+        encrypted.data.data,
+        encrypted.data.len,
+        fields.as_ptr(),
+        fields.len(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(decrypted.error_code, ffi::ERROR_OK);
+    let decrypted_json: Value = serde_json::from_slice(unsafe {
+        std::slice::from_raw_parts(decrypted.data.data, decrypted.data.len)
+    })
+    .unwrap();
+    assert_eq!(decrypted_json["name"], "John\u{0}Doe");
+
+    ffi::free_ffi_result(encrypted);
+    ffi::free_ffi_result(decrypted);
+}
+
+#[test]
+fn test_ffi_invalid_input_error_codes() {
+    let key = [0u8; 32];
+    let fields = br#"["name"]"#;
+
+    let invalid_utf8 = b"{\"name\":\"\xff\"}";
+    let result = ffi::encrypt_fields_bytes(
+        invalid_utf8.as_ptr(),
+        invalid_utf8.len(),
+        fields.as_ptr(),
+        fields.len(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(result.error_code, ffi::ERROR_INVALID_UTF8);
+    assert!(result.data.data.is_null());
+
+    let invalid_json = CString::new("{not json").unwrap();
+    let fields_cstring = CString::new(&fields[..]).unwrap();
+    let result = ffi::encrypt_fields(
+        invalid_json.as_ptr(),
+        fields_cstring.as_ptr(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(result.error_code, ffi::ERROR_INVALID_JSON);
+
+    // Valid JSON of the wrong shape is rejected the same way.
+    let records = CString::new(r#"{"id":"1234"}"#).unwrap();
+    let result = ffi::encrypt_fields_in_batch(
+        records.as_ptr(),
+        fields_cstring.as_ptr(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(result.error_code, ffi::ERROR_INVALID_JSON);
+
+    let config = b"{\"level\":";
+    let result = ffi::init_logger_bytes(config.as_ptr(), config.len());
+    assert_eq!(result.error_code, ffi::ERROR_INVALID_JSON);
+
+    let not_encrypted = br#"{"name":"John Doe"}"#;
+    let bad_path = br#"["a..b"]"#;
+    let result = ffi::decrypt_fields_bytes(
+        not_encrypted.as_ptr(),
+        not_encrypted.len(),
+        bad_path.as_ptr(),
+        bad_path.len(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(result.error_code, ffi::ERROR_FAILED);
+}
+
+#[test]
+fn test_ffi_null_pointers_and_lengths() {
+    let key = [0u8; 32];
+    let null = std::ptr::null();
+
+    // Empty input may be passed as a null pointer and still round-trips.
+    let encrypted = ffi::encrypt(null, 0, key.as_ptr(), key.len());
+    assert_eq!(encrypted.error_code, ffi::ERROR_OK);
+    assert_eq!(encrypted.data.len, ffi::ciphertext_len(0));
+    let decrypted = ffi::decrypt(
+        encrypted.data.data,
+        encrypted.data.len,
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(decrypted.error_code, ffi::ERROR_OK);
+    assert_eq!(decrypted.data.len, 0);
+    ffi::free_ffi_result(encrypted);
+    ffi::free_ffi_result(decrypted);
+
+    let result = ffi::encrypt(null, 5, key.as_ptr(), key.len());
+    assert_eq!(result.error_code, ffi::ERROR_NULL_POINTER);
+    let result = ffi::encrypt(b"Hello".as_ptr(), 5, null, 32);
+    assert_eq!(result.error_code, ffi::ERROR_NULL_POINTER);
+    let result = ffi::encrypt(b"Hello".as_ptr(), 5, key.as_ptr(), 16);
+    assert_eq!(result.error_code, ffi::ERROR_INVALID_KEY);
+
+    let fields = CString::new(r#"["name"]"#).unwrap();
+    let result = ffi::encrypt_fields(std::ptr::null(), fields.as_ptr(), key.as_ptr(), key.len());
+    assert_eq!(result.error_code, ffi::ERROR_NULL_POINTER);
+    let result = ffi::mask_fields(std::ptr::null(), std::ptr::null());
+    assert_eq!(result.error_code, ffi::ERROR_NULL_POINTER);
+    let result =
+        ffi::pseudonymize_fields_bytes(null, 0, null, 0, null, 0, key.as_ptr(), key.len() - 1);
+    assert_eq!(result.error_code, ffi::ERROR_INVALID_KEY);
+
+    let mut out = [0u8; 64];
+    let code = ffi::encrypt_into(
+        b"Hello".as_ptr(),
+        5,
+        key.as_ptr(),
+        key.len(),
+        out.as_mut_ptr(),
+        out.len(),
+        std::ptr::null_mut(),
+    );
+    assert_eq!(code, ffi::ERROR_NULL_POINTER);
+    let mut written = 0;
+    let code = ffi::encrypt_into(
+        b"Hello".as_ptr(),
+        5,
+        key.as_ptr(),
+        key.len(),
+        std::ptr::null_mut(),
+        0,
+        &mut written,
+    );
+    assert_eq!(code, ffi::ERROR_FAILED);
+}
+
 #[test]
 fn test_ffi_encrypt_decrypt_fields_in_batch() {
     let key = [0u8; 32];
@@ -86,6 +289,7 @@ fn test_ffi_encrypt_decrypt_fields_in_batch() {
         records_cstring.as_ptr(),
         fields_cstring.as_ptr(),
         key.as_ptr(),
+        key.len(),
     );
     assert_eq!(encrypted.error_code, 0);
     assert_ne!(encrypted.data.len, 0);
@@ -96,6 +300,7 @@ fn test_ffi_encrypt_decrypt_fields_in_batch() {
         encrypted.data.len,
         fields_cstring.as_ptr(),
         key.as_ptr(),
+        key.len(),
     );
     assert_eq!(decrypted.error_code, 0);
     assert_ne!(decrypted.data.len, 0);
@@ -116,3 +321,286 @@ fn test_ffi_encrypt_decrypt_fields_in_batch() {
     ffi::free_ffi_result(encrypted);
     ffi::free_ffi_result(decrypted);
 }
+
+#[test]
+fn test_ffi_phi_fields_from_schema() {
+    let schema = r#"{"properties": {"id": {}, "name": {"x-phi": true}, "contacts": {"items": {"properties": {"phone": {"x-phi": true}}}}}}"#;
+    let schema_cstring = CString::new(schema).unwrap();
+
+    let result = ffi::phi_fields_from_schema(schema_cstring.as_ptr());
+    assert_eq!(result.error_code, 0);
+
+    let fields: Vec<String> = serde_json::from_slice(unsafe {
+        std::slice::from_raw_parts(result.data.data, result.data.len)
+    })
+    .unwrap();
+    assert_eq!(fields, ["contacts[].phone", "name"]);
+
+    ffi::free_ffi_result(result);
+}
+
+#[test]
+fn test_ffi_decrypt_and_mask_fields() {
+    let key = [0u8; 32];
+    let record = r#"{"id":"1234","ssn":"123-45-6789","dob":"1980-05-12"}"#;
+    let fields = r#"["ssn","dob"]"#;
+    let masks = r#"{"ssn":{"keep_last":4},"dob":"year"}"#;
+
+    let record_cstring = CString::new(record).unwrap();
+    let fields_cstring = CString::new(fields).unwrap();
+    let masks_cstring = CString::new(masks).unwrap();
+
+    let encrypted = ffi::encrypt_fields(
+        record_cstring.as_ptr(),
+        fields_cstring.as_ptr(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(encrypted.error_code, 0);
+
+    let masked = ffi::decrypt_and_mask_fields(
+        encrypted.data.data,
+        encrypted.data.len,
+        masks_cstring.as_ptr(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(masked.error_code, 0);
+
+    let masked_json: Value = serde_json::from_slice(unsafe {
+        std::slice::from_raw_parts(masked.data.data, masked.data.len)
+    })
+    .unwrap();
+    let expected = serde_json::json!({"id": "1234", "ssn": "***-**-6789", "dob": "1980"});
+    assert_eq!(masked_json, expected);
+
+    let plain_masked = ffi::mask_fields(record_cstring.as_ptr(), masks_cstring.as_ptr());
+    assert_eq!(plain_masked.error_code, 0);
+    let plain_masked_json: Value = serde_json::from_slice(unsafe {
+        std::slice::from_raw_parts(plain_masked.data.data, plain_masked.data.len)
+    })
+    .unwrap();
+    assert_eq!(plain_masked_json, expected);
+
+    ffi::free_ffi_result(encrypted);
+    ffi::free_ffi_result(masked);
+    ffi::free_ffi_result(plain_masked);
+}
+
+#[test]
+fn test_ffi_pseudonymize_fields() {
+    let key = [0u8; 32];
+    let fields = CString::new(r#"["patient_id"]"#).unwrap();
+    let domain = CString::new("claims").unwrap();
+
+    let mut pseudonyms = Vec::new();
+    for record in [
+        r#"{"patient_id":"1234","n":1}"#,
+        r#"{"patient_id":1234,"n":2}"#,
+    ] {
+        let record_cstring = CString::new(record).unwrap();
+        let result = ffi::pseudonymize_fields(
+            record_cstring.as_ptr(),
+            fields.as_ptr(),
+            domain.as_ptr(),
+            key.as_ptr(),
+            key.len(),
+        );
+        assert_eq!(result.error_code, 0);
+        let pseudonymized: Value = serde_json::from_slice(unsafe {
+            std::slice::from_raw_parts(result.data.data, result.data.len)
+        })
+        .unwrap();
+        pseudonyms.push(pseudonymized["patient_id"].clone());
+        ffi::free_ffi_result(result);
+    }
+
+    assert_eq!(pseudonyms[0], pseudonyms[1]);
+    assert_ne!(pseudonyms[0], "1234");
+}
+
+#[test]
+fn test_ffi_inspect_ciphertext() {
+    let key = [0u8; 32];
+    let plaintext = b"Hello, world!";
+    let encrypted = ffi::encrypt(plaintext.as_ptr(), plaintext.len(), key.as_ptr(), key.len());
+    assert_eq!(encrypted.error_code, 0);
+
+    let result = ffi::inspect_ciphertext(encrypted.data.data, encrypted.data.len);
+    assert_eq!(result.error_code, 0);
+    let info: Value = serde_json::from_slice(unsafe {
+        std::slice::from_raw_parts(result.data.data, result.data.len)
+    })
+    .unwrap();
+    assert_eq!(info["format"], "legacy");
+    assert_eq!(info["algorithm"], "aes-256-cbc");
+    assert_eq!(info["nonce_len"], 16);
+    assert_eq!(info["payload_len"], 16);
+
+    let garbage = b"not a ciphertext";
+    let failed = ffi::inspect_ciphertext(garbage.as_ptr(), garbage.len());
+    assert_ne!(failed.error_code, 0);
+
+    ffi::free_ffi_result(encrypted);
+    ffi::free_ffi_result(result);
+    ffi::free_ffi_result(failed);
+}
+
+#[test]
+fn test_ffi_init_logger() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = dir.path().join("first.log");
+    let second = dir.path().join("second.log");
+    let key = [0u8; 32];
+    let record = CString::new(r#"{"name":"John Doe"}"#).unwrap();
+    let fields = CString::new(r#"["name"]"#).unwrap();
+    let encrypt = || {
+        let result = ffi::encrypt_fields(record.as_ptr(), fields.as_ptr(), key.as_ptr(), key.len());
+        assert_eq!(result.error_code, 0);
+        ffi::free_ffi_result(result);
+    };
+
+    let config = serde_json::json!({
+        "level": "info",
+        "destination": {"file": first},
+        "context": {"service": "billing"},
+    });
+    let config = CString::new(config.to_string()).unwrap();
+    let result = ffi::init_logger(config.as_ptr());
+    assert_eq!(result.error_code, 0);
+    ffi::free_ffi_result(result);
+    encrypt();
+
+    let entries: Vec<Value> = std::fs::read_to_string(&first)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let completed = entries
+        .iter()
+        .find(|entry| entry["message"] == "Field encryption completed")
+        .unwrap();
+    assert_eq!(completed["context"]["operation"], "encrypt_fields");
+    assert_eq!(completed["context"]["service"], "billing");
+
+    // Reconfiguring redirects and filters subsequent entries.
+    let config =
+        serde_json::json!({"level": "error", "format": "text", "destination": {"file": second}});
+    let config = CString::new(config.to_string()).unwrap();
+    let result = ffi::init_logger(config.as_ptr());
+    assert_eq!(result.error_code, 0);
+    ffi::free_ffi_result(result);
+    encrypt();
+    let contents = std::fs::read_to_string(&second).unwrap();
+    assert!(!contents.contains("Field encryption completed"));
+
+    let invalid = CString::new(r#"{"level": "loud"}"#).unwrap();
+    let result = ffi::init_logger(invalid.as_ptr());
+    assert_eq!(result.error_code, -1);
+}
+
+extern "C" fn collect_log_entry(
+    level: *const std::os::raw::c_char,
+    message: *const std::os::raw::c_char,
+    context: *const std::os::raw::c_char,
+    user_data: *mut std::os::raw::c_void,
+) {
+    let entries = unsafe { &*(user_data as *const std::sync::Mutex<Vec<(String, Value)>>) };
+    let text = |s| unsafe { std::ffi::CStr::from_ptr(s).to_str().unwrap().to_string() };
+    if ["Field decryption completed", "FFI call failed"].contains(&text(message).as_str()) {
+        let context = serde_json::from_str(&text(context)).unwrap();
+        entries.lock().unwrap().push((text(level), context));
+    }
+}
+
+#[test]
+fn test_ffi_log_callback() {
+    let entries = std::sync::Mutex::new(Vec::<(String, Value)>::new());
+    ffi::set_log_callback(
+        Some(collect_log_entry),
+        &entries as *const _ as *mut std::os::raw::c_void,
+    );
+
+    let key = [0u8; 32];
+    let fields = CString::new(r#"["name"]"#).unwrap();
+    let encrypted = ffi::encrypt_fields(
+        CString::new(r#"{"name":"John Doe"}"#).unwrap().as_ptr(),
+        fields.as_ptr(),
+        key.as_ptr(),
+        key.len(),
+    );
+    let decrypted = ffi::decrypt_fields(
+        encrypted.data.data,
+        encrypted.data.len,
+        fields.as_ptr(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(decrypted.error_code, 0);
+    // Failed calls reach the callback too.
+    let truncated = [0u8; 15];
+    let failed = ffi::decrypt(truncated.as_ptr(), truncated.len(), key.as_ptr(), key.len());
+    assert_eq!(failed.error_code, ffi::ERROR_FAILED);
+    ffi::set_log_callback(None, std::ptr::null_mut());
+    ffi::free_ffi_result(encrypted);
+    ffi::free_ffi_result(decrypted);
+    ffi::free_ffi_result(failed);
+
+    // Other tests may log concurrently, so look for this test's entries.
+    let entries = entries.lock().unwrap();
+    assert!(entries
+        .iter()
+        .any(|(level, context)| level == "INFO" && context["operation"] == "decrypt_fields"));
+    assert!(entries.iter().any(|(level, context)| {
+        level == "ERROR"
+            && context["operation"] == "ffi.decrypt"
+            && context["additional_context"]["error_code"] == ffi::ERROR_FAILED
+    }));
+}
+
+#[test]
+fn test_ffi_metrics_snapshot() {
+    let key = [0u8; 32];
+    let encrypted = ffi::encrypt(b"Hello".as_ptr(), 5, key.as_ptr(), key.len());
+    // Shorter than an IV, so decryption fails on every run.
+    let truncated = [0u8; 15];
+    let failed = ffi::decrypt(truncated.as_ptr(), truncated.len(), key.as_ptr(), key.len());
+    assert_ne!(failed.error_code, 0);
+    ffi::free_ffi_result(encrypted);
+    ffi::free_ffi_result(failed);
+    let fields = CString::new(r#"["metrics_name"]"#).unwrap();
+    let encrypted = ffi::encrypt_fields(
+        CString::new(r#"{"metrics_name":"John Doe"}"#)
+            .unwrap()
+            .as_ptr(),
+        fields.as_ptr(),
+        key.as_ptr(),
+        key.len(),
+    );
+    assert_eq!(encrypted.error_code, 0);
+    ffi::free_ffi_result(encrypted);
+
+    let result = ffi::metrics_snapshot();
+    assert_eq!(result.error_code, 0);
+    let snapshot: Vec<Value> = serde_json::from_slice(unsafe {
+        std::slice::from_raw_parts(result.data.data, result.data.len)
+    })
+    .unwrap();
+    ffi::free_ffi_result(result);
+
+    let find = |operation: &str| {
+        snapshot
+            .iter()
+            .find(|m| m["operation"] == operation)
+            .unwrap()
+    };
+    assert!(find("ffi.encrypt")["calls"].as_u64().unwrap() >= 1);
+    assert!(find("ffi.decrypt")["errors"].as_u64().unwrap() >= 1);
+    assert!(find("decrypt")["errors"].as_u64().unwrap() >= 1);
+    assert!(find("encrypt")["buckets"].as_array().unwrap().len() > 1);
+    let field = snapshot
+        .iter()
+        .find(|m| m["operation"] == "encrypt_field" && m["field"] == "metrics_name")
+        .unwrap();
+    assert!(field["calls"].as_u64().unwrap() >= 1);
+}
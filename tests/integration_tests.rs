@@ -1,5 +1,6 @@
-use polycrypt_rs::crypto::encryption;
-use polycrypt_rs::PolyCryptError;
+use polycrypt_rs::audit::{self, AuditEntry};
+use polycrypt_rs::crypto::{encryption, schema};
+use polycrypt_rs::{AuditContext, AuditLog, EncryptionPolicy, Keyring, PolyCrypt, PolyCryptError};
 use serde_json::json;
 
 #[test]
@@ -72,3 +73,126 @@ fn test_encryption_error_handling() {
     let result = encrypt_wrapper(plaintext, &key);
     assert!(result.is_ok());
 }
+
+#[test]
+fn test_policy_record_encryption() {
+    let policy = EncryptionPolicy::from_json(
+        r#"{"record_types": {"patient": {"fields": [
+            {"path": "name", "key_id": "phi"},
+            {"path": "dob", "key_id": "phi", "mask": "redact"}
+        ]}}}"#,
+    )
+    .unwrap();
+    let mut keyring = Keyring::new();
+    keyring.insert("phi", [7u8; 32]);
+
+    let mut polycrypt = PolyCrypt::new(json!({"service": "integration-tests"}));
+    assert!(polycrypt.encrypt_record("patient", &json!({})).is_err());
+    assert!(polycrypt
+        .load_policy(policy.clone(), Keyring::new())
+        .is_err());
+    polycrypt.load_policy(policy, keyring).unwrap();
+
+    let record = json!({"id": "1", "name": "John Doe", "dob": "1980-01-01"});
+    let encrypted = polycrypt.encrypt_record("patient", &record).unwrap();
+    assert_ne!(encrypted["name"], record["name"]);
+    assert_eq!(encrypted["id"], record["id"]);
+
+    let decrypted = polycrypt.decrypt_record("patient", &encrypted).unwrap();
+    assert_eq!(decrypted, record);
+    assert_eq!(
+        polycrypt.mask_record("patient", &decrypted).unwrap()["dob"],
+        json!("[REDACTED]")
+    );
+    assert!(polycrypt.encrypt_record("visit", &record).is_err());
+}
+
+#[test]
+fn test_audited_record_decryption() {
+    let policy = EncryptionPolicy::from_json(
+        r#"{"record_types": {"patient": {"fields": [
+            {"path": "name", "key_id": "phi"},
+            {"path": "ssn", "key_id": "ssn"}
+        ]}}}"#,
+    )
+    .unwrap();
+    let mut keyring = Keyring::new();
+    keyring.insert("phi", [7u8; 32]);
+    keyring.insert("ssn", [8u8; 32]);
+
+    let mut polycrypt = PolyCrypt::new(json!({"service": "integration-tests"}));
+    polycrypt.load_policy(policy, keyring).unwrap();
+    let context = AuditContext::new("dr.smith", "treatment");
+    let record = json!({"id": "1", "name": "John Doe", "ssn": "123-45-6789"});
+    let encrypted = polycrypt.encrypt_record("patient", &record).unwrap();
+    assert!(matches!(
+        polycrypt.decrypt_record_audited("patient", &encrypted, &context),
+        Err(PolyCryptError::AuditError(_))
+    ));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.log");
+    polycrypt.set_audit_log(AuditLog::open_file(&path).unwrap());
+    let decrypted = polycrypt
+        .decrypt_record_audited("patient", &encrypted, &context)
+        .unwrap();
+    assert_eq!(decrypted, record);
+
+    assert_eq!(audit::verify_file(&path).unwrap().unwrap().seq, 1);
+    let entries: Vec<AuditEntry> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries[0].operation, "decrypt_record:patient");
+    assert_eq!(entries[0].key_id.as_deref(), Some("phi"));
+    assert_eq!(entries[0].fields, ["name"]);
+    assert_eq!(entries[1].key_id.as_deref(), Some("ssn"));
+    assert_eq!(entries[1].principal, "dr.smith");
+    assert_eq!(entries[1].purpose, "treatment");
+
+    // Only fields that held ciphertext are recorded.
+    let partial = json!({"id": "2", "name": encrypted["name"], "ssn": "123-45-6789"});
+    polycrypt
+        .decrypt_record_audited("patient", &partial, &context)
+        .unwrap();
+    let entries: Vec<AuditEntry> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[2].key_id.as_deref(), Some("phi"));
+    assert_eq!(entries[2].fields, ["name"]);
+}
+
+#[test]
+fn test_schema_driven_field_encryption() {
+    let key = [0u8; 32];
+    let schema = json!({
+        "properties": {
+            "id": {"type": "string"},
+            "name": {"type": "string", "x-phi": true},
+            "address": {"properties": {"zip": {"type": "string", "x-phi": true}}},
+            "visits": {"items": {"properties": {"notes": {"type": "string", "x-phi": true}}}}
+        }
+    });
+    let fields = schema::phi_fields(&schema).unwrap();
+
+    let records = vec![
+        json!({"id": "1", "name": "John Doe", "address": {"zip": "12345"}, "visits": [{"notes": "a"}, {"notes": "b"}]}),
+        json!({"id": "2", "name": "Jane Smith", "visits": []}),
+    ];
+
+    let encrypted = encryption::encrypt_fields_in_batch(&records, &fields, &key).unwrap();
+    assert_eq!(encrypted[0]["id"], records[0]["id"]);
+    assert_ne!(encrypted[0]["address"]["zip"], records[0]["address"]["zip"]);
+    assert_ne!(
+        encrypted[0]["visits"][1]["notes"],
+        records[0]["visits"][1]["notes"]
+    );
+    assert_ne!(encrypted[1]["name"], records[1]["name"]);
+
+    let decrypted = encryption::decrypt_fields_in_batch(&encrypted, &fields, &key).unwrap();
+    assert_eq!(decrypted, records);
+}
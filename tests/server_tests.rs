@@ -0,0 +1,183 @@
+#![cfg(feature = "server")]
+
+use polycrypt_rs::server::{Server, MAX_BODY_LEN};
+use polycrypt_rs::Keyring;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+const TOKEN: &str = "test-token";
+
+fn send(
+    stream: &mut impl ReadWrite,
+    method: &str,
+    path: &str,
+    headers: &str,
+    body: &Value,
+) -> (String, Value) {
+    let body = body.to_string();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        headers,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.to_string(), serde_json::from_str(body).unwrap())
+}
+
+fn request(
+    stream: &mut impl ReadWrite,
+    host: &str,
+    method: &str,
+    path: &str,
+    body: &Value,
+) -> Value {
+    let headers = format!("Host: {}\r\nAuthorization: Bearer {}\r\n", host, TOKEN);
+    let (head, body) = send(stream, method, path, &headers, body);
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    body
+}
+
+trait ReadWrite: Read + Write {}
+impl<T: Read + Write> ReadWrite for T {}
+
+fn keyring() -> Keyring {
+    let mut keyring = Keyring::new();
+    keyring.insert("k1", [1u8; 32]);
+    keyring.set_primary("k1").unwrap();
+    keyring
+}
+
+#[test]
+fn test_server_over_tcp() {
+    let server = Server::bind("127.0.0.1:0", keyring(), TOKEN.to_string()).unwrap();
+    let addr = server.local_addr().unwrap();
+    let host = addr.to_string();
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| server.run(2));
+
+        let record = json!({"id": "1", "name": "John Doe"});
+        let encrypted = request(
+            &mut TcpStream::connect(addr).unwrap(),
+            &host,
+            "POST",
+            "/v1/encrypt_fields",
+            &json!({"record": record, "fields": ["name"]}),
+        );
+        assert_ne!(encrypted["record"]["name"], "John Doe");
+
+        let decrypted = request(
+            &mut TcpStream::connect(addr).unwrap(),
+            &format!("localhost:{}", addr.port()),
+            "POST",
+            "/v1/decrypt_fields",
+            &json!({"record": encrypted["record"], "fields": ["name"]}),
+        );
+        assert_eq!(decrypted["record"], record);
+
+        server.shutdown();
+    });
+}
+
+#[test]
+fn test_server_rejects_unauthorized_requests() {
+    let server = Server::bind("127.0.0.1:0", keyring(), TOKEN.to_string()).unwrap();
+    let addr = server.local_addr().unwrap();
+    let body = json!({"record": {"name": "John Doe"}, "fields": ["name"]});
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| server.run(1));
+
+        let host = format!("Host: {}\r\n", addr);
+        let (head, _) = send(
+            &mut TcpStream::connect(addr).unwrap(),
+            "POST",
+            "/v1/encrypt_fields",
+            &host,
+            &body,
+        );
+        assert!(head.starts_with("HTTP/1.1 401"), "{}", head);
+
+        let (head, _) = send(
+            &mut TcpStream::connect(addr).unwrap(),
+            "POST",
+            "/v1/encrypt_fields",
+            &format!("{}Authorization: Bearer wrong-token\r\n", host),
+            &body,
+        );
+        assert!(head.starts_with("HTTP/1.1 401"), "{}", head);
+
+        let (head, _) = send(
+            &mut TcpStream::connect(addr).unwrap(),
+            "POST",
+            "/v1/encrypt_fields",
+            &format!(
+                "Host: attacker.example:{}\r\nAuthorization: Bearer {}\r\n",
+                addr.port(),
+                TOKEN
+            ),
+            &body,
+        );
+        assert!(head.starts_with("HTTP/1.1 403"), "{}", head);
+
+        // Unauthenticated requests are refused before the server reads their body.
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST /v1/encrypt_fields HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n",
+            host, MAX_BODY_LEN
+        )
+        .unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8; 1];
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        assert!(head.starts_with(b"HTTP/1.1 401"));
+        drop(stream);
+
+        server.shutdown();
+    });
+}
+
+#[test]
+fn test_server_refuses_non_loopback_bind() {
+    assert!(Server::bind("0.0.0.0:0", keyring(), TOKEN.to_string()).is_err());
+    assert!(Server::bind("127.0.0.1:0", keyring(), String::new()).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_server_over_unix_socket() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("polycrypt.sock");
+    let server = Server::bind_unix(&path, keyring(), TOKEN.to_string()).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    // The socket is bound in a private directory, which is removed afterwards.
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    assert!(Server::bind_unix(&path, keyring(), TOKEN.to_string()).is_err());
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| server.run(1));
+
+        let mut stream = std::os::unix::net::UnixStream::connect(&path).unwrap();
+        assert_eq!(
+            request(&mut stream, "localhost", "GET", "/health", &json!({})),
+            json!({"status": "ok"})
+        );
+
+        server.shutdown();
+    });
+}
@@ -0,0 +1,58 @@
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+// Run with `make wasm-test`; wasm-bindgen-test executes these under Node.
+
+use polycrypt_rs::bindings::wasm;
+use serde_json::{json, Value};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+fn to_js(value: &Value) -> JsValue {
+    serde_wasm_bindgen::to_value(value).unwrap()
+}
+
+fn from_js(value: JsValue) -> Value {
+    serde_wasm_bindgen::from_value(value).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_wasm_encrypt_decrypt() {
+    let key = [0u8; 32];
+    let encrypted = wasm::encrypt(b"Hello, world!", &key).unwrap();
+    let decrypted = wasm::decrypt(&encrypted, &key).unwrap();
+    assert_eq!(decrypted, b"Hello, world!");
+
+    // The IV comes from crypto.getRandomValues, so two encryptions must differ.
+    assert_ne!(wasm::encrypt(b"Hello, world!", &key).unwrap(), encrypted);
+}
+
+#[wasm_bindgen_test]
+fn test_wasm_encrypt_decrypt_fields() {
+    let key = [0u8; 32];
+    let record = json!({"id": "1234", "name": "John Doe", "address": {"zip": "12345"}});
+    let fields = vec!["name".to_string(), "address.zip".to_string()];
+
+    let encrypted = from_js(wasm::encrypt_fields(to_js(&record), fields.clone(), &key).unwrap());
+    assert_ne!(encrypted["name"], "John Doe");
+    assert_eq!(encrypted["id"], "1234");
+
+    let decrypted = from_js(wasm::decrypt_fields(to_js(&encrypted), fields, &key).unwrap());
+    assert_eq!(decrypted, record);
+}
+
+#[wasm_bindgen_test]
+fn test_wasm_batch() {
+    let key = [0u8; 32];
+    let records = json!([{"id": "1", "name": "John Doe"}, {"id": "2", "name": "Jane Doe"}]);
+    let fields = vec!["name".to_string()];
+
+    let encrypted = wasm::encrypt_fields_in_batch(to_js(&records), fields.clone(), &key).unwrap();
+    let decrypted = from_js(wasm::decrypt_fields_in_batch(encrypted, fields, &key).unwrap());
+    assert_eq!(decrypted, records);
+}
+
+#[wasm_bindgen_test]
+fn test_wasm_invalid_key() {
+    assert!(wasm::encrypt(b"data", &[0u8; 16]).is_err());
+    assert!(wasm::decrypt(b"not ciphertext", &[0u8; 32]).is_err());
+}